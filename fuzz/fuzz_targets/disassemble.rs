@@ -0,0 +1,22 @@
+//! Feeds arbitrary bytes to the disassembler, which must either fail with an
+//! `Error` or produce instructions that can all be printed and re-encoded,
+//! without panicking.
+//!
+//! Run with `cargo fuzz run disassemble` from the repository root.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use stew3d::{disassemble, disassemble_with_labels, LabelOptions};
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(instrs) = disassemble(bytes) {
+        let mut encoded = Vec::new();
+        for ins in &instrs {
+            let _ = ins.to_string();
+            encoded.extend(ins.to_bytes());
+        }
+        assert_eq!(encoded, bytes);
+    }
+
+    let _ = disassemble_with_labels(bytes, &LabelOptions::default());
+});
@@ -0,0 +1,521 @@
+use crate::instr::Instruction::{self, *};
+use crate::instr::Operands::One;
+use crate::opcode::Opcode::{self, *};
+use crate::semantics::{Reg, RegSet};
+use std::ops::Range;
+
+/// Computes, for each entry in `instrs`, the set of registers that are live
+/// immediately after it executes: those whose current value may still be read
+/// before being overwritten.
+///
+/// This is a backward pass over the linear listing, so it is only precise
+/// within straight-line code. Wherever control may leave the fall-through path
+/// (any jump or call, `ret`, and `hlt`), every register is conservatively
+/// assumed to be live, since the code that runs next is not known. The zero
+/// register is never considered live, as it can't be written.
+pub fn live_after(instrs: &[Instruction]) -> Vec<RegSet> {
+    let all = RegSet::of(&Reg::ALL).difference(RegSet::of(&[Reg::Z]));
+    let mut live = vec![RegSet::default(); instrs.len()];
+    let mut next = all; // registers live on entry to the following instruction
+
+    for (i, ins) in instrs.iter().enumerate().rev() {
+        live[i] = if ins.ends_block() { all } else { next };
+
+        if let Jump(_, op, _, _) | Instr(_, op, _) = ins {
+            let sem = op.semantics();
+            next = live[i].difference(sem.writes).union(sem.reads);
+        }
+    }
+
+    live
+}
+
+/// Finds the instructions that write a register whose value is then
+/// overwritten (or otherwise never read) before any instruction reads it.
+/// The result holds indices into `instrs`.
+///
+/// This inherits the precision of [`live_after`]: a write is only reported
+/// when it is provably dead along the straight-line code that follows it.
+/// Flags are not tracked, so an instruction kept only for its effect on the
+/// flags (such as `dcr a` before a `jne`) may be reported.
+pub fn dead_writes(instrs: &[Instruction]) -> Vec<usize> {
+    let live = live_after(instrs);
+
+    instrs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, ins)| match ins {
+            Instr(_, op, _) => {
+                let writes = op.semantics().writes;
+                let dead = !writes.is_empty() && writes.difference(live[i]) == writes;
+                if dead {
+                    Some(i)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Determines whether any instruction in `instrs` has the opcode `op`.
+pub fn contains_opcode(instrs: &[Instruction], op: Opcode) -> bool {
+    !find_opcodes(instrs, &[op]).is_empty()
+}
+
+/// Finds the instructions with any of the opcodes in `ops`. The result holds
+/// indices into `instrs`.
+pub fn find_opcodes(instrs: &[Instruction], ops: &[Opcode]) -> Vec<usize> {
+    instrs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, ins)| match ins {
+            Jump(_, op, _, _) | Instr(_, op, _) if ops.contains(op) => Some(i),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Finds the unconditional `jmp`s whose target is also the target of some
+/// `call`: most likely tail calls, where a subroutine ends by jumping into
+/// another instead of calling it and returning. The result holds indices into
+/// `instrs`.
+pub fn find_tail_calls(instrs: &[Instruction]) -> Vec<usize> {
+    let call_targets: Vec<u8> = instrs
+        .iter()
+        .filter_map(|ins| match ins {
+            Jump(_, CALL, target, _) => Some(*target),
+            _ => None,
+        })
+        .collect();
+
+    instrs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, ins)| match ins {
+            Jump(_, JMP, target, _) if call_targets.contains(target) => Some(i),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Finds instructions that have no effect given the instruction just before
+/// them: `nop`s after the first in a run, and a `mov` that copies a value
+/// straight back to where it came from. The result holds indices into
+/// `instrs`.
+///
+/// An instruction at a label is never reported, since it may be reached by a
+/// jump rather than from the instruction before it.
+pub fn find_redundant(instrs: &[Instruction]) -> Vec<usize> {
+    pairs_in_a_row(instrs, has_no_effect)
+        .into_iter()
+        .map(|(_, i)| i)
+        .collect()
+}
+
+/// Finds pairs of instructions in a row that cancel each other out: two `not`s
+/// or two `neg`s of the same register. Unlike a [redundant](find_redundant)
+/// instruction, neither can be removed on its own, but both can be removed
+/// together. The result holds the indices of both instructions.
+///
+/// As with [`find_redundant`], an instruction at a label is never part of a
+/// pair. Of three in a row, only the first two are a pair.
+pub fn find_cancelling(instrs: &[Instruction]) -> Vec<(usize, usize)> {
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+    for (i, j) in pairs_in_a_row(instrs, cancels) {
+        if pairs.last().map(|&(_, last)| last) != Some(i) {
+            pairs.push((i, j));
+        }
+    }
+    pairs
+}
+
+/// Finds each instruction whose opcode is related to that of the instruction
+/// just before it by `related`, as a pair of indices into `instrs`.
+fn pairs_in_a_row<F>(instrs: &[Instruction], related: F) -> Vec<(usize, usize)>
+where
+    F: Fn(Opcode, Opcode) -> bool,
+{
+    let mut pairs = Vec::new();
+    let mut prev = None;
+
+    for (i, ins) in instrs.iter().enumerate() {
+        let op = match ins {
+            Label(_, _) => {
+                prev = None;
+                continue;
+            }
+            Jump(_, op, _, _) | Instr(_, op, _) => *op,
+        };
+
+        if let Some((j, prev_op)) = prev {
+            if related(prev_op, op) {
+                pairs.push((j, i));
+            }
+        }
+        prev = Some((i, op));
+    }
+
+    pairs
+}
+
+/// Determines whether `op` has no effect when it immediately follows `prev`,
+/// since what it would do is already done.
+fn has_no_effect(prev: Opcode, op: Opcode) -> bool {
+    matches!(
+        (prev, op),
+        (NOP, NOP)
+            | (MOV_A_B, MOV_B_A)
+            | (MOV_B_A, MOV_A_B)
+            | (MOV_A_C, MOV_C_A)
+            | (MOV_C_A, MOV_A_C)
+            | (MOV_B_C, MOV_C_B)
+            | (MOV_C_B, MOV_B_C)
+    )
+}
+
+/// Determines whether `op` undoes `prev` when it immediately follows it, so
+/// that the two together have no effect.
+fn cancels(prev: Opcode, op: Opcode) -> bool {
+    matches!(
+        (prev, op),
+        (NOT_A, NOT_A)
+            | (NOT_B, NOT_B)
+            | (NOT_C, NOT_C)
+            | (NEG_A, NEG_A)
+            | (NEG_B, NEG_B)
+            | (NEG_C, NEG_C)
+    )
+}
+
+/// Finds each `mvi` that is directly followed by an `addi`, `subi`, `ani`,
+/// `ori`, or `xri` of an immediate into the same register, as in `mvi 5, a;
+/// addi 3, a`. The pair could be folded into a single `mvi` of the result
+/// (see [`fold`]), as long as nothing relies on the flags the second
+/// instruction sets. The result holds the indices of both instructions.
+///
+/// As with [`find_redundant`], an instruction at a label is never part of a
+/// pair, since it may be reached by a jump with some other value loaded.
+pub fn find_foldable(instrs: &[Instruction]) -> Vec<(usize, usize)> {
+    instrs
+        .windows(2)
+        .enumerate()
+        .filter(|(_, pair)| fold(&pair[0], &pair[1]).is_some())
+        .map(|(i, _)| (i, i + 1))
+        .collect()
+}
+
+/// Folds an `mvi` and the immediate arithmetic or logic on the same register
+/// directly after it into a single `mvi` of the result, at the address of the
+/// first. Arithmetic wraps, as it does on the 3000. Returns `None` if the two
+/// can't be folded.
+///
+/// # Examples
+/// ```
+/// # use stew3d::analysis::fold;
+/// # use stew3d::instr::{Instruction::*, Operands::*};
+/// # use stew3d::opcode::Opcode::*;
+/// let mvi = Instr(0x00, MVI_A, One(0x05));
+/// assert_eq!(fold(&mvi, &Instr(0x02, ADDI_A, One(0x03))), Some(Instr(0x00, MVI_A, One(0x08))));
+/// assert_eq!(fold(&mvi, &Instr(0x02, ADDI_B, One(0x03))), None);
+/// ```
+pub fn fold(first: &Instruction, second: &Instruction) -> Option<Instruction> {
+    let (addr, mvi, value) = match first {
+        Instr(addr, op @ (MVI_A | MVI_B | MVI_C), One(value)) => (*addr, *op, *value),
+        _ => return None,
+    };
+    let (op, imm) = match second {
+        Instr(_, op, One(imm)) if op.semantics().writes == mvi.semantics().writes => (*op, *imm),
+        _ => return None,
+    };
+
+    let folded = match op {
+        ADDI_A | ADDI_B | ADDI_C | ADDI_SP => value.wrapping_add(imm),
+        SUBI_A | SUBI_B | SUBI_C | SUBI_SP => value.wrapping_sub(imm),
+        ANI_A | ANI_B | ANI_C => value & imm,
+        ORI_A | ORI_B | ORI_C => value | imm,
+        XRI_A | XRI_B | XRI_C => value ^ imm,
+        _ => return None,
+    };
+    Some(Instr(addr, mvi, One(folded)))
+}
+
+/// Finds the runs of at least `min_len` zero bytes in a program, which are
+/// more likely to be padding (as in a zero-filled ROM) than code, even though
+/// each decodes as `add a, a`. The result holds ranges of indices into
+/// `instrs`, in order. A label splits a run, since the code there is jumped to.
+pub fn find_padding(instrs: &[Instruction], min_len: usize) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for (i, ins) in instrs.iter().enumerate() {
+        if !matches!(ins, Instr(_, ADD_A_A, _)) {
+            if i - start >= min_len.max(1) {
+                runs.push(start..i);
+            }
+            start = i + 1;
+        }
+    }
+    if instrs.len() - start >= min_len.max(1) {
+        runs.push(start..instrs.len());
+    }
+    runs
+}
+
+/// Finds the runs of instructions that directly follow a `jmp` or `ret` with
+/// no label before them. Nothing falls through or jumps to them, so in a
+/// linear sweep they're more likely data (say, between two routines) that
+/// happened to decode than code. Each run ends at the next label. The result
+/// holds ranges of indices into `instrs`, in order.
+pub fn find_data_after_jumps(instrs: &[Instruction]) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (i, ins) in instrs.iter().enumerate() {
+        match ins {
+            Label(_, _) => {
+                if let Some(start) = start.take() {
+                    runs.push(start..i);
+                }
+            }
+            Jump(_, JMP, _, _) | Instr(_, RET, _) if start.is_none() => start = Some(i + 1),
+            _ => {}
+        }
+    }
+    if let Some(start) = start {
+        runs.push(start..instrs.len());
+    }
+    runs.retain(|run| !run.is_empty());
+    runs
+}
+
+/// Finds the runs of at least `min_len` `jmp`s and `call`s in a row, which
+/// are likely a dispatch table: code jumps to one of the entries, which passes
+/// control on to the routine it names. Labels don't split a run, as entries
+/// may be labeled. The result holds ranges of indices into `instrs`, from the
+/// first entry to the last, in order.
+pub fn find_jump_tables(instrs: &[Instruction], min_len: usize) -> Vec<Range<usize>> {
+    let mut tables = Vec::new();
+    // The current run's first entry, its last entry so far, and its length.
+    let mut run: Option<(usize, usize, usize)> = None;
+    for (i, ins) in instrs.iter().enumerate() {
+        match ins {
+            Jump(_, JMP, _, _) | Jump(_, CALL, _, _) => {
+                let (first, _, len) = run.unwrap_or((i, i, 0));
+                run = Some((first, i, len + 1));
+            }
+            Label(_, _) => {}
+            _ => {
+                if let Some((first, last, len)) = run.take() {
+                    if len >= min_len.max(1) {
+                        tables.push(first..last + 1);
+                    }
+                }
+            }
+        }
+    }
+    if let Some((first, last, len)) = run {
+        if len >= min_len.max(1) {
+            tables.push(first..last + 1);
+        }
+    }
+    tables
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::instr::Operands::*;
+
+    #[test]
+    fn overwritten_immediate_is_dead() {
+        // mvi 1, a; mvi 2, a; out a; hlt
+        let instrs = [
+            Instr(0x00, MVI_A, One(0x01)),
+            Instr(0x02, MVI_A, One(0x02)),
+            Instr(0x04, OUT_A, Zero),
+            Instr(0x05, HLT, Zero),
+        ];
+        assert_eq!(dead_writes(&instrs), vec![0]);
+        assert!(live_after(&instrs)[1].contains(Reg::A));
+        assert!(!live_after(&instrs)[0].contains(Reg::A));
+    }
+
+    #[test]
+    fn branches_keep_registers_live() {
+        // mvi 1, a; jmp l0; l0: mvi 2, a; hlt
+        let instrs = [
+            Instr(0x00, MVI_A, One(0x01)),
+            Jump(0x02, JMP, 0x04, "l0".into()),
+            Label(0x04, "l0".into()),
+            Instr(0x04, MVI_A, One(0x02)),
+            Instr(0x06, HLT, Zero),
+        ];
+        assert_eq!(dead_writes(&instrs), vec![]);
+    }
+
+    #[test]
+    fn jumps_to_subroutines_are_tail_calls() {
+        // call fn0; jmp l1; hlt; fn0: jmp fn0; l1: jmp fn0
+        let instrs = [
+            Jump(0x00, CALL, 0x05, "fn0".into()),
+            Jump(0x02, JMP, 0x07, "l1".into()),
+            Instr(0x04, HLT, Zero),
+            Label(0x05, "fn0".into()),
+            Jump(0x05, JMP, 0x05, "fn0".into()),
+            Label(0x07, "l1".into()),
+            Jump(0x07, JMP, 0x05, "fn0".into()),
+        ];
+        assert_eq!(find_tail_calls(&instrs), vec![4, 6]);
+    }
+
+    #[test]
+    fn double_not_cancels_out() {
+        // not a; not a; out a; nop; nop; nop; l0: nop; hlt
+        let instrs = [
+            Instr(0x00, NOT_A, Zero),
+            Instr(0x01, NOT_A, Zero),
+            Instr(0x02, OUT_A, Zero),
+            Instr(0x03, NOP, Zero),
+            Instr(0x04, NOP, Zero),
+            Instr(0x05, NOP, Zero),
+            Label(0x06, "l0".into()),
+            Instr(0x06, NOP, Zero),
+            Instr(0x07, HLT, Zero),
+        ];
+        assert_eq!(find_redundant(&instrs), vec![4, 5]);
+        assert_eq!(find_cancelling(&instrs), vec![(0, 1)]);
+
+        // neg b; neg b; neg b; neg c
+        let instrs = [
+            Instr(0x00, NEG_B, Zero),
+            Instr(0x01, NEG_B, Zero),
+            Instr(0x02, NEG_B, Zero),
+            Instr(0x03, NEG_C, Zero),
+        ];
+        assert_eq!(find_cancelling(&instrs), vec![(0, 1)]);
+        assert_eq!(find_redundant(&instrs), vec![]);
+    }
+
+    #[test]
+    fn mov_back_is_redundant() {
+        // mov a, b; mov b, a; mov b, c
+        let instrs = [
+            Instr(0x00, MOV_A_B, Zero),
+            Instr(0x01, MOV_B_A, Zero),
+            Instr(0x02, MOV_B_C, Zero),
+        ];
+        assert_eq!(find_redundant(&instrs), vec![1]);
+    }
+
+    #[test]
+    fn immediates_fold() {
+        // mvi 5, a; addi 3, a; mvi 1, b; subi 2, b; mvi 0, c; l0: ori 1, c;
+        // mvi 7, a; addi 1, b; mvi 255, a; xri 15, a
+        let instrs = [
+            Instr(0x00, MVI_A, One(0x05)),
+            Instr(0x02, ADDI_A, One(0x03)),
+            Instr(0x04, MVI_B, One(0x01)),
+            Instr(0x06, SUBI_B, One(0x02)),
+            Instr(0x08, MVI_C, One(0x00)),
+            Label(0x0a, "l0".into()),
+            Instr(0x0a, ORI_C, One(0x01)),
+            Instr(0x0c, MVI_A, One(0x07)),
+            Instr(0x0e, ADDI_B, One(0x01)),
+            Instr(0x10, MVI_A, One(0xff)),
+            Instr(0x12, XRI_A, One(0x0f)),
+        ];
+        assert_eq!(find_foldable(&instrs), vec![(0, 1), (2, 3), (9, 10)]);
+
+        assert_eq!(
+            fold(&instrs[0], &instrs[1]),
+            Some(Instr(0x00, MVI_A, One(0x08)))
+        );
+        // wraps around
+        assert_eq!(
+            fold(&instrs[2], &instrs[3]),
+            Some(Instr(0x04, MVI_B, One(0xff)))
+        );
+        assert_eq!(
+            fold(&instrs[9], &instrs[10]),
+            Some(Instr(0x10, MVI_A, One(0xf0)))
+        );
+    }
+
+    #[test]
+    fn zero_runs_are_padding() {
+        let zeros = |addr: usize, n: usize| (addr..addr + n).map(|addr| Instr(addr, ADD_A_A, Zero));
+
+        // hlt; 20 zeros; l0: 3 zeros; out a; 4 zeros
+        let mut instrs = vec![Instr(0x00, HLT, Zero)];
+        instrs.extend(zeros(0x01, 20));
+        instrs.push(Label(0x15, "l0".into()));
+        instrs.extend(zeros(0x15, 3));
+        instrs.push(Instr(0x18, OUT_A, Zero));
+        instrs.extend(zeros(0x19, 4));
+
+        assert_eq!(find_padding(&instrs, 16), vec![1..21]);
+        assert_eq!(find_padding(&instrs, 3), vec![1..21, 22..25, 26..30]);
+        assert_eq!(find_padding(&instrs[..1], 0), vec![]);
+    }
+
+    #[test]
+    fn data_after_jumps() {
+        // jmp l0; out a; hlt; l0: ret; out b; l1: hlt; jmp l1
+        let instrs = [
+            Jump(0x00, JMP, 0x04, "l0".into()),
+            Instr(0x02, OUT_A, Zero),
+            Instr(0x03, HLT, Zero),
+            Label(0x04, "l0".into()),
+            Instr(0x04, RET, Zero),
+            Instr(0x05, OUT_B, Zero),
+            Label(0x06, "l1".into()),
+            Instr(0x06, HLT, Zero),
+            Jump(0x07, JMP, 0x06, "l1".into()),
+        ];
+        assert_eq!(find_data_after_jumps(&instrs), vec![1..3, 5..6]);
+        assert_eq!(find_data_after_jumps(&instrs[..2]), vec![1..2]);
+        assert_eq!(find_data_after_jumps(&instrs[3..]), vec![2..3]);
+    }
+
+    #[test]
+    fn jump_tables() {
+        // hlt; jmp l0; l1: jmp l1; call l0; jmp l2; l0: out a; jmp l0; l2: hlt
+        let instrs = [
+            Instr(0x00, HLT, Zero),
+            Jump(0x01, JMP, 0x09, "l0".into()),
+            Label(0x03, "l1".into()),
+            Jump(0x03, JMP, 0x03, "l1".into()),
+            Jump(0x05, CALL, 0x09, "l0".into()),
+            Jump(0x07, JMP, 0x0c, "l2".into()),
+            Label(0x09, "l0".into()),
+            Instr(0x09, OUT_A, Zero),
+            Jump(0x0a, JMP, 0x09, "l0".into()),
+            Label(0x0c, "l2".into()),
+            Instr(0x0c, HLT, Zero),
+        ];
+        assert_eq!(find_jump_tables(&instrs, 3), vec![1..6]);
+        assert_eq!(find_jump_tables(&instrs, 1), vec![1..6, 8..9]);
+        assert_eq!(find_jump_tables(&instrs[..10], 1), vec![1..6, 8..9]);
+        assert_eq!(find_jump_tables(&instrs, 5), vec![]);
+    }
+
+    #[test]
+    fn opcode_queries() {
+        // mvi 1, a; out a; jmp l0; l0: hlt
+        let instrs = [
+            Instr(0x00, MVI_A, One(0x01)),
+            Instr(0x02, OUT_A, Zero),
+            Jump(0x03, JMP, 0x05, "l0".into()),
+            Label(0x05, "l0".into()),
+            Instr(0x05, HLT, Zero),
+        ];
+        assert!(contains_opcode(&instrs, OUT_A));
+        assert!(contains_opcode(&instrs, JMP));
+        assert!(!contains_opcode(&instrs, OUT_B));
+        assert_eq!(
+            find_opcodes(&instrs, &Opcode::with_mnemonic("out")),
+            vec![1]
+        );
+        assert_eq!(find_opcodes(&instrs, &[JMP, HLT]), vec![2, 4]);
+    }
+}
@@ -0,0 +1,333 @@
+use crate::instr::Instruction::{self, *};
+use crate::opcode::Opcode::{self, *};
+use crate::semantics::Reg;
+use std::fmt;
+
+/// Represents the errors that can occur when building a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// An instruction doesn't exist in the instruction set (e.g. `out sp`).
+    /// Holds the instruction as it would be written.
+    InvalidInstruction(String),
+    /// A jump refers to a label that was never placed.
+    UndefinedLabel(String),
+    /// The same label was placed more than once.
+    DuplicateLabel(String),
+    /// A label is past the last address a jump can reach (`0xff`). Holds the
+    /// label and its address.
+    OutOfRange(String, usize),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidInstruction(ins) => write!(f, "`{}` is not a valid instruction", ins),
+            Self::UndefinedLabel(label) => write!(f, "label `{}` is never defined", label),
+            Self::DuplicateLabel(label) => write!(f, "label `{}` is defined more than once", label),
+            Self::OutOfRange(label, addr) => write!(
+                f,
+                "label `{}` is at 0x{:x}, beyond the reach of a jump",
+                label, addr
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Assembles a program from Rust code, one instruction per method call, which
+/// is handier for tests and examples than writing out hex by hand. Jumps refer
+/// to labels by name, and are resolved when the program is built.
+///
+/// Methods are provided for the common instructions; any other instruction
+/// can be added by opcode with [`op`](ProgramBuilder::op). Mistakes, such as
+/// asking for an instruction that doesn't exist, are reported by
+/// [`build`](ProgramBuilder::build).
+///
+/// # Examples
+/// ```
+/// # use stew3d::builder::ProgramBuilder;
+/// # use stew3d::semantics::Reg;
+/// let bytes = ProgramBuilder::new()
+///     .mvi(10, Reg::A)
+///     .call("add_4")
+///     .hlt()
+///     .label("add_4")
+///     .addi(4, Reg::A)
+///     .ret()
+///     .build();
+/// assert_eq!(bytes, Ok(vec![0x7f, 0x0a, 0xbc, 0x05, 0xc7, 0x0c, 0x04, 0xbd]));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ProgramBuilder {
+    /// The instructions so far, with jumps holding their target label and
+    /// labels at the address of the next instruction.
+    instrs: Vec<Instruction>,
+    /// The address of the next instruction.
+    addr: usize,
+    /// The first mistake made, if any, to be reported when building.
+    error: Option<BuildError>,
+}
+
+impl ProgramBuilder {
+    /// Starts an empty program.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Places a label at the address of the next instruction.
+    pub fn label(mut self, name: &str) -> Self {
+        self.instrs.push(Label(self.addr, name.to_string()));
+        self
+    }
+
+    /// Adds an instruction by opcode, with the given operand bytes. The number
+    /// of operands must match the opcode. Jumps must be added with
+    /// [`jump`](ProgramBuilder::jump) instead, so that their target is resolved.
+    pub fn op(self, op: Opcode, operands: &[u8]) -> Self {
+        let mut bytes = vec![op.to_byte()];
+        bytes.extend_from_slice(operands);
+
+        match crate::decode_one(&bytes, 0) {
+            Ok(ins @ Instr(_, _, _)) if ins.size() == bytes.len() => self.push(ins),
+            _ => self.fail(format!("{:?} {:?}", op, operands)),
+        }
+    }
+
+    /// Adds a jump (or call) with the given opcode to a label.
+    pub fn jump(self, op: Opcode, label: &str) -> Self {
+        if op.is_jump() {
+            self.push(Jump(0, op, 0, label.to_string()))
+        } else {
+            self.fail(format!("{:?} {}", op, label))
+        }
+    }
+
+    /// Adds a (non-jump) instruction written in assembly, as the disassembler
+    /// prints it.
+    fn asm(self, text: String) -> Self {
+        match text.parse() {
+            Ok(ins @ Instr(_, _, _)) => self.push(ins),
+            _ => self.fail(text),
+        }
+    }
+
+    /// Adds an instruction at the next address, whatever address it has.
+    fn push(mut self, ins: Instruction) -> Self {
+        let ins = match ins {
+            Jump(_, op, target, label) => Jump(self.addr, op, target, label),
+            Instr(_, op, operands) => Instr(self.addr, op, operands),
+            Label(_, name) => Label(self.addr, name),
+        };
+        self.addr += ins.size();
+        self.instrs.push(ins);
+        self
+    }
+
+    /// Records that `ins` isn't a valid instruction, unless an earlier
+    /// mistake has already been recorded.
+    fn fail(mut self, ins: String) -> Self {
+        self.error
+            .get_or_insert(BuildError::InvalidInstruction(ins));
+        self
+    }
+
+    /// `mvi imm, reg`
+    pub fn mvi(self, imm: u8, reg: Reg) -> Self {
+        self.asm(format!("mvi {}, {}", imm, reg))
+    }
+
+    /// `addi imm, reg`
+    pub fn addi(self, imm: u8, reg: Reg) -> Self {
+        self.asm(format!("addi {}, {}", imm, reg))
+    }
+
+    /// `subi imm, reg`
+    pub fn subi(self, imm: u8, reg: Reg) -> Self {
+        self.asm(format!("subi {}, {}", imm, reg))
+    }
+
+    /// `mov src, dst`
+    pub fn mov(self, src: Reg, dst: Reg) -> Self {
+        self.asm(format!("mov {}, {}", src, dst))
+    }
+
+    /// `add src, dst`
+    pub fn add(self, src: Reg, dst: Reg) -> Self {
+        self.asm(format!("add {}, {}", src, dst))
+    }
+
+    /// `cmp x, y`
+    pub fn cmp(self, x: Reg, y: Reg) -> Self {
+        self.asm(format!("cmp {}, {}", x, y))
+    }
+
+    /// `inr reg`
+    pub fn inr(self, reg: Reg) -> Self {
+        self.asm(format!("inr {}", reg))
+    }
+
+    /// `dcr reg`
+    pub fn dcr(self, reg: Reg) -> Self {
+        self.asm(format!("dcr {}", reg))
+    }
+
+    /// `out reg`
+    pub fn out(self, reg: Reg) -> Self {
+        self.asm(format!("out {}", reg))
+    }
+
+    /// `jmp label`
+    pub fn jmp(self, label: &str) -> Self {
+        self.jump(JMP, label)
+    }
+
+    /// `je label`
+    pub fn je(self, label: &str) -> Self {
+        self.jump(JE, label)
+    }
+
+    /// `jne label`
+    pub fn jne(self, label: &str) -> Self {
+        self.jump(JNE, label)
+    }
+
+    /// `call label`
+    pub fn call(self, label: &str) -> Self {
+        self.jump(CALL, label)
+    }
+
+    /// `ret`
+    pub fn ret(self) -> Self {
+        self.op(RET, &[])
+    }
+
+    /// `nop`
+    pub fn nop(self) -> Self {
+        self.op(NOP, &[])
+    }
+
+    /// `hlt`
+    pub fn hlt(self) -> Self {
+        self.op(HLT, &[])
+    }
+
+    /// Resolves every jump to its label and encodes the program.
+    pub fn build(self) -> Result<Vec<u8>, BuildError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        let entries = self.instrs.into_iter().map(|ins| ((), ins)).collect();
+        let instrs = bind_jump_targets(entries, false).map_err(|((), error)| error)?;
+        Ok(instrs.iter().flat_map(Instruction::to_bytes).collect())
+    }
+}
+
+/// Points every jump in a program with addresses at the address of the label
+/// it names. Each entry comes with where it was written (e.g. a line number),
+/// which is returned along with the first label placed twice or jump that
+/// can't be resolved. If `numeric_targets` is set, a jump whose label starts
+/// with a digit is a jump to that number (as written in source), and keeps the
+/// target it has.
+pub(crate) fn bind_jump_targets<L: Copy>(
+    entries: Vec<(L, Instruction)>,
+    numeric_targets: bool,
+) -> Result<Vec<Instruction>, (L, BuildError)> {
+    let mut labels = Vec::new();
+    for (at, ins) in &entries {
+        if let Label(addr, name) = ins {
+            if labels.iter().any(|(_, other)| other == name) {
+                return Err((*at, BuildError::DuplicateLabel(name.clone())));
+            }
+            labels.push((*addr, name.clone()));
+        }
+    }
+
+    let mut instrs = Vec::with_capacity(entries.len());
+    for (at, ins) in entries {
+        let ins = match ins {
+            Jump(addr, op, target, label) => {
+                let target = match labels.iter().find(|(_, name)| *name == label) {
+                    Some(&(target, _)) if target <= 0xff => target as u8,
+                    Some(&(target, _)) => return Err((at, BuildError::OutOfRange(label, target))),
+                    None if numeric_targets && label.starts_with(|c: char| c.is_ascii_digit()) => {
+                        target
+                    }
+                    None => return Err((at, BuildError::UndefinedLabel(label))),
+                };
+                Jump(addr, op, target, label)
+            }
+            ins => ins,
+        };
+        instrs.push(ins);
+    }
+    Ok(instrs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builds_loop_program() {
+        // the program from the `stats` tests
+        let bytes = ProgramBuilder::new()
+            .mvi(255, Reg::A)
+            .label("l0")
+            .out(Reg::A)
+            .dcr(Reg::A)
+            .cmp(Reg::A, Reg::Z)
+            .jne("l0")
+            .hlt()
+            .build();
+        assert_eq!(
+            bytes,
+            Ok(vec![0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7])
+        );
+    }
+
+    #[test]
+    fn forward_jumps_and_raw_opcodes() {
+        let bytes = ProgramBuilder::new()
+            .jmp("end")
+            .op(STSI, &[0x03, 0x07])
+            .label("end")
+            .hlt()
+            .build();
+        assert_eq!(bytes, Ok(vec![0xb1, 0x05, 0x9e, 0x03, 0x07, 0xc7]));
+    }
+
+    #[test]
+    fn build_errors() {
+        assert_eq!(
+            ProgramBuilder::new().jmp("nowhere").build(),
+            Err(BuildError::UndefinedLabel(String::from("nowhere")))
+        );
+        assert_eq!(
+            ProgramBuilder::new().label("a").nop().label("a").build(),
+            Err(BuildError::DuplicateLabel(String::from("a")))
+        );
+        assert_eq!(
+            ProgramBuilder::new().out(Reg::Sp).build(),
+            Err(BuildError::InvalidInstruction(String::from("out sp")))
+        );
+        assert_eq!(
+            ProgramBuilder::new().op(HLT, &[0x01]).build(),
+            Err(BuildError::InvalidInstruction(String::from("HLT [1]")))
+        );
+        assert_eq!(
+            ProgramBuilder::new().op(JMP, &[0x00]).build(),
+            Err(BuildError::InvalidInstruction(String::from("JMP [0]")))
+        );
+
+        let mut far = ProgramBuilder::new().jmp("far");
+        for _ in 0..0x100 {
+            far = far.nop();
+        }
+        assert_eq!(
+            far.label("far").hlt().build(),
+            Err(BuildError::OutOfRange(String::from("far"), 0x102))
+        );
+    }
+}
@@ -0,0 +1,436 @@
+use crate::instr::Instruction::{self, *};
+use crate::semantics::RegSet;
+use std::collections::{BTreeSet, VecDeque};
+use std::ops::Range;
+
+/// A maximal run of instructions that control can only enter at the start of
+/// and only leave at the end of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// The indices of this block's entries (including any leading label) in
+    /// the instruction list the graph was built from.
+    pub range: Range<usize>,
+    /// The address of the first instruction in the block.
+    pub start: usize,
+    /// The address just past the last instruction in the block.
+    pub end: usize,
+    /// The name of the label at the start of the block, if any.
+    pub label: Option<String>,
+    /// The blocks that control may pass to after this one. Calls are not
+    /// followed, as they return to the instruction after the call.
+    pub successors: Vec<usize>,
+}
+
+/// A subroutine: the blocks reachable from a single entry point without
+/// following calls or entering another subroutine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    /// The label at the entry point, or `main` if the top-level code is
+    /// unlabeled.
+    pub name: String,
+    /// The block at which the function is entered.
+    pub entry: usize,
+    /// Every block belonging to the function, in address order.
+    pub blocks: Vec<usize>,
+}
+
+/// A natural loop: a header block, and the blocks that can reach a jump back
+/// to the header without passing through it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    /// The block at which the loop is entered, which dominates every block in
+    /// the loop.
+    pub header: usize,
+    /// Every block in the loop, including the header, in address order.
+    pub blocks: Vec<usize>,
+}
+
+/// The control-flow graph of a disassembled program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    /// The program's basic blocks, in address order.
+    pub blocks: Vec<BasicBlock>,
+    /// The blocks that are the target of at least one `call`, in address order.
+    pub call_targets: Vec<usize>,
+}
+
+impl Cfg {
+    /// Splits the given (labeled) program into basic blocks and connects them.
+    /// Jumps to addresses that don't start an instruction have no edge.
+    pub fn new(instrs: &[Instruction]) -> Self {
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        let mut block_start = 0;
+
+        for (i, ins) in instrs.iter().enumerate() {
+            let ends_block = match instrs.get(i + 1) {
+                None | Some(Label(_, _)) => true,
+                Some(_) => ins.ends_block(),
+            };
+
+            if ends_block {
+                let start = instrs[block_start].addr();
+                blocks.push(BasicBlock {
+                    range: block_start..i + 1,
+                    start,
+                    end: ins.addr() + ins.size(),
+                    label: match &instrs[block_start] {
+                        Label(_, name) => Some(name.clone()),
+                        _ => None,
+                    },
+                    successors: Vec::new(),
+                });
+                block_start = i + 1;
+            }
+        }
+
+        let block_at = |addr: usize| blocks.iter().position(|block| block.start == addr);
+        let mut call_targets = BTreeSet::new();
+        let mut edges = Vec::with_capacity(blocks.len());
+
+        for (i, block) in blocks.iter().enumerate() {
+            let fall_through = if i + 1 < blocks.len() {
+                Some(i + 1)
+            } else {
+                None
+            };
+
+            let last = &instrs[block.range.end - 1];
+            let target = match last {
+                Jump(_, _, target, _) => block_at(*target as usize),
+                _ => None,
+            };
+
+            let mut successors = Vec::new();
+            if last.is_call() {
+                call_targets.extend(target);
+            } else {
+                successors.extend(target);
+            }
+            if !last.is_terminator() {
+                if let Some(next) = fall_through {
+                    if !successors.contains(&next) {
+                        successors.insert(0, next);
+                    }
+                }
+            }
+            edges.push(successors);
+        }
+
+        for (block, successors) in blocks.iter_mut().zip(edges) {
+            block.successors = successors;
+        }
+
+        Cfg {
+            blocks,
+            call_targets: call_targets.into_iter().collect(),
+        }
+    }
+
+    /// Groups the blocks into functions: the top-level code entered at the
+    /// first block comes first, followed by each `call` target in address order.
+    ///
+    /// Each function claims the blocks reachable from its entry that haven't
+    /// been claimed by an earlier function, without entering another function's
+    /// entry, so code only reachable by falling through from a function stays
+    /// with that function. Unreachable blocks stay with the function owning the
+    /// block just before them.
+    pub fn functions(&self) -> Vec<Function> {
+        if self.blocks.is_empty() {
+            return Vec::new();
+        }
+
+        let mut entries = vec![0];
+        entries.extend(self.call_targets.iter().filter(|&&b| b != 0));
+
+        let mut owner: Vec<Option<usize>> = vec![None; self.blocks.len()];
+        for (f, &entry) in entries.iter().enumerate() {
+            let mut worklist = VecDeque::from(vec![entry]);
+            while let Some(b) = worklist.pop_front() {
+                if owner[b].is_some() || (b != entry && entries.contains(&b)) {
+                    continue;
+                }
+                owner[b] = Some(f);
+                worklist.extend(&self.blocks[b].successors);
+            }
+        }
+
+        let mut functions: Vec<Function> = entries
+            .iter()
+            .map(|&entry| Function {
+                name: self.blocks[entry]
+                    .label
+                    .clone()
+                    .unwrap_or_else(|| String::from("main")),
+                entry,
+                blocks: Vec::new(),
+            })
+            .collect();
+
+        let mut prev_owner = 0;
+        for (b, owner) in owner.into_iter().enumerate() {
+            let f = owner.unwrap_or(prev_owner);
+            functions[f].blocks.push(b);
+            prev_owner = f;
+        }
+
+        functions
+    }
+
+    /// Finds the dominators of each block: the blocks that control must pass
+    /// through to reach it, from the start of the program or of a subroutine.
+    /// Every reachable block dominates itself. Unreachable blocks have no
+    /// dominators.
+    pub fn dominators(&self) -> Vec<BTreeSet<usize>> {
+        let n = self.blocks.len();
+        let mut roots = vec![0];
+        roots.extend(self.call_targets.iter().filter(|&&b| b != 0));
+
+        let mut reachable = vec![false; n];
+        let mut worklist: VecDeque<usize> = roots.iter().copied().filter(|&b| b < n).collect();
+        while let Some(b) = worklist.pop_front() {
+            if !reachable[b] {
+                reachable[b] = true;
+                worklist.extend(&self.blocks[b].successors);
+            }
+        }
+
+        let mut preds = vec![Vec::new(); n];
+        for (b, block) in self.blocks.iter().enumerate() {
+            for &succ in &block.successors {
+                preds[succ].push(b);
+            }
+        }
+
+        // Start from "everything dominates everything" and narrow each block
+        // down to the blocks dominating all of its predecessors, until nothing
+        // changes.
+        let all: BTreeSet<usize> = (0..n).filter(|&b| reachable[b]).collect();
+        let mut doms: Vec<BTreeSet<usize>> = (0..n)
+            .map(|b| {
+                if !reachable[b] {
+                    BTreeSet::new()
+                } else if roots.contains(&b) {
+                    std::iter::once(b).collect()
+                } else {
+                    all.clone()
+                }
+            })
+            .collect();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in (0..n).filter(|&b| reachable[b] && !roots.contains(&b)) {
+                let mut new = preds[b]
+                    .iter()
+                    .filter(|&&p| reachable[p])
+                    .map(|&p| doms[p].clone())
+                    .fold(None, |acc: Option<BTreeSet<usize>>, d| match acc {
+                        None => Some(d),
+                        Some(acc) => Some(acc.intersection(&d).copied().collect()),
+                    })
+                    .unwrap_or_default();
+                new.insert(b);
+                if new != doms[b] {
+                    doms[b] = new;
+                    changed = true;
+                }
+            }
+        }
+
+        doms
+    }
+
+    /// Collects, for each block, the registers that any of its instructions
+    /// read or write, according to their semantics. The `len()` of each set is
+    /// the number of registers (out of five) competing for use in that block,
+    /// which makes it a simple measure of register pressure.
+    ///
+    /// `instrs` must be the instructions the graph was built from.
+    pub fn block_register_pressure(&self, instrs: &[Instruction]) -> Vec<RegSet> {
+        self.blocks
+            .iter()
+            .map(|block| {
+                instrs[block.range.clone()]
+                    .iter()
+                    .fold(RegSet::default(), |used, ins| match ins {
+                        Jump(_, op, _, _) | Instr(_, op, _) => {
+                            let sem = op.semantics();
+                            used.union(sem.reads).union(sem.writes)
+                        }
+                        Label(_, _) => used,
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Finds the natural loops of a program: for each edge from a block back to
+/// one of its dominators (the loop header), the blocks that can reach that
+/// edge without passing through the header. Loops sharing a header are
+/// merged into one, and the loops are returned in order of their header.
+pub fn find_loops(cfg: &Cfg) -> Vec<Loop> {
+    let doms = cfg.dominators();
+    let mut loops: Vec<Loop> = Vec::new();
+
+    for (b, block) in cfg.blocks.iter().enumerate() {
+        for &header in block.successors.iter().filter(|&&h| doms[b].contains(&h)) {
+            let mut body: BTreeSet<usize> = std::iter::once(header).collect();
+            let mut worklist = vec![b];
+            while let Some(b) = worklist.pop() {
+                if body.insert(b) {
+                    worklist.extend(
+                        cfg.blocks
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, pred)| pred.successors.contains(&b))
+                            .map(|(p, _)| p),
+                    );
+                }
+            }
+
+            match loops.iter_mut().find(|l| l.header == header) {
+                Some(l) => {
+                    body.extend(&l.blocks);
+                    l.blocks = body.into_iter().collect();
+                }
+                None => loops.push(Loop {
+                    header,
+                    blocks: body.into_iter().collect(),
+                }),
+            }
+        }
+    }
+
+    loops.sort_by_key(|l| l.header);
+    loops
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::disassemble;
+
+    #[test]
+    fn blocks_and_edges() {
+        // 00:    7f ff    |   mvi 255, a
+        // 02:             | l0:
+        // 02:    be       |   out a
+        // 03:    67       |   dcr a
+        // 04:    a1       |   cmp a, z
+        // 05:    b3 02    |   jne l0
+        // 07:    c7       |   hlt
+        let b = [0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7];
+        let cfg = Cfg::new(&disassemble(&b).unwrap());
+
+        let spans: Vec<_> = cfg.blocks.iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(spans, vec![(0x00, 0x02), (0x02, 0x07), (0x07, 0x08)]);
+        assert_eq!(cfg.blocks[0].successors, vec![1]);
+        assert_eq!(cfg.blocks[1].successors, vec![2, 1]);
+        assert_eq!(cfg.blocks[1].label, Some(String::from("l0")));
+        assert!(cfg.blocks[2].successors.is_empty());
+    }
+
+    #[test]
+    fn single_loop() {
+        // the program from `blocks_and_edges`
+        let b = [0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7];
+        let cfg = Cfg::new(&disassemble(&b).unwrap());
+
+        let doms = cfg.dominators();
+        assert_eq!(doms[1], vec![0, 1].into_iter().collect());
+        assert_eq!(doms[2], vec![0, 1, 2].into_iter().collect());
+
+        let loops = find_loops(&cfg);
+        assert_eq!(
+            loops,
+            vec![Loop {
+                header: 1,
+                blocks: vec![1],
+            }]
+        );
+        assert_eq!(cfg.blocks[loops[0].header].label, Some(String::from("l0")));
+    }
+
+    #[test]
+    fn nested_loops() {
+        // 00:             | l0:
+        // 00:    5b       |   inr a
+        // 01:             | l1:
+        // 01:    5c       |   inr b
+        // 02:    b3 01    |   jne l1
+        // 04:    b3 00    |   jne l0
+        // 06:    c7       |   hlt
+        let b = [0x5b, 0x5c, 0xb3, 0x01, 0xb3, 0x00, 0xc7];
+        let cfg = Cfg::new(&disassemble(&b).unwrap());
+        let spans: Vec<_> = cfg.blocks.iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(
+            spans,
+            vec![(0x00, 0x01), (0x01, 0x04), (0x04, 0x06), (0x06, 0x07)]
+        );
+
+        assert_eq!(
+            find_loops(&cfg),
+            vec![
+                Loop {
+                    header: 0,
+                    blocks: vec![0, 1, 2],
+                },
+                Loop {
+                    header: 1,
+                    blocks: vec![1],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn register_pressure() {
+        use crate::instr::Operands::*;
+        use crate::opcode::Opcode::*;
+        use crate::semantics::Reg;
+
+        let instrs = [
+            Instr(0x00, MOV_A_B, Zero),
+            Instr(0x01, ADD_A_SP, Zero),
+            Instr(0x02, HLT, Zero),
+            Label(0x03, String::from("l0")),
+            Instr(0x03, OUT_C, Zero),
+            Instr(0x04, RET, Zero),
+        ];
+        let cfg = Cfg::new(&instrs);
+
+        let pressure = cfg.block_register_pressure(&instrs);
+        assert_eq!(pressure[0], RegSet::of(&[Reg::A, Reg::B, Reg::Sp]));
+        assert_eq!(pressure[0].len(), 3);
+        assert_eq!(pressure[1], RegSet::of(&[Reg::C, Reg::Sp])); // `ret` pops the stack
+    }
+
+    #[test]
+    fn groups_subroutines() {
+        // 00:    7f 0a    |   mvi 10, a
+        // 02:    bc 05    |   call l0
+        // 04:    c7       |   hlt
+        // 05:             | l0:
+        // 05:    0c 04    |   addi 4, a
+        // 07:    bd       |   ret
+        let b = [0x7f, 0x0a, 0xbc, 0x05, 0xc7, 0x0c, 0x04, 0xbd];
+        let cfg = Cfg::new(&disassemble(&b).unwrap());
+
+        assert_eq!(
+            cfg.functions(),
+            vec![
+                Function {
+                    name: String::from("main"),
+                    entry: 0,
+                    blocks: vec![0, 1],
+                },
+                Function {
+                    name: String::from("l0"),
+                    entry: 2,
+                    blocks: vec![2],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::instr::Instruction;
+
+/// One line of an instruction-level diff between two programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change<'a> {
+    /// An instruction found in both programs, as it appears in the old and
+    /// the new one (its address may differ between the two).
+    Same(&'a Instruction, &'a Instruction),
+    /// An instruction only found in the old program.
+    Removed(&'a Instruction),
+    /// An instruction only found in the new program.
+    Added(&'a Instruction),
+}
+
+/// Compares two disassembled programs by the canonical text of their
+/// instructions, aligning them along a longest common subsequence so that code
+/// shifted to a new address by an insertion still lines up. Where the programs
+/// differ, removals are listed before the additions that replace them.
+pub fn diff<'a>(old: &'a [Instruction], new: &'a [Instruction]) -> Vec<Change<'a>> {
+    let old_text: Vec<_> = old.iter().map(Instruction::canonical).collect();
+    let new_text: Vec<_> = new.iter().map(Instruction::canonical).collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..]
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old_text[i] == new_text[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old_text[i] == new_text[j] {
+            changes.push(Change::Same(&old[i], &new[j]));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            changes.push(Change::Removed(&old[i]));
+            i += 1;
+        } else {
+            changes.push(Change::Added(&new[j]));
+            j += 1;
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::disassemble;
+
+    #[test]
+    fn one_changed_instruction() {
+        // the second program increments `a` instead of decrementing it
+        let old = disassemble(&[0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7]).unwrap();
+        let new = disassemble(&[0x7f, 0xff, 0xbe, 0x5b, 0xa1, 0xb3, 0x02, 0xc7]).unwrap();
+
+        let changes = diff(&old, &new);
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|change| match change {
+                Change::Same(ins, _) => format!("  {}", ins.canonical()),
+                Change::Removed(ins) => format!("- {}", ins.canonical()),
+                Change::Added(ins) => format!("+ {}", ins.canonical()),
+            })
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                "  mvi 255, a",
+                "  l0:",
+                "  out a",
+                "- dcr a",
+                "+ inr a",
+                "  cmp a, z",
+                "  jne l0",
+                "  hlt",
+            ]
+        );
+    }
+
+    #[test]
+    fn shifted_instructions_stay_aligned() {
+        // the second program has a `nop` inserted at the start
+        let old = disassemble(&[0xbe, 0xc7]).unwrap();
+        let new = disassemble(&[0xc8, 0xbe, 0xc7]).unwrap();
+
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                Change::Added(&new[0]),
+                Change::Same(&old[0], &new[1]),
+                Change::Same(&old[1], &new[2]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use crate::instr::Instruction::{self, *};
+use crate::listing::AddrRadix;
+
+/// The number of data bytes in each S-record, as is conventional.
+const SREC_DATA_LEN: usize = 16;
+
+/// Encodes a program as Motorola S-records: `S1` data records with 16-bit
+/// addresses, followed by an `S9` record giving 0 as the start address. The
+/// bytes are taken from the instructions themselves, so labels take up no
+/// space.
+///
+/// # Examples
+/// ```
+/// # use stew3d::emit::srec;
+/// # use stew3d::disassemble;
+/// // outi 1; hlt
+/// let instrs = disassemble(&[0xc1, 0x01, 0xc7]).unwrap();
+/// assert_eq!(srec(&instrs), "S1060000C101C770\nS9030000FC\n");
+/// ```
+pub fn srec(instrs: &[Instruction]) -> String {
+    let bytes: Vec<u8> = instrs.iter().flat_map(|ins| ins.to_bytes()).collect();
+
+    let mut records = String::new();
+    for (i, data) in bytes.chunks(SREC_DATA_LEN).enumerate() {
+        records.push_str(&srec_record('1', (i * SREC_DATA_LEN) as u16, data));
+    }
+    records.push_str(&srec_record('9', 0, &[]));
+    records
+}
+
+/// Formats a single S-record of the given type. The byte count covers the
+/// address, data, and checksum, and the checksum is the ones' complement of
+/// the low byte of the sum of every byte before it (count included).
+fn srec_record(kind: char, addr: u16, data: &[u8]) -> String {
+    let mut fields = vec![(data.len() + 3) as u8];
+    fields.extend_from_slice(&addr.to_be_bytes());
+    fields.extend_from_slice(data);
+
+    let sum = fields.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    fields.push(!sum);
+
+    let hex: String = fields.iter().map(|b| format!("{:02X}", b)).collect();
+    format!("S{}{}\n", kind, hex)
+}
+
+/// Renders a program as a standalone HTML page, with the listing in a `<pre>`
+/// block. Each label is an anchor (`id="l0"`), and each jump or call target
+/// links to the label it jumps to, with the numeric target as its tooltip.
+/// Addresses are written in `radix`.
+///
+/// # Examples
+/// ```
+/// # use stew3d::emit::html;
+/// # use stew3d::disassemble;
+/// # use stew3d::listing::AddrRadix;
+/// // l0: jmp l0
+/// let page = html(&disassemble(&[0xb1, 0x00]).unwrap(), AddrRadix::Hex);
+/// assert!(page.contains(r##"<a href="#l0" title="0x00">l0</a>"##));
+/// ```
+pub fn html(instrs: &[Instruction], radix: AddrRadix) -> String {
+    let mut page = String::from(HTML_HEAD);
+    for ins in instrs {
+        let line = match ins {
+            Label(_, name) => format!("<span id=\"{}\">{}:</span>", escape(name), escape(name)),
+            Jump(addr, op, target, name) => format!(
+                "{}:  {} <a href=\"#{}\" title=\"{}\">{}</a>",
+                radix.column(*addr),
+                op,
+                escape(name),
+                radix.reference(*target as usize),
+                escape(name)
+            ),
+            Instr(addr, _, _) => format!("{}:  {}", radix.column(*addr), escape(&ins.canonical())),
+        };
+        page.push_str(&line);
+        page.push('\n');
+    }
+    page.push_str(HTML_TAIL);
+    page
+}
+
+/// Renders a program as JSON, for other tools to read: an object with the
+/// `instructions` (and labels) in order, and a `byte_map` classifying each of
+/// the `image_len` bytes of the image the program was decoded from. Each byte
+/// is an `opcode` or `operand` of the instruction at `addr`, or `data` (with a
+/// null `addr`) if no instruction covers it, as when lenient decoding stops
+/// early. Offsets into the image are taken to be addresses.
+///
+/// # Examples
+/// ```
+/// # use stew3d::emit::json;
+/// # use stew3d::disassemble;
+/// // outi 1
+/// let out = json(&disassemble(&[0xc1, 0x01]).unwrap(), 2);
+/// assert!(out.contains(r#"{"addr": 0, "bytes": [193, 1], "text": "outi 1"}"#));
+/// assert!(out.contains(r#"{"offset": 1, "kind": "operand", "addr": 0}"#));
+/// ```
+pub fn json(instrs: &[Instruction], image_len: usize) -> String {
+    let entries: Vec<String> = instrs
+        .iter()
+        .map(|ins| match ins {
+            Label(addr, name) => {
+                format!("{{\"addr\": {}, \"label\": {}}}", addr, json_string(name))
+            }
+            _ => {
+                let bytes: Vec<_> = ins.to_bytes().iter().map(u8::to_string).collect();
+                format!(
+                    "{{\"addr\": {}, \"bytes\": [{}], \"text\": {}}}",
+                    ins.addr(),
+                    bytes.join(", "),
+                    json_string(&ins.canonical())
+                )
+            }
+        })
+        .collect();
+
+    let mut byte_map = vec![(String::from("data"), String::from("null")); image_len];
+    for ins in instrs.iter().filter(|ins| !matches!(ins, Label(_, _))) {
+        for i in 0..ins.size() {
+            if let Some(entry) = byte_map.get_mut(ins.addr() + i) {
+                let kind = if i < ins.num_opcodes() {
+                    "opcode"
+                } else {
+                    "operand"
+                };
+                *entry = (String::from(kind), ins.addr().to_string());
+            }
+        }
+    }
+    let byte_map: Vec<String> = byte_map
+        .into_iter()
+        .enumerate()
+        .map(|(offset, (kind, addr))| {
+            format!(
+                "{{\"offset\": {}, \"kind\": \"{}\", \"addr\": {}}}",
+                offset, kind, addr
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\n  \"instructions\": [\n{}\n  ],\n  \"byte_map\": [\n{}\n  ]\n}}\n",
+        indent_items(&entries),
+        indent_items(&byte_map)
+    )
+}
+
+/// Joins the items of a JSON array, one per line, indented under its key.
+fn indent_items(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|item| format!("    {}", item))
+        .collect::<Vec<_>>()
+        .join(",\n")
+}
+
+/// Quotes and escapes a string for inclusion in JSON output.
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Everything in an HTML listing before the listing itself.
+const HTML_HEAD: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>stew3d listing</title>
+</head>
+<body>
+<pre>
+";
+
+/// Everything in an HTML listing after the listing itself.
+const HTML_TAIL: &str = "</pre>
+</body>
+</html>
+";
+
+/// Escapes the characters that are special in HTML text and attribute values.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::disassemble;
+
+    /// Checks that a record's bytes, checksum included, sum to 0xff.
+    fn checksum_ok(record: &str) -> bool {
+        let hex = &record[2..];
+        let sum = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .fold(0u8, |sum, b| sum.wrapping_add(b));
+        sum == 0xff
+    }
+
+    #[test]
+    fn byte_map() {
+        // outi 1; l0: jmp l0; then an undecoded byte
+        let instrs = disassemble(&[0xc1, 0x01, 0xb1, 0x02]).unwrap();
+        assert_eq!(
+            json(&instrs, 5),
+            r#"{
+  "instructions": [
+    {"addr": 0, "bytes": [193, 1], "text": "outi 1"},
+    {"addr": 2, "label": "l0"},
+    {"addr": 2, "bytes": [177, 2], "text": "jmp l0"}
+  ],
+  "byte_map": [
+    {"offset": 0, "kind": "opcode", "addr": 0},
+    {"offset": 1, "kind": "operand", "addr": 0},
+    {"offset": 2, "kind": "opcode", "addr": 2},
+    {"offset": 3, "kind": "operand", "addr": 2},
+    {"offset": 4, "kind": "data", "addr": null}
+  ]
+}
+"#
+        );
+    }
+
+    #[test]
+    fn json_strings() {
+        assert_eq!(json_string("a \"b\"\\\n"), r#""a \"b\"\\\u000a""#);
+    }
+
+    #[test]
+    fn tiny_program() {
+        // mvi 10, a; call 5; hlt; addi 4, a; ret
+        let instrs = disassemble(&[0x7f, 0x0a, 0xbc, 0x05, 0xc7, 0x0c, 0x04, 0xbd]).unwrap();
+        let records = srec(&instrs);
+
+        assert_eq!(records, "S10B00007F0ABC05C70C04BD16\nS9030000FC\n");
+        assert!(records.lines().all(checksum_ok));
+    }
+
+    #[test]
+    fn splits_long_programs() {
+        let instrs = disassemble(&[0xc8; 20]).unwrap();
+        let records: Vec<_> = srec(&instrs).lines().map(String::from).collect();
+
+        assert_eq!(records.len(), 3);
+        assert!(records[0].starts_with("S1130000"));
+        assert!(records[1].starts_with("S1070010"));
+        assert!(records.iter().all(|record| checksum_ok(record)));
+    }
+
+    #[test]
+    fn hyperlinked_listing() {
+        // mvi 1, c; jmp l0; l0: hlt
+        let page = html(
+            &disassemble(&[0x81, 0x01, 0xb1, 0x04, 0xc7]).unwrap(),
+            AddrRadix::Hex,
+        );
+
+        assert!(page.starts_with("<!DOCTYPE html>"));
+        assert!(page.contains("02:  jmp <a href=\"#l0\" title=\"0x04\">l0</a>\n"));
+        assert!(page.contains("<span id=\"l0\">l0:</span>\n04:  hlt\n"));
+        assert!(page.ends_with("</pre>\n</body>\n</html>\n"));
+    }
+
+    #[test]
+    fn escapes_names() {
+        let instrs = vec![
+            Label(0x00, "a<b>&\"c\"".into()),
+            Jump(0x00, crate::opcode::Opcode::JMP, 0x00, "a<b>&\"c\"".into()),
+        ];
+        let page = html(&instrs, AddrRadix::Hex);
+
+        assert!(page.contains("id=\"a&lt;b&gt;&amp;&quot;c&quot;\""));
+        assert!(page.contains("href=\"#a&lt;b&gt;&amp;&quot;c&quot;\""));
+        assert!(!page.contains("a<b>"));
+    }
+}
@@ -0,0 +1,86 @@
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+/// Computes the SHA-256 digest of `bytes`, so that a listing can be matched
+/// to the exact binary it came from.
+pub fn sha256(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
+
+/// Renders a digest as lowercase hex, as printed by tools like `sha256sum`.
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The checksums a ROM might store to verify itself, as computed by
+/// [`checksum`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChecksumAlgo {
+    /// The sum of the bytes, modulo 256.
+    Sum,
+    /// CRC-8 with polynomial 0x07, initial value 0, and no final XOR (as used
+    /// by SMBus).
+    Crc8,
+}
+
+impl FromStr for ChecksumAlgo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sum" => Ok(ChecksumAlgo::Sum),
+            "crc8" => Ok(ChecksumAlgo::Crc8),
+            _ => Err(format!("unknown checksum algorithm `{}`", s)),
+        }
+    }
+}
+
+/// Computes a one-byte checksum of `bytes` with the given algorithm.
+///
+/// # Examples
+/// ```
+/// # use stew3d::hash::{checksum, ChecksumAlgo};
+/// assert_eq!(checksum(b"123456789", ChecksumAlgo::Sum), 0xdd);
+/// assert_eq!(checksum(b"123456789", ChecksumAlgo::Crc8), 0xf4);
+/// ```
+pub fn checksum(bytes: &[u8], algo: ChecksumAlgo) -> u8 {
+    match algo {
+        ChecksumAlgo::Sum => bytes.iter().fold(0, |sum, b| sum.wrapping_add(*b)),
+        ChecksumAlgo::Crc8 => bytes.iter().fold(0, |crc, b| {
+            (0..8).fold(crc ^ b, |crc, _| {
+                if crc & 0x80 != 0 {
+                    crc << 1 ^ 0x07
+                } else {
+                    crc << 1
+                }
+            })
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn known_digests() {
+        assert_eq!(
+            to_hex(&sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            to_hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(checksum(&[], ChecksumAlgo::Sum), 0x00);
+        assert_eq!(checksum(&[0xff, 0x02], ChecksumAlgo::Sum), 0x01);
+        assert_eq!(checksum(&[], ChecksumAlgo::Crc8), 0x00);
+        assert_eq!(checksum(&[0x01], ChecksumAlgo::Crc8), 0x07);
+        assert_eq!("crc8".parse(), Ok(ChecksumAlgo::Crc8));
+        assert!("crc32".parse::<ChecksumAlgo>().is_err());
+    }
+}
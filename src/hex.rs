@@ -0,0 +1,178 @@
+use std::fmt;
+
+/// Represents a token in hex input that isn't a valid byte, along with the
+/// (1-based) line it was found on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseHexError {
+    token: String,
+    line: usize,
+}
+
+impl fmt::Display for ParseHexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid hex byte `{}` on line {}", self.token, self.line)
+    }
+}
+
+impl ParseHexError {
+    /// Places the error on the given line, for text that's parsed a line at a
+    /// time (where each line would otherwise be line 1).
+    pub fn on_line(self, line: usize) -> Self {
+        ParseHexError { line, ..self }
+    }
+}
+
+impl std::error::Error for ParseHexError {}
+
+/// Parses a single byte written as one or two hex digits, optionally prefixed
+/// with `0x`.
+fn parse_byte(token: &str, line: usize) -> Result<u8, ParseHexError> {
+    let err = || ParseHexError {
+        token: token.into(),
+        line,
+    };
+
+    let digits = token.strip_prefix("0x").unwrap_or(token);
+    if digits.is_empty() || digits.len() > 2 {
+        return Err(err());
+    }
+    u8::from_str_radix(digits, 16).map_err(|_| err())
+}
+
+/// Parses each line of `s` as whitespace-separated hex bytes, after passing it
+/// through `strip`.
+fn parse_lines(s: &str, strip: fn(&str) -> &str) -> Result<Vec<u8>, ParseHexError> {
+    let mut bytes = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        for token in strip(line).split_whitespace() {
+            bytes.push(parse_byte(token, i + 1)?);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Parses a string of whitespace-separated hex bytes, such as `"c1 01 c7"`.
+///
+/// # Examples
+/// ```
+/// # use stew3d::hex::parse_hex;
+/// assert_eq!(parse_hex("c1 01 c7"), Ok(vec![0xc1, 0x01, 0xc7]));
+/// assert!(parse_hex("c1 zz").is_err());
+/// ```
+pub fn parse_hex(s: &str) -> Result<Vec<u8>, ParseHexError> {
+    parse_lines(s, |line| line)
+}
+
+/// Parses an annotated hex text file: whitespace-separated hex bytes, where
+/// anything from a `;` or `#` to the end of the line is a comment, and blank
+/// lines are ignored.
+///
+/// # Examples
+/// ```
+/// # use stew3d::hex::parse_hex_text;
+/// let text = "7f 0a  ; mvi 10, a\n# done\nc7";
+/// assert_eq!(parse_hex_text(text), Ok(vec![0x7f, 0x0a, 0xc7]));
+/// ```
+pub fn parse_hex_text(s: &str) -> Result<Vec<u8>, ParseHexError> {
+    parse_lines(s, |line| match line.find(&[';', '#'][..]) {
+        Some(comment) => &line[..comment],
+        None => line,
+    })
+}
+
+/// Parses a byte signature to search for: whitespace-separated hex bytes, where
+/// `??` stands for any byte.
+///
+/// # Examples
+/// ```
+/// # use stew3d::hex::parse_pattern;
+/// assert_eq!(parse_pattern("7f ?? c7"), Ok(vec![Some(0x7f), None, Some(0xc7)]));
+/// ```
+pub fn parse_pattern(s: &str) -> Result<Vec<Option<u8>>, ParseHexError> {
+    s.split_whitespace()
+        .map(|token| match token {
+            "??" => Ok(None),
+            _ => parse_byte(token, 1).map(Some),
+        })
+        .collect()
+}
+
+/// Finds every offset in `bytes` at which `pattern` matches (including
+/// overlapping matches), in increasing order. An empty pattern matches
+/// nowhere.
+///
+/// # Examples
+/// ```
+/// # use stew3d::hex::scan;
+/// let bytes = [0x7f, 0x01, 0x7f, 0x7f, 0x02];
+/// assert_eq!(scan(&bytes, &[Some(0x7f), None]), vec![0, 2, 3]);
+/// ```
+pub fn scan(bytes: &[u8], pattern: &[Option<u8>]) -> Vec<usize> {
+    if pattern.is_empty() {
+        return Vec::new();
+    }
+
+    bytes
+        .windows(pattern.len())
+        .enumerate()
+        .filter(|(_, window)| {
+            window
+                .iter()
+                .zip(pattern)
+                .all(|(byte, expected)| match expected {
+                    Some(expected) => byte == expected,
+                    None => true,
+                })
+        })
+        .map(|(offset, _)| offset)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_bytes() {
+        assert_eq!(parse_hex(""), Ok(vec![]));
+        assert_eq!(parse_hex(" 0x7f  a\n"), Ok(vec![0x7f, 0x0a]));
+        assert_eq!(
+            parse_hex("c1 101"),
+            Err(ParseHexError {
+                token: String::from("101"),
+                line: 1
+            })
+        );
+    }
+
+    #[test]
+    fn parses_annotated_text() {
+        assert_eq!(
+            parse_hex_text("c1 01 ; note\nc7"),
+            Ok(vec![0xc1, 0x01, 0xc7])
+        );
+        assert_eq!(
+            parse_hex_text("# header\n\n  c7 # hlt\n; c1 zz\n"),
+            Ok(vec![0xc7])
+        );
+        assert_eq!(
+            parse_hex_text("c7\nc1 zz ; bad\n").unwrap_err().to_string(),
+            "invalid hex byte `zz` on line 2"
+        );
+    }
+
+    #[test]
+    fn scans_for_patterns() {
+        let pattern = parse_pattern("c1 ?? c7").unwrap();
+        // mvi 1, a; outi 5; hlt
+        let bytes = [0x7f, 0x01, 0xc1, 0x05, 0xc7];
+        assert_eq!(scan(&bytes, &pattern), vec![2]);
+        assert_eq!(scan(&bytes[..4], &pattern), vec![]);
+        assert_eq!(scan(&bytes, &[]), vec![]);
+
+        assert_eq!(
+            parse_pattern("c1 ? c7").unwrap_err().to_string(),
+            "invalid hex byte `?` on line 1"
+        );
+    }
+}
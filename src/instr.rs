@@ -1,5 +1,8 @@
+use crate::isa::{InstructionSet, Stew3000};
+use crate::semantics::Operand;
 use crate::Opcode;
 use std::fmt;
+use std::str::FromStr;
 use Instruction::*;
 use Opcode::*;
 use Operands::*;
@@ -13,6 +16,42 @@ pub enum Operands {
     Two(u8, u8),
 }
 
+/// The bases in which immediate operands can be written in a listing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Radix {
+    /// Decimal, e.g. `170`. This is how instructions are normally displayed.
+    Dec,
+    /// Hex with a `0x` prefix, e.g. `0xaa`.
+    Hex,
+    /// Binary with a `0b` prefix and all eight bits, e.g. `0b10101010`, which
+    /// is the easiest to read for masks.
+    Bin,
+}
+
+impl Radix {
+    /// Writes a byte in this base.
+    pub fn format(self, byte: u8) -> String {
+        match self {
+            Radix::Dec => byte.to_string(),
+            Radix::Hex => format!("0x{:02x}", byte),
+            Radix::Bin => format!("0b{:08b}", byte),
+        }
+    }
+}
+
+impl FromStr for Radix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dec" => Ok(Radix::Dec),
+            "hex" => Ok(Radix::Hex),
+            "bin" => Ok(Radix::Bin),
+            _ => Err(format!("unknown radix `{}`", s)),
+        }
+    }
+}
+
 /// An instruction that has been reconstructed via disassembly. For the purposes
 /// of turning raw addresses in jump instructions into labels, this type is
 /// split into three variants:
@@ -34,6 +73,29 @@ pub enum Instruction {
 }
 
 impl Instruction {
+    /// Builds an instruction at address 0 with the given opcode and operands of
+    /// the right shape for it (zeroes, or a jump to `l0`), for use where any
+    /// valid instance of an opcode will do.
+    ///
+    /// # Examples
+    /// ```
+    /// # use stew3d::instr::{Instruction::{self, *}, Operands::*};
+    /// # use stew3d::opcode::Opcode::*;
+    /// assert_eq!(Instruction::example(ADDI_A), Instr(0x00, ADDI_A, One(0)));
+    /// assert_eq!(Instruction::example(JNE).to_string(), "  jne l0");
+    /// ```
+    pub fn example(op: Opcode) -> Self {
+        if op.is_jump() {
+            return Jump(0, op, 0, String::from("l0"));
+        }
+
+        match op.operand_count() {
+            0 => Instr(0, op, Zero),
+            1 => Instr(0, op, One(0)),
+            _ => Instr(0, op, Two(0, 0)),
+        }
+    }
+
     /// Extracts the address in the binary of a given instruction. Labels, jumps
     /// and other instructions all have this component, so this is defined
     /// for any instruction.
@@ -43,20 +105,66 @@ impl Instruction {
         }
     }
 
+    /// Copies this instruction to a new address, leaving everything else about
+    /// it (including a jump's target) as it was.
+    pub fn with_addr(&self, addr: usize) -> Instruction {
+        let mut ins = self.clone();
+        match &mut ins {
+            Label(old, _) | Jump(old, _, _, _) | Instr(old, _, _) => *old = addr,
+        }
+        ins
+    }
+
+    /// Determines whether this is the same instruction as `other` wherever
+    /// each of them is, unlike `==`, which also compares addresses. Jumps are
+    /// the same if they go to the same label, since the address of the label
+    /// moves with the code.
+    ///
+    /// # Examples
+    /// ```
+    /// # use stew3d::instr::{Instruction::*, Operands::*};
+    /// # use stew3d::opcode::Opcode::*;
+    /// let here = Jump(0x00, JMP, 0x04, String::from("l0"));
+    /// let there = Jump(0x10, JMP, 0x14, String::from("l0"));
+    /// assert_ne!(here, there);
+    /// assert!(here.eq_ignoring_addr(&there));
+    /// ```
+    pub fn eq_ignoring_addr(&self, other: &Instruction) -> bool {
+        match (self, other) {
+            (Label(_, name), Label(_, other_name)) => name == other_name,
+            (Jump(_, op, _, label), Jump(_, other_op, _, other_label)) => {
+                op == other_op && label == other_label
+            }
+            (Instr(_, op, operands), Instr(_, other_op, other_operands)) => {
+                op == other_op && operands == other_operands
+            }
+            _ => false,
+        }
+    }
+
     /// Convert an instruction into the sequence of bytes used to represent it.
     pub fn to_bytes(&self) -> Vec<u8> {
-        match self {
+        self.to_bytes_in(&Stew3000)
+            .expect("every opcode has a 3000 encoding")
+    }
+
+    /// Converts an instruction into its bytes as [`to_bytes`](Self::to_bytes)
+    /// does, but with the opcode encoded by the given instruction set. Returns
+    /// `None` if the instruction set has no byte for the opcode.
+    pub fn to_bytes_in<I: InstructionSet>(&self, isa: &I) -> Option<Vec<u8>> {
+        let bytes = match self {
             Label(_, _) => Vec::new(),
-            Jump(_, op, target, _) => vec![*op as u8, *target],
+            Jump(_, op, target, _) => vec![isa.byte(*op)?, *target],
             Instr(_, op, operands) => {
-                let op = *op as u8;
+                let op = isa.byte(*op)?;
                 match operands {
                     Zero => vec![op],
                     One(first) => vec![op, *first],
                     Two(first, second) => vec![op, *first, *second],
                 }
             }
-        }
+        };
+        Some(bytes)
     }
 
     /// Determines the number of bytes to encode this instruction.
@@ -86,6 +194,184 @@ impl Instruction {
             _ => 1,
         }
     }
+
+    /// Renders this instruction as plain assembly text, without the
+    /// indentation used in listings (e.g. `jne l0` or `l0:`). This is the form
+    /// used when comparing programs, so that layout doesn't affect the result.
+    pub fn canonical(&self) -> String {
+        self.to_string().trim_start().to_string()
+    }
+
+    /// Renders this instruction like [`canonical`](Instruction::canonical),
+    /// but with its immediate operands written in the given base.
+    pub fn canonical_in(&self, radix: Radix) -> String {
+        self.canonical_with(radix, false)
+    }
+
+    /// Renders this instruction like [`canonical_in`](Instruction::canonical_in),
+    /// but with each stack offset written relative to `sp` (e.g. `lds [sp+4],
+    /// a` instead of `lds 4, a`), which reads more naturally in stack frame
+    /// code.
+    pub fn canonical_sp_in(&self, radix: Radix) -> String {
+        self.canonical_with(radix, true)
+    }
+
+    fn canonical_with(&self, radix: Radix, sp_syntax: bool) -> String {
+        let imms = match self {
+            Instr(_, _, One(first)) => vec![*first],
+            Instr(_, _, Two(first, second)) => vec![*first, *second],
+            _ => Vec::new(),
+        };
+        let op = match self {
+            Instr(_, op, _) if (radix != Radix::Dec || sp_syntax) && !imms.is_empty() => op,
+            _ => return self.canonical(),
+        };
+
+        let mut imms = imms.into_iter();
+        let operands: Vec<String> = op
+            .semantics()
+            .operands
+            .iter()
+            .map(|operand| match operand {
+                Operand::Reg(reg) => reg.to_string(),
+                Operand::Offset if sp_syntax => {
+                    format!("[sp+{}]", radix.format(imms.next().unwrap()))
+                }
+                Operand::Imm | Operand::Offset | Operand::Target => {
+                    radix.format(imms.next().unwrap())
+                }
+            })
+            .collect();
+        format!("{} {}", op, operands.join(", "))
+    }
+
+    /// Renders this instruction as it appears in a listing, but indented with
+    /// `indent` instead of the usual two spaces. Labels are never indented.
+    pub fn render(&self, indent: &str) -> String {
+        self.render_in(indent, Radix::Dec)
+    }
+
+    /// Renders this instruction like [`render`](Instruction::render), but
+    /// with its immediate operands written in the given base.
+    pub fn render_in(&self, indent: &str, radix: Radix) -> String {
+        match self {
+            Label(_, _) => self.to_string(),
+            _ => format!("{}{}", indent, self.canonical_in(radix)),
+        }
+    }
+}
+
+impl Instruction {
+    /// Determines whether control never falls through to the next instruction
+    /// after this one: true for `jmp`, `ret`, and `hlt`.
+    pub fn is_terminator(&self) -> bool {
+        matches!(
+            self,
+            Jump(_, JMP, _, _) | Instr(_, RET, _) | Instr(_, HLT, _)
+        )
+    }
+
+    /// Determines whether this is a jump that is only taken depending on the
+    /// flags (every jump except `jmp` and `call`).
+    pub fn is_conditional_branch(&self) -> bool {
+        match self {
+            Jump(_, op, _, _) => !matches!(op, JMP | CALL),
+            _ => false,
+        }
+    }
+
+    /// Determines whether this is a `call`.
+    pub fn is_call(&self) -> bool {
+        matches!(self, Jump(_, CALL, _, _))
+    }
+
+    /// Determines whether this instruction reads from memory: the loads (`ld`
+    /// and `lds`), and `ret`, which pops its return address off the stack.
+    pub fn reads_memory(&self) -> bool {
+        match self {
+            Label(_, _) => false,
+            Jump(_, op, _, _) | Instr(_, op, _) => matches!(
+                op,
+                LD_A_A
+                    | LD_B_A
+                    | LD_C_A
+                    | LD_A_B
+                    | LD_B_B
+                    | LD_C_B
+                    | LD_A_C
+                    | LD_B_C
+                    | LD_C_C
+                    | LDS_A
+                    | LDS_B
+                    | LDS_C
+                    | RET
+            ),
+        }
+    }
+
+    /// Determines whether this instruction writes to memory: the stores (`st`,
+    /// `sts`, and `stsi`), and `call`, which pushes its return address onto
+    /// the stack.
+    pub fn writes_memory(&self) -> bool {
+        match self {
+            Label(_, _) => false,
+            Jump(_, op, _, _) | Instr(_, op, _) => matches!(
+                op,
+                ST_A_A
+                    | ST_A_B
+                    | ST_A_C
+                    | ST_B_A
+                    | ST_B_B
+                    | ST_B_C
+                    | ST_C_A
+                    | ST_C_B
+                    | ST_C_C
+                    | ST_Z_A
+                    | ST_Z_B
+                    | ST_Z_C
+                    | STS_A
+                    | STS_B
+                    | STS_C
+                    | STS_Z
+                    | STSI
+                    | CALL
+            ),
+        }
+    }
+
+    /// Spells out the stack slot a stack-relative load or store (`lds`, `sts`,
+    /// or `stsi`) accesses, and what it moves there, such as `stores 5 at
+    /// sp+255` for `stsi 5, 255`. The bytes are written in the given base.
+    /// Other instructions have no such description.
+    pub fn stack_access(&self, radix: Radix) -> Option<String> {
+        let (op, imms) = match self {
+            Instr(_, op, One(first)) => (op, vec![*first]),
+            Instr(_, op, Two(first, second)) => (op, vec![*first, *second]),
+            _ => return None,
+        };
+        let operands = op.semantics().operands;
+        let slot = operands
+            .iter()
+            .position(|operand| *operand == Operand::Offset)?;
+
+        let mut imms = imms.into_iter();
+        let mut values = operands.iter().map(|operand| match operand {
+            Operand::Reg(reg) => reg.to_string(),
+            _ => radix.format(imms.next().unwrap()),
+        });
+        let (first, second) = (values.next()?, values.next()?);
+        Some(if slot == 0 {
+            format!("loads {} from sp+{}", second, first)
+        } else {
+            format!("stores {} at sp+{}", first, second)
+        })
+    }
+
+    /// Determines whether control may leave the straight-line path at this
+    /// instruction, either by stopping, jumping, or calling.
+    pub fn ends_block(&self) -> bool {
+        self.is_terminator() || self.is_conditional_branch() || self.is_call()
+    }
 }
 
 /// The tab character that is used to indent instructions in the disassembly.
@@ -95,24 +381,7 @@ impl fmt::Display for Instruction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Label(_, name) => write!(f, "{}:", name),
-            Jump(_, op, _, target) => {
-                let jmp = match op {
-                    JMP => "jmp",
-                    JE => "je",
-                    JNE => "jne",
-                    JG => "jg",
-                    JGE => "jge",
-                    JL => "jl",
-                    JLE => "jle",
-                    JA => "ja",
-                    JAE => "jae",
-                    JB => "jb",
-                    JBE => "jbe",
-                    CALL => "call",
-                    _ => unreachable!(),
-                };
-                write!(f, "{}{} {}", TAB, jmp, target)
-            }
+            Jump(_, op, _, target) => write!(f, "{}{} {}", TAB, op, target),
             Instr(_, op, operands) => {
                 let str = match operands {
                     Zero => match op {
@@ -355,3 +624,354 @@ impl fmt::Display for Instruction {
         }
     }
 }
+
+/// The error produced when a line of assembly isn't a recognized instruction.
+/// Holds the offending line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseInstructionError(pub String);
+
+impl fmt::Display for ParseInstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unrecognized instruction `{}`", self.0)
+    }
+}
+
+impl std::error::Error for ParseInstructionError {}
+
+/// Parses a byte operand written in decimal, in hex with a `0x` prefix, or in
+/// binary with a `0b` prefix.
+fn parse_byte(s: &str) -> Option<u8> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        u8::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = s.strip_prefix("0b") {
+        u8::from_str_radix(bin, 2).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+/// Parses a stack offset operand, written either as a byte or relative to the
+/// stack pointer as [`canonical_sp_in`](Instruction::canonical_sp_in) writes
+/// it (e.g. `[sp+4]`).
+fn parse_offset(s: &str) -> Option<u8> {
+    match s.strip_prefix("[sp+").and_then(|s| s.strip_suffix(']')) {
+        Some(offset) => parse_byte(offset),
+        None => parse_byte(s),
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = ParseInstructionError;
+
+    /// Parses a single line of assembly in the form the disassembler prints,
+    /// such as `add a, b`, `mvi 10, a`, or `jne l0`. Anything after a `;` or
+    /// `#` is a comment and is ignored, as is a label at the start of the line
+    /// (unless it's the only thing on the line, in which case it's parsed as a
+    /// `Label`). Byte operands may be written in decimal or `0x`-prefixed hex,
+    /// and stack offsets also relative to `sp` (e.g. `lds [sp+4], a`).
+    ///
+    /// As a single line carries no address, the result is at address 0. A jump
+    /// to a label keeps the label name with a target of 0, to be resolved once
+    /// the label's address is known; a jump to a number targets that address,
+    /// and keeps the number as written as its label.
+    ///
+    /// # Examples
+    /// ```
+    /// # use stew3d::instr::{Instruction::{self, *}, Operands::*};
+    /// # use stew3d::opcode::Opcode::*;
+    /// assert_eq!("mvi 10, a".parse(), Ok(Instr(0x00, MVI_A, One(10))));
+    /// assert_eq!("loop: jne loop ; again".parse(), Ok(Jump(0x00, JNE, 0x00, String::from("loop"))));
+    /// assert!("frob a".parse::<Instruction>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseInstructionError(s.trim().to_string());
+
+        let line = s.find(&[';', '#'][..]).map_or(s, |i| &s[..i]).trim();
+        let line = match line.find(':') {
+            Some(i) if line[i + 1..].trim().is_empty() => {
+                let name = line[..i].trim();
+                return if name.is_empty() || name.contains(char::is_whitespace) {
+                    Err(err())
+                } else {
+                    Ok(Label(0, name.to_string()))
+                };
+            }
+            Some(i) => line[i + 1..].trim(),
+            None => line,
+        };
+
+        let (mnemonic, rest) = line.split_at(line.find(char::is_whitespace).unwrap_or(line.len()));
+        let operands: Vec<&str> = match rest.trim() {
+            "" => Vec::new(),
+            rest => rest.split(',').map(str::trim).collect(),
+        };
+
+        if let [target] = operands[..] {
+            if let Some(&op) = Opcode::JUMPS.iter().find(|&&op| op.to_string() == mnemonic) {
+                let addr = parse_byte(target).unwrap_or(0);
+                return Ok(Jump(0, op, addr, target.to_string()));
+            }
+        }
+
+        // Every other instruction is identified by matching the operands
+        // against those of each opcode with the mnemonic, in the order its
+        // semantics give them, which is the order they're printed in. This is
+        // what tells `cmpi a, 5` from `cmpi 5, a`, for example.
+        Opcode::with_mnemonic(mnemonic)
+            .into_iter()
+            .filter(|op| !op.is_jump() && op.semantics().operands.len() == operands.len())
+            .find_map(|op| {
+                let mut bytes = Vec::new();
+                for (operand, text) in op.semantics().operands.iter().zip(&operands) {
+                    match operand {
+                        Operand::Reg(reg) if reg.to_string() == *text => {}
+                        Operand::Imm => bytes.push(parse_byte(text)?),
+                        Operand::Offset => bytes.push(parse_offset(text)?),
+                        _ => return None,
+                    }
+                }
+                let operands = match bytes[..] {
+                    [] => Zero,
+                    [first] => One(first),
+                    [first, second] => Two(first, second),
+                    _ => unreachable!("`{:?}` has more than two operand bytes", op),
+                };
+                Some(Instr(0, op, operands))
+            })
+            .ok_or_else(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn terminators() {
+        assert!(Jump(0x00, JMP, 0x04, "l0".into()).is_terminator());
+        assert!(Instr(0x00, RET, Zero).is_terminator());
+        assert!(Instr(0x00, HLT, Zero).is_terminator());
+        assert!(!Jump(0x00, JNE, 0x04, "l0".into()).is_terminator());
+        assert!(!Jump(0x00, CALL, 0x04, "l0".into()).is_terminator());
+        assert!(!Instr(0x00, NOP, Zero).is_terminator());
+        assert!(!Label(0x00, "l0".into()).is_terminator());
+    }
+
+    #[test]
+    fn conditional_branches() {
+        for op in &[JE, JNE, JG, JGE, JL, JLE, JA, JAE, JB, JBE] {
+            assert!(Jump(0x00, *op, 0x04, "l0".into()).is_conditional_branch());
+        }
+        assert!(!Jump(0x00, JMP, 0x04, "l0".into()).is_conditional_branch());
+        assert!(!Jump(0x00, CALL, 0x04, "l0".into()).is_conditional_branch());
+        assert!(!Instr(0x00, CMP_A_Z, Zero).is_conditional_branch());
+    }
+
+    #[test]
+    fn calls() {
+        assert!(Jump(0x00, CALL, 0x04, "l0".into()).is_call());
+        assert!(!Jump(0x00, JMP, 0x04, "l0".into()).is_call());
+        assert!(!Instr(0x00, RET, Zero).is_call());
+        assert!(Instr(0x00, RET, Zero).ends_block());
+        assert!(!Instr(0x00, OUT_A, Zero).ends_block());
+    }
+
+    #[test]
+    fn parse_zero_operands() {
+        assert_eq!("add a, b".parse(), Ok(Instr(0x00, ADD_A_B, Zero)));
+        assert_eq!("  hlt  ".parse(), Ok(Instr(0x00, HLT, Zero)));
+    }
+
+    #[test]
+    fn parse_one_operand() {
+        assert_eq!("addi 4, a".parse(), Ok(Instr(0x00, ADDI_A, One(4))));
+        assert_eq!("addi 0x04, a".parse(), Ok(Instr(0x00, ADDI_A, One(4))));
+    }
+
+    #[test]
+    fn parse_two_operands() {
+        assert_eq!("stsi 3, 7".parse(), Ok(Instr(0x00, STSI, Two(3, 7))));
+    }
+
+    #[test]
+    fn parse_operand_orders() {
+        for (text, ins) in &[
+            ("sts a, 5", Instr(0x00, STS_A, One(5))),
+            ("lds 5, a", Instr(0x00, LDS_A, One(5))),
+            ("cmpi a, 5", Instr(0x00, CMPI_A_BYTE, One(5))),
+            ("cmpi 5, a", Instr(0x00, CMPI_BYTE_A, One(5))),
+        ] {
+            assert_eq!(text.parse().as_ref(), Ok(ins));
+            assert_eq!(ins.canonical(), *text);
+        }
+        assert!("sts 5, a".parse::<Instruction>().is_err());
+        assert!("lds a, 5".parse::<Instruction>().is_err());
+
+        // stack offsets can also be written relative to sp
+        let stsi = Instr(0x00, STSI, Two(5, 255));
+        assert_eq!(stsi.canonical_sp_in(Radix::Dec).parse(), Ok(stsi));
+        assert_eq!("lds [sp+0x04], b".parse(), Ok(Instr(0x00, LDS_B, One(4))));
+    }
+
+    #[test]
+    fn parse_jumps() {
+        assert_eq!("jne l0".parse(), Ok(Jump(0x00, JNE, 0x00, "l0".into())));
+        assert_eq!("jmp 0x06".parse(), Ok(Jump(0x00, JMP, 0x06, "0x06".into())));
+    }
+
+    #[test]
+    fn parse_labels_and_comments() {
+        assert_eq!("l0:".parse(), Ok(Label(0x00, "l0".into())));
+        assert_eq!("l0: out a ; show it".parse(), Ok(Instr(0x00, OUT_A, Zero)));
+        assert_eq!("dcr a # count down".parse(), Ok(Instr(0x00, DCR_A, Zero)));
+    }
+
+    #[test]
+    fn parse_rejects_unknown_lines() {
+        assert_eq!(
+            "frob a".parse::<Instruction>(),
+            Err(ParseInstructionError(String::from("frob a")))
+        );
+        assert!("addi 4".parse::<Instruction>().is_err());
+        assert!("addi 300, a".parse::<Instruction>().is_err());
+        assert!("jne".parse::<Instruction>().is_err());
+    }
+
+    #[test]
+    fn moved() {
+        let jump = Jump(0x02, JNE, 0x06, "l0".into());
+        assert_eq!(jump.with_addr(0x10), Jump(0x10, JNE, 0x06, "l0".into()));
+        assert_eq!(jump.addr(), 0x02);
+
+        let label = Label(0x06, "l0".into());
+        assert_eq!(label.with_addr(0x00), Label(0x00, "l0".into()));
+        let mvi = Instr(0x00, MVI_A, One(0x0a));
+        assert_eq!(mvi.with_addr(0x04), Instr(0x04, MVI_A, One(0x0a)));
+    }
+
+    #[test]
+    fn stack_slots() {
+        let stsi = Instr(0x00, STSI, Two(0x05, 0xff));
+        assert_eq!(stsi.canonical(), "stsi 5, 255");
+        assert_eq!(
+            stsi.stack_access(Radix::Dec).as_deref(),
+            Some("stores 5 at sp+255")
+        );
+        assert_eq!(
+            stsi.stack_access(Radix::Hex).as_deref(),
+            Some("stores 0x05 at sp+0xff")
+        );
+
+        let sts = Instr(0x00, STS_Z, One(0x04));
+        assert_eq!(
+            sts.stack_access(Radix::Dec).as_deref(),
+            Some("stores z at sp+4")
+        );
+        let lds = Instr(0x00, LDS_B, One(0x02));
+        assert_eq!(
+            lds.stack_access(Radix::Dec).as_deref(),
+            Some("loads b from sp+2")
+        );
+        assert_eq!(Instr(0x00, MVI_A, One(0x02)).stack_access(Radix::Dec), None);
+    }
+
+    #[test]
+    fn equal_ignoring_addr() {
+        let mvi = Instr(0x00, MVI_A, One(0x05));
+        assert!(mvi.eq_ignoring_addr(&Instr(0x20, MVI_A, One(0x05))));
+        assert!(!mvi.eq_ignoring_addr(&Instr(0x00, MVI_A, One(0x06))));
+        assert!(!mvi.eq_ignoring_addr(&Instr(0x00, MVI_B, One(0x05))));
+
+        let jmp = Jump(0x00, JMP, 0x04, "l0".into());
+        assert!(!jmp.eq_ignoring_addr(&Jump(0x00, JMP, 0x08, "l1".into())));
+        assert!(!jmp.eq_ignoring_addr(&Jump(0x00, JNE, 0x04, "l0".into())));
+        assert!(jmp.eq_ignoring_addr(&Jump(0x30, JMP, 0x34, "l0".into())));
+
+        assert!(Label(0x00, "l0".into()).eq_ignoring_addr(&Label(0x08, "l0".into())));
+        assert!(!Label(0x00, "l0".into()).eq_ignoring_addr(&jmp));
+    }
+
+    #[test]
+    fn sp_syntax() {
+        let lds = Instr(0x00, LDS_A, One(0x04));
+        assert_eq!(lds.canonical(), "lds 4, a");
+        assert_eq!(lds.canonical_sp_in(Radix::Dec), "lds [sp+4], a");
+        assert_eq!(
+            Instr(0x00, STS_Z, One(0x10)).canonical_sp_in(Radix::Hex),
+            "sts z, [sp+0x10]"
+        );
+        assert_eq!(
+            Instr(0x00, STSI, Two(0x05, 0xff)).canonical_sp_in(Radix::Dec),
+            "stsi 5, [sp+255]"
+        );
+        // anything else is unchanged
+        assert_eq!(
+            Instr(0x00, MVI_A, One(0x04)).canonical_sp_in(Radix::Dec),
+            "mvi 4, a"
+        );
+    }
+
+    #[test]
+    fn memory_access() {
+        let load = Instr(0x00, LDS_B, One(0x02));
+        assert!(load.reads_memory());
+        assert!(!load.writes_memory());
+
+        let store = Instr(0x00, ST_A_C, Zero);
+        assert!(!store.reads_memory());
+        assert!(store.writes_memory());
+
+        let add = Instr(0x00, ADD_A_B, Zero);
+        assert!(!add.reads_memory());
+        assert!(!add.writes_memory());
+        assert!(!Label(0x00, "l0".into()).reads_memory());
+
+        // every load and store is classified, and nothing else but the stack
+        // accesses of `call` and `ret`
+        let ops: Vec<_> = (0..=u8::MAX)
+            .filter_map(|byte| Opcode::try_from(byte).ok())
+            .collect();
+        let reads = ops
+            .iter()
+            .filter(|&&op| Instruction::example(op).reads_memory())
+            .count();
+        let writes = ops
+            .iter()
+            .filter(|&&op| Instruction::example(op).writes_memory())
+            .count();
+        assert_eq!(reads, 9 + 3 + 1); // ld, lds, ret
+        assert_eq!(writes, 12 + 4 + 1 + 1); // st, sts, stsi, call
+    }
+
+    #[test]
+    fn render_with_indent() {
+        let ins = Jump(0x05, JNE, 0x02, "l0".into());
+        assert_eq!(ins.render("    "), "    jne l0");
+        assert_eq!(ins.render(TAB), ins.to_string());
+        assert_eq!(Label(0x02, "l0".into()).render("    "), "l0:");
+    }
+
+    #[test]
+    fn operand_radix() {
+        let ani = Instr(0x00, ANI_A, One(0xaa));
+        assert_eq!(ani.canonical_in(Radix::Bin), "ani 0b10101010, a");
+        assert_eq!(ani.canonical_in(Radix::Hex), "ani 0xaa, a");
+        assert_eq!(ani.render_in(TAB, Radix::Bin), "  ani 0b10101010, a");
+        assert_eq!("ani 0b10101010, a".parse(), Ok(ani));
+
+        let stsi = Instr(0x00, STSI, Two(0x01, 0x0f));
+        assert_eq!(stsi.canonical_in(Radix::Hex), "stsi 0x01, 0x0f");
+
+        // operands are written in the same order as in decimal, and
+        // instructions without immediates are unaffected
+        for byte in 0..=u8::MAX {
+            if let Ok(op) = Opcode::try_from(byte) {
+                let ins = Instruction::example(op);
+                let hex = ins.canonical_in(Radix::Hex);
+                assert_eq!(hex.parse(), Ok(ins.clone()), "{}", hex);
+                assert_eq!(ins.canonical_in(Radix::Dec), ins.canonical());
+            }
+        }
+    }
+}
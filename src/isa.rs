@@ -0,0 +1,40 @@
+use crate::opcode::Opcode;
+use std::convert::TryFrom;
+
+/// Describes which byte encodes which opcode in an instruction set that the
+/// disassembler can decode. The built-in [`Stew3000`] is the 3000's own
+/// encoding; a variant such as a prototype with a different opcode map can
+/// implement this trait and be passed to
+/// [`disassemble_with_isa`](crate::disassemble_with_isa).
+///
+/// This trait covers only the encoding, not mnemonics, sizes, or semantics.
+/// Decoded instructions are represented with [`Opcode`], a fixed enum of the
+/// 3000's instructions, so an instruction set can't add opcodes of its own,
+/// and each opcode keeps the 3000's mnemonic, size, operands, and semantics.
+/// Supporting a variant with new instructions would mean making `Opcode`
+/// open-ended first. Anything that renders an instruction's bytes (the
+/// listing's byte column, S-records, JSON) also uses the 3000's encoding;
+/// [`Instruction::to_bytes_in`](crate::instr::Instruction::to_bytes_in)
+/// re-encodes with another instruction set instead.
+pub trait InstructionSet {
+    /// Decodes an opcode byte, or returns `None` if it isn't a valid opcode.
+    fn opcode(&self, byte: u8) -> Option<Opcode>;
+
+    /// Encodes an opcode as its byte, the inverse of [`opcode`](Self::opcode),
+    /// or returns `None` if the opcode isn't part of this instruction set.
+    fn byte(&self, op: Opcode) -> Option<u8>;
+}
+
+/// The instruction set of the Stew 3000, as defined by [`Opcode`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Stew3000;
+
+impl InstructionSet for Stew3000 {
+    fn opcode(&self, byte: u8) -> Option<Opcode> {
+        Opcode::try_from(byte).ok()
+    }
+
+    fn byte(&self, op: Opcode) -> Option<u8> {
+        Some(op.to_byte())
+    }
+}
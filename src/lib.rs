@@ -0,0 +1,1325 @@
+//! A disassembler for the [Stew 3000](https://github.com/stew2003/Stew-3000).
+//!
+//! The entry point is [`disassemble`], which turns a slice of bytes into a
+//! list of [`Instruction`]s, with labels inserted at every jump target.
+
+use bimap::BiMap;
+use builder::{bind_jump_targets, BuildError};
+use instr::Instruction::{self, *};
+use instr::Operands::*;
+use isa::{InstructionSet, Stew3000};
+use opcode::Opcode;
+use std::fmt;
+use warning::Warning;
+
+pub mod analysis;
+pub mod builder;
+pub mod cfg;
+pub mod diff;
+pub mod emit;
+pub mod hash;
+pub mod hex;
+pub mod instr;
+pub mod isa;
+pub mod lint;
+pub mod listing;
+pub mod opcode;
+pub mod program;
+pub mod semantics;
+pub mod stats;
+pub mod warning;
+
+/// Represents possible errors that can occur while disassembling. `InvalidOpcode`
+/// indicates an opcode outside the valid range was encountered. `UnexpectedEndOfFile`
+/// indicates we were in the middle of parsing the operands for an instruction,
+/// but encountered the end of input before all the operands were provided.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Error {
+    InvalidOpcode(u8, usize),
+    UnexpectedEndOfFile(Opcode),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidOpcode(opcode, addr) => {
+                write!(
+                    f,
+                    "invalid opcode encountered at byte {}: `{:x}`",
+                    addr, opcode
+                )?;
+                if *opcode > opcode::OPCODE_MAX {
+                    write!(
+                        f,
+                        " (opcodes range from {:02x} to {:02x})",
+                        opcode::OPCODE_MIN,
+                        opcode::OPCODE_MAX
+                    )?;
+                }
+                Ok(())
+            }
+            Self::UnexpectedEndOfFile(opcode) => write!(
+                f,
+                "unexpected end of file while processing instruction with opcode {:02x}",
+                opcode.to_byte()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Decodes the single instruction at the start of `bytes`, which is assumed
+/// to live at address `addr` in the binary. Jumps are produced with an empty
+/// target label, since labels can only be assigned once the whole program is
+/// known. Any bytes after the instruction are ignored.
+///
+/// # Panics
+/// Panics if `bytes` is empty.
+///
+/// # Examples
+/// ```
+/// # use stew3d::decode_one;
+/// # use stew3d::instr::{Instruction::*, Operands::*};
+/// # use stew3d::opcode::Opcode::*;
+/// assert_eq!(decode_one(&[0xc1, 0x01, 0xc7], 0), Ok(Instr(0x00, OUTI, One(0x01))));
+/// ```
+pub fn decode_one(bytes: &[u8], addr: usize) -> Result<Instruction, Error> {
+    decode_with(&Stew3000, bytes, addr)
+}
+
+/// Decodes the single instruction at the start of `bytes` as [`decode_one`]
+/// does, but using the given instruction set.
+fn decode_with<I: InstructionSet>(
+    isa: &I,
+    bytes: &[u8],
+    addr: usize,
+) -> Result<Instruction, Error> {
+    let mut bytes = bytes.iter();
+
+    let opcode = match bytes.next() {
+        Some(&opcode) => opcode,
+        None => unreachable!("decode_one called with no bytes"),
+    };
+    let opcode: Opcode = match isa.opcode(opcode) {
+        Some(opcode) => opcode,
+        None => return Err(Error::InvalidOpcode(opcode, addr)),
+    };
+
+    // Expect another byte in the input stream and error with unexpected
+    // end of input if no more bytes.
+    let mut expect_operand = || bytes.next().ok_or(Error::UnexpectedEndOfFile(opcode));
+
+    let ins = match opcode.instruction_size() {
+        // Opcode + no operands
+        1 => Instr(addr, opcode, Zero),
+        // Opcode + single operand
+        2 => {
+            let operand = *expect_operand()?;
+
+            // If the instruction is a jump (needs labels)
+            if opcode.is_jump() {
+                Jump(addr, opcode, operand, String::new())
+            } else {
+                Instr(addr, opcode, One(operand))
+            }
+        }
+        // Opcode + two operands
+        3 => {
+            let operand1 = *expect_operand()?;
+            let operand2 = *expect_operand()?;
+            Instr(addr, opcode, Two(operand1, operand2))
+        }
+        // All instructions are currently between 1-3 bytes in size.
+        _ => unreachable!(),
+    };
+
+    Ok(ins)
+}
+
+/// Decodes a slice of bytes, passing each instruction to `f` as soon as it
+/// is decoded. Unlike [`disassemble`], no labels are generated: jumps carry
+/// only their numeric target, and their label is left empty. This is useful
+/// for analyses that don't need the whole program in memory at once.
+///
+/// # Examples
+/// ```
+/// # use stew3d::for_each_instruction;
+/// // mvi 10, a; call 5; hlt
+/// let bytes = [0x7f, 0x0a, 0xbc, 0x05, 0xc7];
+/// let mut count = 0;
+/// for_each_instruction(&bytes, |_| count += 1).unwrap();
+/// assert_eq!(count, 3);
+/// ```
+pub fn for_each_instruction<F: FnMut(&Instruction)>(bytes: &[u8], f: F) -> Result<(), Error> {
+    for_each_with(&Stew3000, bytes, f)
+}
+
+/// Decodes a slice of bytes as [`for_each_instruction`] does, but using the
+/// given instruction set.
+fn for_each_with<I, F>(isa: &I, bytes: &[u8], mut f: F) -> Result<(), Error>
+where
+    I: InstructionSet,
+    F: FnMut(&Instruction),
+{
+    let mut addr = 0; // current address in binary
+
+    while addr < bytes.len() {
+        let ins = decode_with(isa, &bytes[addr..], addr)?;
+        addr += ins.size();
+        f(&ins);
+    }
+
+    Ok(())
+}
+
+/// The error produced when disassembling a program from a reader: either
+/// reading failed, or what was read isn't a valid program.
+#[derive(Debug)]
+pub enum ReadError {
+    Io(std::io::Error),
+    Decode(Error),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read program: {}", e),
+            Self::Decode(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Decode(e) => Some(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for ReadError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<Error> for ReadError {
+    fn from(e: Error) -> Self {
+        Self::Decode(e)
+    }
+}
+
+/// Disassembles a program as [`disassemble`] does, but reads it a chunk at a
+/// time from `reader`, decoding as it goes, instead of needing all of it in
+/// memory up front. After each chunk, `progress` is called with the total
+/// number of bytes read so far.
+///
+/// # Examples
+/// ```
+/// # use stew3d::{disassemble, disassemble_from_reader};
+/// let bytes = [0x7f, 0x0a, 0xbc, 0x05, 0xc7, 0x0c, 0x04, 0xbd];
+/// let mut read = 0;
+/// let instrs = disassemble_from_reader(&bytes[..], |n| read = n).unwrap();
+/// assert_eq!(instrs, disassemble(&bytes).unwrap());
+/// assert_eq!(read, bytes.len());
+/// ```
+pub fn disassemble_from_reader<R, P>(
+    mut reader: R,
+    mut progress: P,
+) -> Result<Vec<Instruction>, ReadError>
+where
+    R: std::io::Read,
+    P: FnMut(usize),
+{
+    let mut chunk = [0; 4096];
+    // Bytes read but not yet decoded: at most the start of one instruction
+    // that continues in the next chunk.
+    let mut pending = Vec::new();
+    let mut addr = 0;
+    let mut total_read = 0;
+    let mut instrs = Vec::new();
+
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        pending.extend_from_slice(&chunk[..n]);
+        total_read += n;
+
+        let mut offset = 0;
+        while offset < pending.len() {
+            match decode_one(&pending[offset..], addr) {
+                Ok(ins) => {
+                    offset += ins.size();
+                    addr += ins.size();
+                    instrs.push(ins);
+                }
+                // the rest of the instruction is in the next chunk
+                Err(Error::UnexpectedEndOfFile(_)) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        pending.drain(..offset);
+        progress(total_read);
+    }
+
+    // Anything left over is an instruction cut off by the end of the input.
+    if !pending.is_empty() {
+        decode_one(&pending, addr)?;
+    }
+
+    Ok(assign_labels(instrs, &LabelOptions::default()).0)
+}
+
+/// Controls how the disassembler names the labels it generates for jump targets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelOptions {
+    /// The prefix of every generated label.
+    pub prefix: String,
+    /// If set, each label is named after the (hex) address it points at, e.g.
+    /// `loc_05`, instead of being numbered in order of first use.
+    pub by_address: bool,
+    /// If set, the instruction at address 0 (where execution begins) is always
+    /// given a label with this name, even if nothing jumps to it.
+    pub entry: Option<String>,
+    /// User-chosen names for particular addresses. These take precedence over
+    /// both the entry label and generated labels.
+    pub symbols: Vec<(usize, String)>,
+}
+
+impl Default for LabelOptions {
+    fn default() -> Self {
+        LabelOptions {
+            prefix: String::from("l"),
+            by_address: false,
+            entry: None,
+            symbols: Vec::new(),
+        }
+    }
+}
+
+/// Controls everything about how a program is disassembled, for use with
+/// [`disassemble_with`]. The defaults decode the whole input, from address 0,
+/// and fail on the first invalid instruction.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DecodeOptions {
+    /// How to name labels.
+    pub labels: LabelOptions,
+    /// The address at which the program is loaded, i.e. the address of the
+    /// first byte decoded. Jump targets are absolute, so this determines which
+    /// instruction each jump lands on.
+    pub base: usize,
+    /// The number of bytes to skip at the start of the input (e.g. a header)
+    /// before decoding.
+    pub skip: usize,
+    /// The most instructions to decode, or `None` to decode the whole input.
+    pub max_instrs: Option<usize>,
+    /// If set, an invalid instruction ends the program with a warning,
+    /// instead of failing the whole disassembly.
+    pub lenient: bool,
+    /// If set, bytes that fail to decode after a `jmp` or `ret` (with no jump
+    /// target in between) are taken as data, up to the next known jump
+    /// target, where decoding resumes. Nothing falls through to such bytes,
+    /// so they're most likely data rather than an invalid instruction.
+    pub data_after_jumps: bool,
+}
+
+/// The result of disassembling a program, along with what was learned about
+/// it along the way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    /// The program's instructions, with labels inserted at every named address.
+    pub instrs: Vec<Instruction>,
+    /// The name of every label, by the address it refers to.
+    pub labels: BiMap<usize, String>,
+    /// The number of bytes that were disassembled.
+    pub total_bytes: usize,
+    /// The address ranges of the bytes that were taken as data rather than
+    /// decoded (see [`DecodeOptions::data_after_jumps`]), in order.
+    pub data: Vec<std::ops::Range<usize>>,
+    /// Non-fatal problems noticed while decoding, such as jumps into the
+    /// middle of an instruction.
+    pub warnings: Vec<Warning>,
+}
+
+/// Parses a slice of bytes into an assembly program (list of instructions),
+/// naming generated labels `l0`, `l1`, etc. See [`disassemble_with_labels`]
+/// to control how labels are named, and [`Program`](program::Program) for
+/// helpers to query the result.
+///
+/// # Examples
+/// ```
+/// # use stew3d::disassemble;
+/// # use stew3d::instr::{Instruction::*, Operands::*};
+/// # use stew3d::opcode::Opcode::*;
+/// // outi 1; hlt
+/// let bytes = [0xc1, 0x01, 0xc7];
+/// assert_eq!(
+///     disassemble(&bytes).unwrap(),
+///     vec![Instr(0x00, OUTI, One(0x01)), Instr(0x02, HLT, Zero)],
+/// );
+/// ```
+pub fn disassemble(bytes: &[u8]) -> Result<Vec<Instruction>, Error> {
+    disassemble_with(bytes, &DecodeOptions::default()).map(|d| d.instrs)
+}
+
+/// Parses a slice of bytes into an assembly program, decoding the part of it
+/// given by `opts` and naming labels as [`disassemble_with_labels`] does.
+///
+/// # Examples
+/// ```
+/// # use stew3d::{disassemble_with, DecodeOptions, LabelOptions};
+/// // a 1-byte header, then `jmp 0x13` loaded at 0x10, then `hlt` and junk
+/// let bytes = [0xff, 0xb1, 0x13, 0xc8, 0xc7, 0xff];
+/// let opts = DecodeOptions {
+///     base: 0x10,
+///     skip: 1,
+///     lenient: true,
+///     ..Default::default()
+/// };
+/// let d = disassemble_with(&bytes, &opts).unwrap();
+/// assert_eq!(d.instrs[0].to_string(), "  jmp l0");
+/// assert_eq!(d.instrs[2].to_string(), "l0:");
+/// assert_eq!(d.instrs[2].addr(), 0x13);
+/// assert_eq!(d.total_bytes, 4);
+/// assert_eq!(d.warnings.len(), 1);
+/// ```
+pub fn disassemble_with(bytes: &[u8], opts: &DecodeOptions) -> Result<Disassembly, Error> {
+    decode_program(&Stew3000, bytes, opts)
+}
+
+/// Parses a slice of bytes into an assembly program, naming labels according
+/// to `opts`. Regardless of naming scheme, each address receives at most one
+/// label, and each label refers to exactly one address.
+///
+/// User symbols are assigned before anything else, so a jump to an address
+/// with a symbol uses the symbol rather than a generated label (with a warning
+/// saying so), and generated names never collide with symbols. Where the
+/// requested names conflict with each other (two names for one address, one
+/// name for two addresses, or a symbol at the entry point), the first one wins
+/// and a warning is returned.
+///
+/// Labels can only be placed at the start of an instruction, so jumps into the
+/// middle of an instruction (or past the end of the program) and symbols at
+/// such addresses are also reported as warnings.
+pub fn disassemble_with_labels(bytes: &[u8], opts: &LabelOptions) -> Result<Disassembly, Error> {
+    disassemble_with_isa(bytes, opts, &Stew3000)
+}
+
+/// Decodes the part of `bytes` given by `opts` with the given instruction
+/// set, and labels the result.
+fn decode_program<I: InstructionSet>(
+    isa: &I,
+    bytes: &[u8],
+    opts: &DecodeOptions,
+) -> Result<Disassembly, Error> {
+    let bytes = bytes.get(opts.skip..).unwrap_or_default();
+    let max_instrs = opts.max_instrs.unwrap_or(usize::MAX);
+
+    let mut instrs = Vec::new();
+    let mut decode_warnings = Vec::new();
+    let mut data = Vec::new();
+    // The jump targets seen so far, and the address just past the last `jmp`
+    // or `ret`, if none of them lies between it and the current address.
+    let mut targets = std::collections::BTreeSet::<usize>::new();
+    let mut unreached = None;
+    let mut offset = 0;
+    let end_of_input = opts.base + bytes.len();
+    while offset < bytes.len() && instrs.len() < max_instrs {
+        let addr = opts.base + offset;
+        if let Some(since) = unreached {
+            if targets.range(since..=addr).next().is_some() {
+                unreached = None;
+            }
+        }
+        let ins = match decode_with(isa, &bytes[offset..], addr) {
+            Ok(ins) => ins,
+            Err(_) if opts.data_after_jumps && unreached.is_some() => {
+                let end = match targets.range(addr + 1..).next() {
+                    Some(&target) => target.min(end_of_input),
+                    None => end_of_input,
+                };
+                data.push(addr..end);
+                offset = end - opts.base;
+                continue;
+            }
+            Err(e) if opts.lenient => {
+                decode_warnings.push(Warning::Undecodable(opts.base + offset, e));
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        if let Jump(_, _, target, _) = ins {
+            targets.insert(target as usize);
+        }
+        if matches!(ins, Jump(_, Opcode::JMP, _, _) | Instr(_, Opcode::RET, _)) {
+            unreached = Some(addr + ins.size());
+        }
+        offset += ins.size();
+        instrs.push(ins);
+    }
+
+    let (instrs, labels, mut warnings) = assign_labels(instrs, &opts.labels);
+    warnings.extend(decode_warnings);
+    Ok(Disassembly {
+        instrs,
+        labels,
+        total_bytes: offset - data.iter().map(ExactSizeIterator::len).sum::<usize>(),
+        data,
+        warnings,
+    })
+}
+
+/// Parses a slice of bytes into an assembly program as
+/// [`disassemble_with_labels`] does, but decoding it with the given
+/// instruction set instead of the 3000's.
+pub fn disassemble_with_isa<I: InstructionSet>(
+    bytes: &[u8],
+    opts: &LabelOptions,
+    isa: &I,
+) -> Result<Disassembly, Error> {
+    let opts = DecodeOptions {
+        labels: opts.clone(),
+        ..Default::default()
+    };
+    decode_program(isa, bytes, &opts)
+}
+
+/// Parses the parts of `bytes` that are reachable as code into an assembly
+/// program, naming labels as [`disassemble`] does. Unlike the other
+/// `disassemble` functions, which decode every byte in order (a linear sweep),
+/// this follows control flow (recursive descent): starting from address 0 and
+/// each of `entries`, it decodes instructions until a `jmp`, `ret`, or `hlt`,
+/// and also from the target of every jump and call along the way. Bytes that
+/// are never reached, such as data between subroutines, are left out of the
+/// program, rather than being misread as instructions.
+///
+/// Rather than failing, an invalid instruction along some path ends that path
+/// with a [`Warning::Undecodable`]. The `total_bytes` of the result counts
+/// only the bytes that were decoded.
+///
+/// # Examples
+/// ```
+/// # use stew3d::disassemble_recursive;
+/// // jmp l0; then a data byte (0xff); l0: hlt
+/// let d = disassemble_recursive(&[0xb1, 0x03, 0xff, 0xc7], &[]);
+/// let text: Vec<_> = d.instrs.iter().map(|ins| ins.to_string()).collect();
+/// assert_eq!(text, vec!["  jmp l0", "l0:", "  hlt"]);
+/// assert_eq!(d.total_bytes, 3);
+/// ```
+pub fn disassemble_recursive(bytes: &[u8], entries: &[usize]) -> Disassembly {
+    disassemble_recursive_with_labels(bytes, entries, &LabelOptions::default())
+}
+
+/// Parses the reachable parts of `bytes` into an assembly program as
+/// [`disassemble_recursive`] does, but naming labels according to `opts`.
+pub fn disassemble_recursive_with_labels(
+    bytes: &[u8],
+    entries: &[usize],
+    opts: &LabelOptions,
+) -> Disassembly {
+    // The decoded instructions by address, and which bytes they cover
+    let mut decoded = std::collections::BTreeMap::new();
+    let mut covered = vec![false; bytes.len()];
+    let mut decode_warnings = Vec::new();
+
+    let mut pending: Vec<usize> = entries.iter().rev().copied().collect();
+    pending.push(0);
+    while let Some(mut addr) = pending.pop() {
+        // Follow straight-line code until it stops, runs off the end, or runs
+        // into code that has already been decoded.
+        while addr < bytes.len() && !covered[addr] {
+            let ins = match decode_one(&bytes[addr..], addr) {
+                Ok(ins) => ins,
+                Err(e) => {
+                    decode_warnings.push(Warning::Undecodable(addr, e));
+                    break;
+                }
+            };
+
+            if let Jump(_, _, target, _) = ins {
+                pending.push(target as usize);
+            }
+            let stops = ins.is_terminator();
+            let size = ins.size();
+            for byte in &mut covered[addr..addr + size] {
+                *byte = true;
+            }
+            decoded.insert(addr, ins);
+
+            if stops {
+                break;
+            }
+            addr += size;
+        }
+    }
+
+    let total_bytes = covered.iter().filter(|&&byte| byte).count();
+    let (instrs, labels, mut warnings) = assign_labels(decoded.into_values().collect(), opts);
+    warnings.extend(decode_warnings);
+    Disassembly {
+        instrs,
+        labels,
+        total_bytes,
+        data: Vec::new(),
+        warnings,
+    }
+}
+
+/// Recomputes the addresses and labels of a program after it has been
+/// edited, e.g. by removing or inserting instructions. Any existing labels
+/// are dropped, each instruction is moved to follow the one before it, and
+/// every jump is retargeted to where its target instruction now lives, with
+/// new labels generated as by [`disassemble`].
+///
+/// Jump targets are followed by the old address of each instruction, so an
+/// inserted instruction should carry the address of the instruction it was
+/// inserted before. A jump to an instruction that was removed instead lands
+/// on whatever followed it.
+///
+/// # Examples
+/// ```
+/// # use stew3d::{disassemble, resolve_labels};
+/// // mvi 1, c; nop; jmp 5; hlt
+/// let mut instrs = disassemble(&[0x81, 0x01, 0xc8, 0xb1, 0x05, 0xc7]).unwrap();
+/// instrs.remove(1); // the nop
+/// resolve_labels(&mut instrs);
+/// assert_eq!(
+///     instrs.iter().map(|ins| ins.to_string()).collect::<Vec<_>>(),
+///     vec!["  mvi 1, c", "  jmp l0", "l0:", "  hlt"],
+/// );
+/// ```
+pub fn resolve_labels(instrs: &mut Vec<Instruction>) {
+    let mut moved = Vec::new(); // (old address, new address) of each instruction
+    let mut addr = 0;
+    let mut code = Vec::with_capacity(instrs.len());
+
+    for ins in instrs.drain(..) {
+        let old = ins.addr();
+        let ins = match ins {
+            Label(_, _) => continue,
+            Jump(_, op, target, label) => Jump(addr, op, target, label),
+            Instr(_, op, operands) => Instr(addr, op, operands),
+        };
+        moved.push((old, addr));
+        addr += ins.size();
+        code.push(ins);
+    }
+
+    for ins in &mut code {
+        if let Jump(_, _, target, _) = ins {
+            let new = moved
+                .iter()
+                .find(|(old, _)| *old >= *target as usize)
+                .map_or(addr, |(_, new)| *new);
+            *target = new as u8;
+        }
+    }
+
+    *instrs = assign_labels(code, &LabelOptions::default()).0;
+}
+
+/// Reassigns the address of every entry in an edited program, so that each
+/// instruction follows the one before it and each label is at the instruction
+/// after it, then points every jump at the new address of its label. Unlike
+/// [`resolve_labels`], the existing labels are kept, and it's the label names
+/// (not the old addresses) that decide where jumps go.
+///
+/// Fails if a jump refers to a label that's no longer in the program, a label
+/// is in it twice, or a label has moved beyond the reach of a jump (`0xff`).
+///
+/// # Examples
+/// ```
+/// # use stew3d::{disassemble, recompute_addresses};
+/// # use stew3d::instr::{Instruction::*, Operands::*};
+/// # use stew3d::opcode::Opcode::*;
+/// // jmp l0; l0: hlt
+/// let mut instrs = disassemble(&[0xb1, 0x02, 0xc7]).unwrap();
+/// instrs.insert(1, Instr(0, NOP, Zero));
+/// recompute_addresses(&mut instrs).unwrap();
+/// assert_eq!(instrs[0], Jump(0x00, JMP, 0x03, String::from("l0")));
+/// ```
+pub fn recompute_addresses(instrs: &mut [Instruction]) -> Result<(), BuildError> {
+    let mut addr = 0;
+    for ins in instrs.iter_mut() {
+        *ins = ins.with_addr(addr);
+        addr += ins.size();
+    }
+
+    let entries = instrs.iter().cloned().map(|ins| ((), ins)).collect();
+    let bound = bind_jump_targets(entries, false).map_err(|((), error)| error)?;
+    instrs.clone_from_slice(&bound);
+    Ok(())
+}
+
+/// Names the target of every jump in a program without labels, according to
+/// `opts`, and inserts the labels before the instructions they refer to.
+/// Returns the labeled program, the labels by address, and any problems with
+/// them. The entry label, if any, goes at the first instruction.
+fn assign_labels(
+    instrs: Vec<Instruction>,
+    opts: &LabelOptions,
+) -> (Vec<Instruction>, BiMap<usize, String>, Vec<Warning>) {
+    let mut warnings = Vec::new();
+    let entry_addr = instrs.first().map_or(0, Instruction::addr);
+
+    // This map maintains a bidirectional correspondence between addresses and labels
+    let mut label_addr_map: BiMap<usize, String> = BiMap::new();
+
+    let named = opts
+        .symbols
+        .iter()
+        .map(|(addr, name)| (*addr, name))
+        .chain(opts.entry.iter().map(|entry| (entry_addr, entry)));
+    for (addr, name) in named {
+        if let Some(existing) = label_addr_map.get_by_left(&addr) {
+            warnings.push(Warning::DuplicateLabel(
+                addr,
+                existing.clone(),
+                name.clone(),
+            ));
+        } else if let Some(&existing) = label_addr_map.get_by_right(name) {
+            warnings.push(Warning::DuplicateSymbol(name.clone(), existing, addr));
+        } else {
+            label_addr_map.insert(addr, name.clone());
+        }
+    }
+
+    // Gensym is used to generate unique label names. Address-based labels are
+    // unique by construction, as each address gets at most one label, unless a
+    // user symbol happens to have the same name.
+    let mut gensym_counter: usize = 0;
+    let mut gensym = |base: &str, addr: usize, taken: &BiMap<usize, String>| -> String {
+        let mut name = if opts.by_address {
+            format!("{}{:02x}", base, addr)
+        } else {
+            String::new()
+        };
+        while name.is_empty() || taken.contains_right(&name) {
+            if opts.by_address {
+                name.push('_');
+            } else {
+                gensym_counter += 1;
+                name = format!("{}{}", base, gensym_counter - 1);
+            }
+        }
+        name
+    };
+
+    let instrs: Vec<_> = instrs
+        .into_iter()
+        .map(|ins| match ins {
+            // If the instruction is a jump (needs labels)
+            Jump(addr, opcode, operand, _) => {
+                // Check map for label already generated for this address
+                match label_addr_map.get_by_left(&(operand as usize)) {
+                    Some(label) => Jump(addr, opcode, operand, label.clone()),
+                    None => {
+                        // No label for this address, generate a new one and
+                        // insert it into the map.
+                        let new_label = gensym(&opts.prefix, operand as usize, &label_addr_map);
+                        label_addr_map.insert(operand as usize, new_label.clone());
+                        Jump(addr, opcode, operand, new_label)
+                    }
+                }
+            }
+            ins => ins,
+        })
+        .collect();
+
+    let is_placed = |addr: usize| instrs.iter().any(|ins| ins.addr() == addr);
+    for ins in &instrs {
+        if let Jump(addr, _, target, _) = ins {
+            if !is_placed(*target as usize) {
+                warnings.push(Warning::MisalignedTarget(*addr, *target));
+            }
+        }
+    }
+    let is_target = |addr: usize| {
+        instrs
+            .iter()
+            .any(|ins| matches!(ins, Jump(_, _, target, _) if *target as usize == addr))
+    };
+    for (addr, name) in &opts.symbols {
+        if label_addr_map.get_by_left(addr) != Some(name) {
+            continue;
+        }
+        let warning = if !is_placed(*addr) {
+            Warning::UnplacedLabel(*addr, name.clone())
+        } else if is_target(*addr) {
+            Warning::SymbolReplacesLabel(*addr, name.clone())
+        } else {
+            continue;
+        };
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+
+    let mut with_labels = Vec::with_capacity(instrs.len());
+    for ins in instrs {
+        // If a label points at this address, add one
+        if let Some(label) = label_addr_map.get_by_left(&ins.addr()) {
+            with_labels.push(Label(ins.addr(), label.clone()));
+        }
+
+        with_labels.push(ins);
+    }
+
+    (with_labels, label_addr_map, warnings)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use opcode::Opcode::*;
+
+    #[test]
+    fn simple_disassembly() {
+        let b = [0x7f, 0x0a, 0xbc, 0x05, 0xc7, 0x0c, 0x04, 0xbd];
+        assert_eq!(
+            disassemble(&b).unwrap(),
+            vec![
+                Instr(0x00, MVI_A, One(0x0a)),
+                Jump(0x02, CALL, 0x05, String::from("l0")),
+                Instr(0x04, HLT, Zero),
+                Label(0x05, String::from("l0")),
+                Instr(0x05, ADDI_A, One(0x04)),
+                Instr(0x07, RET, Zero)
+            ]
+        );
+    }
+
+    #[test]
+    fn errs_on_invalid_opcode() {
+        // df is above OPCODE_MAX
+        let b = [0x80, 0x05, 0xc5, 0xdf, 0xc7];
+        assert_eq!(disassemble(&b), Err(Error::InvalidOpcode(0xdf, 3)));
+        assert_eq!(
+            Error::InvalidOpcode(0xdf, 3).to_string(),
+            "invalid opcode encountered at byte 3: `df` (opcodes range from 00 to c8)"
+        );
+    }
+
+    #[test]
+    fn errs_on_unexpected_eof() {
+        // 97 (lds byte, a) expects a byte operand
+        let b = [0xc8, 0xc8, 0x6f, 0x97];
+        assert_eq!(disassemble(&b), Err(Error::UnexpectedEndOfFile(LDS_A)));
+    }
+
+    #[test]
+    fn custom_label_prefix() {
+        let b = [0x81, 0x01, 0xb1, 0x06, 0xbc, 0x00, 0x73, 0xc7, 0xb9, 0x06];
+        assert_eq!(
+            disassemble_with_labels(
+                &b,
+                &LabelOptions {
+                    prefix: String::from("loc_"),
+                    ..Default::default()
+                }
+            )
+            .unwrap()
+            .instrs,
+            vec![
+                Label(0x00, String::from("loc_1")),
+                Instr(0x00, MVI_C, One(0x01)),
+                Jump(0x02, JMP, 0x06, String::from("loc_0")),
+                Jump(0x04, CALL, 0x00, String::from("loc_1")),
+                Label(0x06, String::from("loc_0")),
+                Instr(0x06, MOV_A_B, Zero),
+                Instr(0x07, HLT, Zero),
+                Jump(0x08, JAE, 0x06, String::from("loc_0")),
+            ]
+        );
+    }
+
+    #[test]
+    fn address_labels() {
+        let b = [0x7f, 0x0a, 0xbc, 0x05, 0xc7, 0x0c, 0x04, 0xbd];
+        let opts = LabelOptions {
+            prefix: String::from("loc_"),
+            by_address: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            disassemble_with_labels(&b, &opts).unwrap().instrs,
+            vec![
+                Instr(0x00, MVI_A, One(0x0a)),
+                Jump(0x02, CALL, 0x05, String::from("loc_05")),
+                Instr(0x04, HLT, Zero),
+                Label(0x05, String::from("loc_05")),
+                Instr(0x05, ADDI_A, One(0x04)),
+                Instr(0x07, RET, Zero)
+            ]
+        );
+    }
+
+    #[test]
+    fn entry_label() {
+        let opts = LabelOptions {
+            entry: Some(String::from("entry")),
+            ..Default::default()
+        };
+
+        let b = [0x7f, 0x0a, 0xbc, 0x05, 0xc7, 0x0c, 0x04, 0xbd];
+        let instrs = disassemble_with_labels(&b, &opts).unwrap().instrs;
+        assert_eq!(instrs[0], Label(0x00, String::from("entry")));
+        assert_eq!(instrs[2], Jump(0x02, CALL, 0x05, String::from("l0")));
+
+        // Jumps back to the start use the entry label
+        let b = [0x81, 0x01, 0xb1, 0x00];
+        assert_eq!(
+            disassemble_with_labels(&b, &opts).unwrap().instrs,
+            vec![
+                Label(0x00, String::from("entry")),
+                Instr(0x00, MVI_C, One(0x01)),
+                Jump(0x02, JMP, 0x00, String::from("entry")),
+            ]
+        );
+    }
+
+    #[test]
+    fn symbols_replace_generated_labels() {
+        let opts = LabelOptions {
+            symbols: vec![(0x05, String::from("add_4"))],
+            ..Default::default()
+        };
+
+        let b = [0x7f, 0x0a, 0xbc, 0x05, 0xc7, 0x0c, 0x04, 0xbd];
+        let d = disassemble_with_labels(&b, &opts).unwrap();
+        assert_eq!(
+            d.instrs,
+            vec![
+                Instr(0x00, MVI_A, One(0x0a)),
+                Jump(0x02, CALL, 0x05, String::from("add_4")),
+                Instr(0x04, HLT, Zero),
+                Label(0x05, String::from("add_4")),
+                Instr(0x05, ADDI_A, One(0x04)),
+                Instr(0x07, RET, Zero)
+            ]
+        );
+        assert_eq!(
+            d.warnings,
+            vec![Warning::SymbolReplacesLabel(0x05, String::from("add_4"))]
+        );
+        assert!(!d.labels.contains_right("l0"));
+    }
+
+    #[test]
+    fn conflicting_labels() {
+        let opts = LabelOptions {
+            entry: Some(String::from("entry")),
+            symbols: vec![
+                (0x00, String::from("start")),
+                (0x02, String::from("l0")),
+                (0x02, String::from("again")),
+                (0x06, String::from("start")),
+            ],
+            ..Default::default()
+        };
+
+        // mvi 1, c; jmp 6; call 0; mov a, b; hlt; jae 6
+        let b = [0x81, 0x01, 0xb1, 0x06, 0xbc, 0x00, 0x73, 0xc7, 0xb9, 0x06];
+        let Disassembly {
+            instrs, warnings, ..
+        } = disassemble_with_labels(&b, &opts).unwrap();
+
+        assert_eq!(
+            warnings,
+            vec![
+                Warning::DuplicateLabel(0x02, String::from("l0"), String::from("again")),
+                Warning::DuplicateSymbol(String::from("start"), 0x00, 0x06),
+                Warning::DuplicateLabel(0x00, String::from("start"), String::from("entry")),
+                Warning::SymbolReplacesLabel(0x00, String::from("start")),
+            ]
+        );
+
+        // Generated labels skip names the user has taken
+        assert_eq!(
+            instrs,
+            vec![
+                Label(0x00, String::from("start")),
+                Instr(0x00, MVI_C, One(0x01)),
+                Label(0x02, String::from("l0")),
+                Jump(0x02, JMP, 0x06, String::from("l1")),
+                Jump(0x04, CALL, 0x00, String::from("start")),
+                Label(0x06, String::from("l1")),
+                Instr(0x06, MOV_A_B, Zero),
+                Instr(0x07, HLT, Zero),
+                Jump(0x08, JAE, 0x06, String::from("l1")),
+            ]
+        );
+    }
+
+    #[test]
+    fn misaligned_targets() {
+        let opts = LabelOptions {
+            symbols: vec![
+                (0x01, String::from("operand")),
+                (0x03, String::from("done")),
+            ],
+            ..Default::default()
+        };
+
+        // mvi 1, c; jmp 1; hlt
+        let b = [0x81, 0x01, 0xb1, 0x01, 0xc7];
+        let d = disassemble_with_labels(&b, &opts).unwrap();
+
+        assert_eq!(d.total_bytes, 5);
+        assert_eq!(d.labels.len(), 2);
+        assert_eq!(d.labels.get_by_left(&0x01), Some(&String::from("operand")));
+        assert_eq!(d.labels.get_by_right("done"), Some(&0x03));
+        assert_eq!(
+            d.warnings,
+            vec![
+                Warning::MisalignedTarget(0x02, 0x01),
+                Warning::UnplacedLabel(0x01, String::from("operand")),
+                Warning::UnplacedLabel(0x03, String::from("done")),
+            ]
+        );
+        assert!(!d.instrs.iter().any(|ins| matches!(ins, Label(_, _))));
+    }
+
+    #[test]
+    fn misaligned_target_warns() {
+        // jmp 1; hlt
+        let b = [0xb1, 0x01, 0xc7];
+        let d = disassemble_with_labels(&b, &LabelOptions::default()).unwrap();
+
+        assert_eq!(d.warnings, vec![Warning::MisalignedTarget(0x00, 0x01)]);
+        assert_eq!(d.warnings[0].addr(), 0x00);
+        assert_eq!(
+            d.instrs,
+            vec![
+                Jump(0x00, JMP, 0x01, String::from("l0")),
+                Instr(0x02, HLT, Zero),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_options() {
+        // jmp l0; nop; l0: hlt
+        let b = [0xb1, 0x03, 0xc8, 0xc7];
+
+        let numbered = DecodeOptions::default();
+        let by_address = DecodeOptions {
+            labels: LabelOptions {
+                prefix: String::from("loc_"),
+                by_address: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let d = disassemble_with(&b, &numbered).unwrap();
+        assert_eq!(d.instrs[2], Label(0x03, String::from("l0")));
+        let d = disassemble_with(&b, &by_address).unwrap();
+        assert_eq!(d.instrs[2], Label(0x03, String::from("loc_03")));
+
+        // only the first two instructions, so the label has nowhere to go
+        let first_two = DecodeOptions {
+            max_instrs: Some(2),
+            ..Default::default()
+        };
+        let d = disassemble_with(&b, &first_two).unwrap();
+        assert_eq!(d.instrs.len(), 2);
+        assert_eq!(d.total_bytes, 3);
+        assert_eq!(d.warnings, vec![Warning::MisalignedTarget(0x00, 0x03)]);
+
+        // a truncated instruction only fails when not lenient
+        let b = [0xc8, 0x7f];
+        let lenient = DecodeOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            disassemble_with(&b, &DecodeOptions::default()).map(|d| d.instrs),
+            Err(Error::UnexpectedEndOfFile(MVI_A))
+        );
+        let d = disassemble_with(&b, &lenient).unwrap();
+        assert_eq!(d.instrs, vec![Instr(0x00, NOP, Zero)]);
+        assert_eq!(
+            d.warnings,
+            vec![Warning::Undecodable(
+                0x01,
+                Error::UnexpectedEndOfFile(MVI_A)
+            )]
+        );
+    }
+
+    #[test]
+    fn undecodable_data_after_jumps() {
+        // jmp l0; two data bytes; l0: ret; more data
+        let b = [0xb1, 0x04, 0xff, 0xfe, 0xbd, 0xff];
+        let opts = DecodeOptions {
+            data_after_jumps: true,
+            ..Default::default()
+        };
+        let d = disassemble_with(&b, &opts).unwrap();
+        assert_eq!(
+            d.instrs,
+            vec![
+                Jump(0x00, JMP, 0x04, String::from("l0")),
+                Label(0x04, String::from("l0")),
+                Instr(0x04, RET, Zero),
+            ]
+        );
+        assert_eq!(d.data, vec![0x02..0x04, 0x05..0x06]);
+        assert_eq!(d.total_bytes, 3);
+        assert_eq!(d.warnings, vec![]);
+
+        // bytes that fall through from code are still an error
+        let b = [0xc8, 0xff, 0xc7];
+        assert_eq!(
+            disassemble_with(&b, &opts).unwrap_err(),
+            Error::InvalidOpcode(0xff, 0x01)
+        );
+    }
+
+    #[test]
+    fn recursive_descent() {
+        // mvi 2, a; call l0; hlt; then data: 0x7f 0xbd; l0: out a; ret
+        let b = [0x7f, 0x02, 0xbc, 0x07, 0xc7, 0x7f, 0xbd, 0xbe, 0xbd];
+
+        // a linear sweep reads the data as `mvi 189, a`, and the label at l0
+        // still lands (0x07 happens to follow it), but the data isn't code
+        let linear = disassemble(&b).unwrap();
+        assert!(linear.contains(&Instr(0x05, MVI_A, One(0xbd))));
+
+        let d = disassemble_recursive(&b, &[]);
+        assert_eq!(
+            d.instrs,
+            vec![
+                Instr(0x00, MVI_A, One(0x02)),
+                Jump(0x02, CALL, 0x07, String::from("l0")),
+                Instr(0x04, HLT, Zero),
+                Label(0x07, String::from("l0")),
+                Instr(0x07, OUT_A, Zero),
+                Instr(0x08, RET, Zero),
+            ]
+        );
+        assert_eq!(d.total_bytes, 7);
+        assert!(d.warnings.is_empty());
+
+        // an extra entry point decodes the data after all, and a bad one ends
+        // its path with a warning
+        let d = disassemble_recursive(&b, &[0x05]);
+        assert!(d.instrs.contains(&Instr(0x05, MVI_A, One(0xbd))));
+        let d = disassemble_recursive(&[0xc7, 0xff], &[0x01, 0x09]);
+        assert_eq!(
+            d.warnings,
+            vec![Warning::Undecodable(0x01, Error::InvalidOpcode(0xff, 0x01))]
+        );
+    }
+
+    /// Reads from a slice a few bytes at a time, in a repeating pattern of
+    /// sizes.
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        sizes: std::iter::Cycle<std::slice::Iter<'a, usize>>,
+    }
+
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = (*self.sizes.next().unwrap())
+                .min(buf.len())
+                .min(self.bytes.len());
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn reads_in_chunks() {
+        // mvi 10, a; call l0; stsi 3, 7; jmp l1; l0: addi 4, a; ret; l1: hlt
+        let b = [
+            0x7f, 0x0a, 0xbc, 0x09, 0x9e, 0x03, 0x07, 0xb1, 0x0c, 0x0c, 0x04, 0xbd, 0xc7,
+        ];
+        let reader = Trickle {
+            bytes: &b,
+            sizes: [1, 3, 2, 5].iter().cycle(),
+        };
+
+        let mut progress = Vec::new();
+        let instrs = disassemble_from_reader(reader, |n| progress.push(n)).unwrap();
+        assert_eq!(instrs, disassemble(&b).unwrap());
+        assert_eq!(progress, vec![1, 4, 6, 11, 12, 13]);
+
+        // an instruction cut off at the very end is still an error
+        let reader = Trickle {
+            bytes: &b[..6],
+            sizes: [4].iter().cycle(),
+        };
+        assert!(matches!(
+            disassemble_from_reader(reader, |_| ()),
+            Err(ReadError::Decode(Error::UnexpectedEndOfFile(STSI)))
+        ));
+    }
+
+    #[test]
+    fn injected_instruction_set() {
+        /// A tiny instruction set where 0x00 is `hlt`, 0x01 is `jmp`, and
+        /// nothing else is valid.
+        struct Tiny;
+
+        impl InstructionSet for Tiny {
+            fn opcode(&self, byte: u8) -> Option<Opcode> {
+                match byte {
+                    0x00 => Some(HLT),
+                    0x01 => Some(JMP),
+                    _ => None,
+                }
+            }
+
+            fn byte(&self, op: Opcode) -> Option<u8> {
+                match op {
+                    HLT => Some(0x00),
+                    JMP => Some(0x01),
+                    _ => None,
+                }
+            }
+        }
+
+        let b = [0x01, 0x03, 0x00, 0x00];
+        assert_eq!(
+            disassemble_with_isa(&b, &LabelOptions::default(), &Tiny)
+                .unwrap()
+                .instrs,
+            vec![
+                Jump(0x00, JMP, 0x03, String::from("l0")),
+                Instr(0x02, HLT, Zero),
+                Label(0x03, String::from("l0")),
+                Instr(0x03, HLT, Zero),
+            ]
+        );
+        assert_eq!(
+            disassemble_with_isa(&[0xc7], &LabelOptions::default(), &Tiny),
+            Err(Error::InvalidOpcode(0xc7, 0))
+        );
+
+        // Re-encoding with the same instruction set gives back the input
+        let d = disassemble_with_isa(&b, &LabelOptions::default(), &Tiny).unwrap();
+        let bytes: Vec<_> = d
+            .instrs
+            .iter()
+            .flat_map(|ins| ins.to_bytes_in(&Tiny).unwrap())
+            .collect();
+        assert_eq!(bytes, b);
+        assert_eq!(Instr(0x00, NOP, Zero).to_bytes_in(&Tiny), None);
+
+        // The same bytes mean something else to the 3000
+        assert_eq!(disassemble(&b).unwrap()[0], Instr(0x00, ADD_A_B, Zero));
+        assert_eq!(d.instrs[0].to_bytes(), vec![0xb1, 0x03]);
+    }
+
+    #[test]
+    fn relabel_after_removal() {
+        // mvi 10, a; call l0; hlt; nop; l0: addi 4, a; jne l0; ret
+        let b = [
+            0x7f, 0x0a, 0xbc, 0x06, 0xc7, 0xc8, 0x0c, 0x04, 0xb3, 0x06, 0xbd,
+        ];
+        let mut instrs = disassemble(&b).unwrap();
+        assert_eq!(instrs[4], Label(0x06, String::from("l0")));
+
+        instrs.retain(|ins| !matches!(ins, Instr(_, NOP, _)));
+        resolve_labels(&mut instrs);
+
+        assert_eq!(
+            instrs,
+            vec![
+                Instr(0x00, MVI_A, One(0x0a)),
+                Jump(0x02, CALL, 0x05, String::from("l0")),
+                Instr(0x04, HLT, Zero),
+                Label(0x05, String::from("l0")),
+                Instr(0x05, ADDI_A, One(0x04)),
+                Jump(0x07, JNE, 0x05, String::from("l0")),
+                Instr(0x09, RET, Zero),
+            ]
+        );
+    }
+
+    #[test]
+    fn recompute_after_insertion() {
+        // mvi 1, c; jmp l0; call l1; l0: mov a, b; hlt; l1: ret
+        let b = [0x81, 0x01, 0xb1, 0x06, 0xbc, 0x08, 0x73, 0xc7, 0xbd];
+        let mut instrs = disassemble(&b).unwrap();
+        assert_eq!(instrs[3], Label(0x06, String::from("l0")));
+
+        // insert a nop just before l0
+        instrs.insert(3, Instr(0, NOP, Zero));
+        recompute_addresses(&mut instrs).unwrap();
+
+        assert_eq!(
+            instrs,
+            vec![
+                Instr(0x00, MVI_C, One(0x01)),
+                Jump(0x02, JMP, 0x07, String::from("l0")),
+                Jump(0x04, CALL, 0x09, String::from("l1")),
+                Instr(0x06, NOP, Zero),
+                Label(0x07, String::from("l0")),
+                Instr(0x07, MOV_A_B, Zero),
+                Instr(0x08, HLT, Zero),
+                Label(0x09, String::from("l1")),
+                Instr(0x09, RET, Zero),
+            ]
+        );
+        assert_eq!(instrs[1].to_bytes(), vec![0xb1, 0x07]);
+
+        // removing a label leaves jumps to it dangling
+        instrs.remove(4);
+        assert_eq!(
+            recompute_addresses(&mut instrs),
+            Err(BuildError::UndefinedLabel(String::from("l0")))
+        );
+    }
+
+    #[test]
+    fn recompute_out_of_reach() {
+        // jmp l0; l0: hlt
+        let mut instrs = disassemble(&[0xb1, 0x02, 0xc7]).unwrap();
+
+        // push the label past the last address a jump can reach
+        for _ in 0..0xfe {
+            instrs.insert(1, Instr(0, NOP, Zero));
+        }
+        assert_eq!(
+            recompute_addresses(&mut instrs),
+            Err(BuildError::OutOfRange(String::from("l0"), 0x100))
+        );
+    }
+
+    #[test]
+    fn visitor_matches_disassembly() {
+        let b = [0x81, 0x01, 0xb1, 0x06, 0xbc, 0x00, 0x73, 0xc7, 0xb9, 0x06];
+
+        let mut count = 0;
+        for_each_instruction(&b, |_| count += 1).unwrap();
+
+        let instrs = disassemble(&b).unwrap();
+        let labels = instrs
+            .iter()
+            .filter(|ins| matches!(ins, Label(_, _)))
+            .count();
+        assert_eq!(count, instrs.len() - labels);
+    }
+
+    #[test]
+    fn visitor_sees_numeric_targets() {
+        // jmp 6; hlt
+        let b = [0xb1, 0x06, 0xc7];
+
+        let mut seen = Vec::new();
+        for_each_instruction(&b, |ins| seen.push(ins.clone())).unwrap();
+        assert_eq!(
+            seen,
+            vec![Jump(0x00, JMP, 0x06, String::new()), Instr(0x02, HLT, Zero)]
+        );
+    }
+}
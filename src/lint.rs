@@ -0,0 +1,219 @@
+use crate::analysis::{find_cancelling, find_foldable, find_redundant, fold};
+use crate::instr::Instruction::{self, *};
+use crate::opcode::Opcode::*;
+use crate::warning::Warning;
+
+/// Checks a program for stylistic problems that don't affect how it runs,
+/// returning a warning for each one found.
+pub fn lint(instrs: &[Instruction]) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    warnings.extend(mixed_compare_order(instrs));
+    warnings.extend(no_halt(instrs));
+    warnings.extend(
+        find_redundant(instrs)
+            .into_iter()
+            .map(|i| Warning::Redundant(instrs[i].addr(), instrs[i].canonical())),
+    );
+    warnings.extend(find_cancelling(instrs).into_iter().map(|(i, j)| {
+        Warning::Cancelling(instrs[i].addr(), instrs[j].addr(), instrs[i].canonical())
+    }));
+    warnings.extend(find_foldable(instrs).into_iter().map(|(i, j)| {
+        Warning::Foldable(
+            instrs[i].addr(),
+            format!("{}; {}", instrs[i].canonical(), instrs[j].canonical()),
+            fold(&instrs[i], &instrs[j]).unwrap().canonical(),
+        )
+    }));
+    warnings
+}
+
+/// Finds the moves and stores whose value comes from the zero register `z`
+/// (e.g. `st z, a`), returning their indices. These always write a zero, which
+/// is sometimes intended, but is easy to get by naming the wrong register.
+/// Compares against `z` aren't included, as that's the usual way to test a
+/// register for zero.
+pub fn zero_register_uses(instrs: &[Instruction]) -> Vec<usize> {
+    instrs
+        .iter()
+        .enumerate()
+        .filter(|(_, ins)| {
+            matches!(
+                ins,
+                Instr(
+                    _,
+                    MOV_Z_A | MOV_Z_B | MOV_Z_C | ST_Z_A | ST_Z_B | ST_Z_C | STS_Z,
+                    _
+                )
+            )
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Flags programs that never `hlt` and don't end by spinning on a `jmp` to
+/// itself, as execution would run off the end of the program.
+fn no_halt(instrs: &[Instruction]) -> Option<Warning> {
+    let last = instrs
+        .iter()
+        .rev()
+        .find(|ins| !matches!(ins, Label(_, _)))?;
+
+    let halts = instrs.iter().any(|ins| matches!(ins, Instr(_, HLT, _)));
+    let spins = matches!(last, Jump(addr, JMP, target, _) if *target as usize == *addr);
+    if halts || spins {
+        None
+    } else {
+        Some(Warning::NoHalt(last.addr()))
+    }
+}
+
+/// Flags programs that use both the register-first and immediate-first forms
+/// of `cmpi`.
+fn mixed_compare_order(instrs: &[Instruction]) -> Option<Warning> {
+    let first_addr =
+        |pred: fn(&Instruction) -> bool| instrs.iter().find(|ins| pred(ins)).map(|ins| ins.addr());
+
+    let reg_first = first_addr(|ins| {
+        matches!(
+            ins,
+            Instr(_, CMPI_A_BYTE, _) | Instr(_, CMPI_B_BYTE, _) | Instr(_, CMPI_C_BYTE, _)
+        )
+    });
+    let imm_first = first_addr(|ins| {
+        matches!(
+            ins,
+            Instr(_, CMPI_BYTE_A, _) | Instr(_, CMPI_BYTE_B, _) | Instr(_, CMPI_BYTE_C, _)
+        )
+    });
+
+    match (reg_first, imm_first) {
+        (Some(reg_first), Some(imm_first)) => {
+            Some(Warning::MixedCompareOrder(reg_first, imm_first))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::instr::Operands::*;
+
+    #[test]
+    fn mixed_compare_orders() {
+        // cmpi a, 5; cmpi 5, a; cmpi b, 5; hlt
+        let instrs = [
+            Instr(0x00, CMPI_A_BYTE, One(0x05)),
+            Instr(0x02, CMPI_BYTE_A, One(0x05)),
+            Instr(0x04, CMPI_B_BYTE, One(0x05)),
+            Instr(0x06, HLT, Zero),
+        ];
+        assert_eq!(lint(&instrs), vec![Warning::MixedCompareOrder(0x00, 0x02)]);
+    }
+
+    #[test]
+    fn consistent_compare_order() {
+        // cmpi 5, a; cmpi 7, c; hlt
+        let instrs = [
+            Instr(0x00, CMPI_BYTE_A, One(0x05)),
+            Instr(0x02, CMPI_BYTE_C, One(0x07)),
+            Instr(0x04, HLT, Zero),
+        ];
+        assert_eq!(lint(&instrs), vec![]);
+    }
+
+    #[test]
+    fn missing_halt() {
+        // out a; ret
+        let instrs = [Instr(0x00, OUT_A, Zero), Instr(0x01, RET, Zero)];
+        assert_eq!(lint(&instrs), vec![Warning::NoHalt(0x01)]);
+
+        // out a; hlt
+        let instrs = [Instr(0x00, OUT_A, Zero), Instr(0x01, HLT, Zero)];
+        assert_eq!(lint(&instrs), vec![]);
+
+        // out a; l0: jmp l0
+        let instrs = [
+            Instr(0x00, OUT_A, Zero),
+            Label(0x01, "l0".into()),
+            Jump(0x01, JMP, 0x01, "l0".into()),
+        ];
+        assert_eq!(lint(&instrs), vec![]);
+    }
+
+    #[test]
+    fn redundant_instructions() {
+        // mov a, c; mov c, a; hlt
+        let instrs = [
+            Instr(0x00, MOV_A_C, Zero),
+            Instr(0x01, MOV_C_A, Zero),
+            Instr(0x02, HLT, Zero),
+        ];
+        let warnings = lint(&instrs);
+        assert_eq!(
+            warnings,
+            vec![Warning::Redundant(0x01, String::from("mov c, a"))]
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "`mov c, a` at 0x01 has no effect after the instruction before it"
+        );
+    }
+
+    #[test]
+    fn cancelling_instructions() {
+        // not b; not b; hlt
+        let instrs = [
+            Instr(0x00, NOT_B, Zero),
+            Instr(0x01, NOT_B, Zero),
+            Instr(0x02, HLT, Zero),
+        ];
+        let warnings = lint(&instrs);
+        assert_eq!(
+            warnings,
+            vec![Warning::Cancelling(0x00, 0x01, String::from("not b"))]
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "`not b` at 0x00 and 0x01 cancel each other out; consider removing both"
+        );
+    }
+
+    #[test]
+    fn foldable_immediates() {
+        // mvi 5, a; addi 3, a; out a; hlt
+        let instrs = [
+            Instr(0x00, MVI_A, One(0x05)),
+            Instr(0x02, ADDI_A, One(0x03)),
+            Instr(0x04, OUT_A, Zero),
+            Instr(0x05, HLT, Zero),
+        ];
+        let warnings = lint(&instrs);
+        assert_eq!(
+            warnings,
+            vec![Warning::Foldable(
+                0x00,
+                String::from("mvi 5, a; addi 3, a"),
+                String::from("mvi 8, a")
+            )]
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "`mvi 5, a; addi 3, a` at 0x00 could be folded into `mvi 8, a`"
+        );
+    }
+
+    #[test]
+    fn zero_register() {
+        // st z, a; mov z, b; cmp a, z; sts z, 3; st a, b; hlt
+        let instrs = [
+            Instr(0x00, ST_Z_A, Zero),
+            Instr(0x01, MOV_Z_B, Zero),
+            Instr(0x02, CMP_A_Z, Zero),
+            Instr(0x03, STS_Z, One(0x03)),
+            Instr(0x05, ST_A_B, Zero),
+            Instr(0x06, HLT, Zero),
+        ];
+        assert_eq!(zero_register_uses(&instrs), vec![0, 1, 3]);
+    }
+}
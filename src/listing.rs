@@ -0,0 +1,1040 @@
+use crate::analysis::{find_jump_tables, find_tail_calls};
+use crate::cfg::{find_loops, Cfg};
+use crate::instr::Instruction::{self, *};
+use crate::instr::{Operands, Radix};
+use crate::lint::zero_register_uses;
+use crate::opcode::Opcode::{self, *};
+use crate::program::Program;
+use crate::semantics::{Operand, Reg};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// The width of the byte column: the bytes of the largest instruction in hex,
+/// separated by spaces (e.g. `9e 05 ff`). Every instruction's bytes are padded
+/// to it, so the columns after them line up.
+const BYTES_WIDTH: usize = Opcode::MAX_SIZE * 3 - 1;
+
+/// The bases in which addresses can be printed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AddrRadix {
+    Hex,
+    Dec,
+}
+
+impl AddrRadix {
+    /// Writes an address for the address column, e.g. `05` or `5`.
+    pub fn column(self, addr: usize) -> String {
+        match self {
+            AddrRadix::Hex => format!("{:02x}", addr),
+            AddrRadix::Dec => addr.to_string(),
+        }
+    }
+
+    /// Writes an address to refer to it in a comment, e.g. `0x05` or `5`.
+    pub fn reference(self, addr: usize) -> String {
+        match self {
+            AddrRadix::Hex => format!("0x{:02x}", addr),
+            AddrRadix::Dec => addr.to_string(),
+        }
+    }
+}
+
+impl FromStr for AddrRadix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" => Ok(AddrRadix::Hex),
+            "dec" => Ok(AddrRadix::Dec),
+            _ => Err(format!("unknown radix `{}`", s)),
+        }
+    }
+}
+
+/// Controls how a listing is laid out: which columns are shown, the bases
+/// numbers are written in, and which explanatory comments are added to each
+/// line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingOptions {
+    /// The string to indent instructions with.
+    pub indent: String,
+    /// Whether to show the raw bytes of each instruction.
+    pub show_bytes: bool,
+    /// The base of the address column and of addresses in comments.
+    pub addr_radix: AddrRadix,
+    /// The base of immediate operands.
+    pub operand_radix: Radix,
+    /// The directive that introduces a line of data bytes, e.g. `db`.
+    pub data_directive: String,
+    /// Whether to comment each jump with the address of its target.
+    pub show_targets: bool,
+    /// Whether to comment each jump with the distance to its target from the
+    /// end of the jump, e.g. `-5` for a short loop back.
+    pub rel_targets: bool,
+    /// Whether to spell out when each conditional jump is taken, and the
+    /// stack slot each stack-relative load or store accesses.
+    pub explain: bool,
+    /// Whether to comment each instruction with which byte encodes what.
+    pub show_encoding: bool,
+    /// Whether to comment each printable ASCII immediate with its character
+    /// (e.g. `'A'` for `mvi 65, a`).
+    pub char_imms: bool,
+    /// Whether to comment the mask of each logical immediate instruction with
+    /// its bits, e.g. `[1010 1010]` for `ani 170, a`.
+    pub bitfields: bool,
+    /// Whether to write stack offsets relative to `sp`, e.g. `lds [sp+4], a`.
+    pub sp_syntax: bool,
+    /// Whether to comment each compare against `z` with the test it amounts
+    /// to, e.g. `a == 0?` for `cmp a, z`.
+    pub zero_compares: bool,
+    /// Whether to comment the first instruction of each loop with the extent
+    /// of the loop's body.
+    pub loops: bool,
+    /// Whether to comment the first entry of each likely jump table with the
+    /// targets of its entries.
+    pub jump_tables: bool,
+    /// Whether to point out each move or store of the zero register.
+    pub zero_register: bool,
+    /// Whether to point out each likely tail call.
+    pub tail_calls: bool,
+    /// If set, the address column gives each address as an offset from this
+    /// one (e.g. `+3`), rather than absolutely. Addresses in comments are
+    /// still absolute.
+    pub offset_from: Option<usize>,
+}
+
+impl Default for ListingOptions {
+    /// Two-space indentation, with bytes, hex addresses, decimal operands,
+    /// `.byte` data, and no comments.
+    fn default() -> Self {
+        ListingOptions {
+            indent: String::from("  "),
+            show_bytes: true,
+            addr_radix: AddrRadix::Hex,
+            operand_radix: Radix::Dec,
+            data_directive: String::from(".byte"),
+            show_targets: false,
+            rel_targets: false,
+            explain: false,
+            show_encoding: false,
+            char_imms: false,
+            bitfields: false,
+            sp_syntax: false,
+            zero_compares: false,
+            loops: false,
+            jump_tables: false,
+            zero_register: false,
+            tail_calls: false,
+            offset_from: None,
+        }
+    }
+}
+
+impl ListingOptions {
+    /// Builds the annotators for the comments these options call for, in the
+    /// order their comments appear on a line. Those that comment on the shape
+    /// of the whole program, such as its loops, analyze `program` up front.
+    pub fn annotators(&self, program: &Program) -> Vec<Box<dyn Annotator>> {
+        let mut annotators: Vec<Box<dyn Annotator>> = Vec::new();
+        if self.char_imms {
+            annotators.push(Box::new(CharLiterals));
+        }
+        if self.bitfields {
+            annotators.push(Box::new(BitFields));
+        }
+        if self.show_targets {
+            annotators.push(Box::new(Targets(self.addr_radix)));
+        }
+        if self.rel_targets {
+            annotators.push(Box::new(RelativeTargets));
+        }
+        if self.explain {
+            annotators.push(Box::new(BranchConditions));
+            annotators.push(Box::new(StackSlots(self.operand_radix)));
+        }
+        if self.zero_compares {
+            annotators.push(Box::new(ZeroCompares));
+        }
+        if self.show_encoding {
+            annotators.push(Box::new(Encodings));
+        }
+        if self.loops {
+            annotators.push(Box::new(Loops::new(program, self.addr_radix)));
+        }
+        if self.jump_tables {
+            annotators.push(Box::new(JumpTables::new(program)));
+        }
+        if self.zero_register {
+            annotators.push(Box::new(ZeroRegister::new(program)));
+        }
+        if self.tail_calls {
+            annotators.push(Box::new(TailCalls::new(program)));
+        }
+        annotators
+    }
+}
+
+/// Something that can comment on instructions in a listing, such as an
+/// analysis that isn't built into the crate. Each annotator contributes at
+/// most one comment per line, after the `;`.
+///
+/// # Examples
+/// ```
+/// # use stew3d::disassemble;
+/// # use stew3d::instr::Instruction;
+/// # use stew3d::listing::{Annotator, Listing, ListingOptions};
+/// # use stew3d::program::Program;
+/// /// Counts the bytes left in the program after each instruction.
+/// struct Remaining;
+///
+/// impl Annotator for Remaining {
+///     fn annotate(&self, ins: &Instruction, program: &Program) -> Option<String> {
+///         let left = program.len_bytes() - ins.addr() - ins.size();
+///         Some(format!("{} left", left))
+///     }
+/// }
+///
+/// // outi 1; hlt
+/// let program = Program::from(disassemble(&[0xc1, 0x01, 0xc7]).unwrap());
+/// let opts = ListingOptions::default();
+/// let listing = Listing::new(&program, &opts).with(Remaining);
+/// assert_eq!(listing.line(&program[0]), "00:    c1 01    |   outi 1   ; 1 left");
+/// ```
+pub trait Annotator {
+    /// Comments on `ins`, which is part of `program`, or returns `None` to
+    /// leave it be.
+    fn annotate(&self, ins: &Instruction, program: &Program) -> Option<String>;
+}
+
+impl<A: Annotator + ?Sized> Annotator for &A {
+    fn annotate(&self, ins: &Instruction, program: &Program) -> Option<String> {
+        (**self).annotate(ins, program)
+    }
+}
+
+/// Comments each printable ASCII immediate with its character, e.g. `'A'`.
+pub struct CharLiterals;
+
+impl Annotator for CharLiterals {
+    fn annotate(&self, ins: &Instruction, _: &Program) -> Option<String> {
+        let literals = char_literals(ins);
+        if literals.is_empty() {
+            None
+        } else {
+            Some(literals.join("; "))
+        }
+    }
+}
+
+/// Comments the mask of each `ani`, `ori`, and `xri` with its bits, most
+/// significant first, in two nibbles, e.g. `[1010 1010]` for 0xaa.
+pub struct BitFields;
+
+impl Annotator for BitFields {
+    fn annotate(&self, ins: &Instruction, _: &Program) -> Option<String> {
+        match ins {
+            Instr(
+                _,
+                ANI_A | ANI_B | ANI_C | ORI_A | ORI_B | ORI_C | XRI_A | XRI_B | XRI_C,
+                Operands::One(mask),
+            ) => Some(format!("[{:04b} {:04b}]", mask >> 4, mask & 0x0f)),
+            _ => None,
+        }
+    }
+}
+
+/// Comments each jump with the address of its target, e.g. `-> 0x02`.
+pub struct Targets(pub AddrRadix);
+
+impl Annotator for Targets {
+    fn annotate(&self, ins: &Instruction, _: &Program) -> Option<String> {
+        match ins {
+            Jump(_, _, target, _) => Some(format!("-> {}", self.0.reference(*target as usize))),
+            _ => None,
+        }
+    }
+}
+
+/// Comments each jump with the distance from its end to its target, e.g. `-5`.
+pub struct RelativeTargets;
+
+impl Annotator for RelativeTargets {
+    fn annotate(&self, ins: &Instruction, _: &Program) -> Option<String> {
+        match ins {
+            Jump(addr, _, target, _) => {
+                let next = addr + ins.size();
+                Some(format!("{:+}", *target as isize - next as isize))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Comments each conditional jump with when it's taken, e.g. `taken if x != y`.
+pub struct BranchConditions;
+
+impl Annotator for BranchConditions {
+    fn annotate(&self, ins: &Instruction, _: &Program) -> Option<String> {
+        match ins {
+            Jump(_, op, _, _) if ins.is_conditional_branch() => {
+                Some(format!("taken if {}", branch_condition(*op)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Comments each stack-relative load or store with the slot it accesses, as
+/// given by [`Instruction::stack_access`].
+pub struct StackSlots(pub Radix);
+
+impl Annotator for StackSlots {
+    fn annotate(&self, ins: &Instruction, _: &Program) -> Option<String> {
+        ins.stack_access(self.0)
+    }
+}
+
+/// Comments each compare of a register against the zero register with the
+/// test it amounts to, e.g. `a == 0?` for `cmp a, z` (or `cmp z, a`), as it's
+/// the usual way of checking for zero.
+pub struct ZeroCompares;
+
+impl Annotator for ZeroCompares {
+    fn annotate(&self, ins: &Instruction, _: &Program) -> Option<String> {
+        let op = match ins {
+            Instr(_, op @ (CMP_A_Z | CMP_B_Z | CMP_C_Z | CMP_Z_A | CMP_Z_B | CMP_Z_C), _) => op,
+            _ => return None,
+        };
+        match op.semantics().operands {
+            [Operand::Reg(reg), Operand::Reg(Reg::Z)]
+            | [Operand::Reg(Reg::Z), Operand::Reg(reg)] => Some(format!("{} == 0?", reg)),
+            _ => None,
+        }
+    }
+}
+
+/// Comments each instruction with which byte encodes what, e.g.
+/// `opcode=0xc1 imm=0x01`.
+pub struct Encodings;
+
+impl Annotator for Encodings {
+    fn annotate(&self, ins: &Instruction, _: &Program) -> Option<String> {
+        match ins {
+            Jump(_, op, _, _) | Instr(_, op, _) => Some(encoding(*op, &ins.to_bytes())),
+            Label(_, _) => None,
+        }
+    }
+}
+
+/// Comments instructions with notes worked out ahead of time, each paired with
+/// the address of the instruction it's about, such as those read from a file.
+/// Labels are left be, and the notes for one instruction are joined into a
+/// single comment.
+pub struct Notes(pub Vec<(usize, String)>);
+
+impl Annotator for Notes {
+    fn annotate(&self, ins: &Instruction, _: &Program) -> Option<String> {
+        if matches!(ins, Label(_, _)) {
+            return None;
+        }
+        let notes: Vec<_> = self
+            .0
+            .iter()
+            .filter(|(addr, _)| *addr == ins.addr())
+            .map(|(_, note)| note.as_str())
+            .collect();
+        if notes.is_empty() {
+            None
+        } else {
+            Some(notes.join("; "))
+        }
+    }
+}
+
+/// Comments the first instruction of each loop's header with the extent of
+/// the loop's body, e.g. `loop header (body 0x02–0x05)`. The body is given by
+/// the addresses of the first and last instructions in the loop.
+pub struct Loops(Notes);
+
+impl Loops {
+    /// Finds the loops in `program`, writing addresses in `radix`.
+    pub fn new(program: &Program, radix: AddrRadix) -> Self {
+        let cfg = Cfg::new(program);
+        let notes = find_loops(&cfg)
+            .into_iter()
+            .map(|l| {
+                let header = &cfg.blocks[l.header];
+                let last = l
+                    .blocks
+                    .iter()
+                    .map(|&b| program[cfg.blocks[b].range.end - 1].addr())
+                    .max()
+                    .unwrap();
+                let note = format!(
+                    "loop header (body {}\u{2013}{})",
+                    radix.reference(header.start),
+                    radix.reference(last)
+                );
+                (header.start, note)
+            })
+            .collect();
+        Loops(Notes(notes))
+    }
+}
+
+impl Annotator for Loops {
+    fn annotate(&self, ins: &Instruction, program: &Program) -> Option<String> {
+        self.0.annotate(ins, program)
+    }
+}
+
+/// The fewest `jmp`s and `call`s in a row that [`JumpTables`] treats as a
+/// table.
+const JUMP_TABLE_MIN: usize = 3;
+
+/// Comments the first entry of each likely jump table with the number of
+/// entries and their targets, e.g. `jump table (3 entries): l0, l1, l2`. See
+/// [`find_jump_tables`].
+pub struct JumpTables(Notes);
+
+impl JumpTables {
+    /// Finds the likely jump tables in `program`.
+    pub fn new(program: &Program) -> Self {
+        let notes = find_jump_tables(program, JUMP_TABLE_MIN)
+            .into_iter()
+            .map(|table| {
+                let targets: Vec<&str> = program[table.clone()]
+                    .iter()
+                    .filter_map(|ins| match ins {
+                        Jump(_, _, _, label) => Some(label.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+                let note = format!(
+                    "jump table ({} entries): {}",
+                    targets.len(),
+                    targets.join(", ")
+                );
+                (program[table.start].addr(), note)
+            })
+            .collect();
+        JumpTables(Notes(notes))
+    }
+}
+
+impl Annotator for JumpTables {
+    fn annotate(&self, ins: &Instruction, program: &Program) -> Option<String> {
+        self.0.annotate(ins, program)
+    }
+}
+
+/// Points out each move or store of the zero register (e.g. `st z, a`), which
+/// always writes a zero. See [`zero_register_uses`].
+pub struct ZeroRegister(Notes);
+
+impl ZeroRegister {
+    /// Finds the uses of the zero register in `program`.
+    pub fn new(program: &Program) -> Self {
+        let notes = zero_register_uses(program)
+            .into_iter()
+            .map(|i| (program[i].addr(), String::from("note: z is zero register")))
+            .collect();
+        ZeroRegister(Notes(notes))
+    }
+}
+
+impl Annotator for ZeroRegister {
+    fn annotate(&self, ins: &Instruction, program: &Program) -> Option<String> {
+        self.0.annotate(ins, program)
+    }
+}
+
+/// Points out each likely tail call with the routine it passes control to,
+/// e.g. `tail call to l1`. See [`find_tail_calls`].
+pub struct TailCalls(Notes);
+
+impl TailCalls {
+    /// Finds the likely tail calls in `program`.
+    pub fn new(program: &Program) -> Self {
+        let notes = find_tail_calls(program)
+            .into_iter()
+            .filter_map(|i| match &program[i] {
+                Jump(addr, _, _, target) => Some((*addr, format!("tail call to {}", target))),
+                _ => None,
+            })
+            .collect();
+        TailCalls(Notes(notes))
+    }
+}
+
+impl Annotator for TailCalls {
+    fn annotate(&self, ins: &Instruction, program: &Program) -> Option<String> {
+        self.0.annotate(ins, program)
+    }
+}
+
+/// A listing of a program, laid out according to `opts`. The annotators the
+/// options call for are built once, along with the listing, and more can be
+/// added after them with [`with`](Listing::with).
+pub struct Listing<'a> {
+    program: &'a Program,
+    opts: &'a ListingOptions,
+    annotators: Vec<Box<dyn Annotator + 'a>>,
+}
+
+impl<'a> Listing<'a> {
+    /// Lays out `program` according to `opts`.
+    pub fn new(program: &'a Program, opts: &'a ListingOptions) -> Self {
+        let annotators = opts
+            .annotators(program)
+            .into_iter()
+            .map(|annotator| annotator as Box<dyn Annotator + 'a>)
+            .collect();
+        Listing {
+            program,
+            opts,
+            annotators,
+        }
+    }
+
+    /// Adds an annotator, whose comments follow those of the annotators
+    /// before it.
+    pub fn with(mut self, annotator: impl Annotator + 'a) -> Self {
+        self.annotators.push(Box::new(annotator));
+        self
+    }
+
+    /// Formats the line of the listing for `ins`, which is part of the
+    /// program, with the comments of each annotator.
+    pub fn line(&self, ins: &Instruction) -> String {
+        let comments: Vec<_> = self
+            .annotators
+            .iter()
+            .filter_map(|annotator| annotator.annotate(ins, self.program))
+            .collect();
+        format_line(ins, self.opts, &comments)
+    }
+}
+
+/// Writes a listing of the whole program to `w`, one line per instruction or
+/// label, laid out according to `opts`. The comments of the given
+/// `annotators` follow those the options call for.
+///
+/// # Examples
+/// ```
+/// # use stew3d::disassemble;
+/// # use stew3d::listing::{write_listing, ListingOptions};
+/// # use stew3d::program::Program;
+/// // outi 1; hlt
+/// let program = Program::from(disassemble(&[0xc1, 0x01, 0xc7]).unwrap());
+/// let mut out = Vec::new();
+/// write_listing(&mut out, &program, &ListingOptions::default(), &[]).unwrap();
+/// assert_eq!(
+///     String::from_utf8(out).unwrap(),
+///     "00:    c1 01    |   outi 1\n02:    c7       |   hlt\n",
+/// );
+/// ```
+pub fn write_listing<W: Write>(
+    w: &mut W,
+    program: &Program,
+    opts: &ListingOptions,
+    annotators: &[&dyn Annotator],
+) -> io::Result<()> {
+    let listing = annotators
+        .iter()
+        .fold(Listing::new(program, opts), |listing, annotator| {
+            listing.with(*annotator)
+        });
+    for ins in program {
+        writeln!(w, "{}", listing.line(ins))?;
+    }
+    Ok(())
+}
+
+/// Formats one line of a listing: the address, the raw bytes, and the
+/// disassembled instruction, followed by the given `comments`. A [`Listing`]
+/// works out the comments the options call for.
+pub fn format_line(ins: &Instruction, opts: &ListingOptions, comments: &[String]) -> String {
+    let text = match ins {
+        Instr(_, _, _) if opts.sp_syntax => {
+            format!("{}{}", opts.indent, ins.canonical_sp_in(opts.operand_radix))
+        }
+        _ => ins.render_in(&opts.indent, opts.operand_radix),
+    };
+    let line = if opts.show_bytes {
+        let bytes_str = ins
+            .to_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!(
+            "{:6} {:width$} | {}",
+            format!("{}:", address_column(ins.addr(), opts)),
+            bytes_str,
+            text,
+            width = BYTES_WIDTH
+        )
+    } else {
+        format!("{}: {}", address_column(ins.addr(), opts), text)
+    };
+
+    if comments.is_empty() {
+        line
+    } else {
+        format!("{}   ; {}", line, comments.join("; "))
+    }
+}
+
+/// Formats the line of the listing that stands in for `len` bytes of padding
+/// starting at `addr`, as a `.zero` directive. Only the first of the bytes is
+/// shown.
+///
+/// # Examples
+/// ```
+/// # use stew3d::listing::{format_padding, ListingOptions};
+/// let opts = ListingOptions::default();
+/// assert_eq!(format_padding(0x10, 20, &opts), "10:    00 ..    |   .zero 20");
+/// ```
+pub fn format_padding(addr: usize, len: usize, opts: &ListingOptions) -> String {
+    let directive = format!("{}.zero {}", opts.indent, len);
+    if opts.show_bytes {
+        format!(
+            "{:6} {:width$} | {}",
+            format!("{}:", address_column(addr, opts)),
+            "00 ..",
+            directive,
+            width = BYTES_WIDTH
+        )
+    } else {
+        format!("{}: {}", address_column(addr, opts), directive)
+    }
+}
+
+/// Formats the line of the listing that stands in for `bytes` of data starting
+/// at `addr`, as a data directive (`.byte` by default) listing each of them in
+/// the operand base.
+/// Only the first few are shown in the byte column.
+///
+/// # Examples
+/// ```
+/// # use stew3d::listing::{format_data, ListingOptions};
+/// let opts = ListingOptions::default();
+/// assert_eq!(
+///     format_data(0x03, &[0xff, 0x12, 0x34, 0x56], &opts),
+///     "03:    ff 12 .. |   .byte 255, 18, 52, 86"
+/// );
+/// ```
+pub fn format_data(addr: usize, bytes: &[u8], opts: &ListingOptions) -> String {
+    let values: Vec<_> = bytes
+        .iter()
+        .map(|&byte| opts.operand_radix.format(byte))
+        .collect();
+    let directive = format!(
+        "{}{} {}",
+        opts.indent,
+        opts.data_directive,
+        values.join(", ")
+    );
+    if opts.show_bytes {
+        let shown = if bytes.len() > Opcode::MAX_SIZE {
+            2
+        } else {
+            bytes.len()
+        };
+        let mut bytes_str: Vec<_> = bytes[..shown]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        if shown < bytes.len() {
+            bytes_str.push(String::from(".."));
+        }
+        format!(
+            "{:6} {:width$} | {}",
+            format!("{}:", address_column(addr, opts)),
+            bytes_str.join(" "),
+            directive,
+            width = BYTES_WIDTH
+        )
+    } else {
+        format!("{}: {}", address_column(addr, opts), directive)
+    }
+}
+
+/// Writes an address for the address column, either absolutely or as an
+/// offset, according to `opts`.
+fn address_column(addr: usize, opts: &ListingOptions) -> String {
+    let base = match opts.offset_from {
+        Some(base) => base,
+        None => return opts.addr_radix.column(addr),
+    };
+    let (sign, offset) = if addr >= base {
+        ('+', addr - base)
+    } else {
+        ('-', base - addr)
+    };
+    match opts.addr_radix {
+        AddrRadix::Hex => format!("{}{:x}", sign, offset),
+        AddrRadix::Dec => format!("{}{}", sign, offset),
+    }
+}
+
+/// Writes each immediate value of an instruction that is printable ASCII as a
+/// character literal, such as `'A'` for the 65 in `mvi 65, a`. Stack offsets
+/// and jump targets are addresses rather than values, so they're skipped.
+fn char_literals(ins: &Instruction) -> Vec<String> {
+    let (op, imms) = match ins {
+        Instr(_, op, Operands::One(first)) => (op, vec![*first]),
+        Instr(_, op, Operands::Two(first, second)) => (op, vec![*first, *second]),
+        _ => return Vec::new(),
+    };
+
+    op.semantics()
+        .operands
+        .iter()
+        .filter(|operand| !matches!(operand, Operand::Reg(_)))
+        .zip(imms)
+        .filter(|(operand, byte)| **operand == Operand::Imm && (0x20..=0x7e).contains(byte))
+        .map(|(_, byte)| match byte {
+            b'\'' | b'\\' => format!("'\\{}'", byte as char),
+            _ => format!("'{}'", byte as char),
+        })
+        .collect()
+}
+
+/// Describes which byte of an instruction encodes which part of it, such as
+/// `opcode=0x0c imm=0x04` for `addi 4, a`. Register operands are part of the
+/// opcode, so only immediates and jump targets get their own byte.
+fn encoding(op: Opcode, bytes: &[u8]) -> String {
+    let parts = op
+        .semantics()
+        .operands
+        .iter()
+        .filter_map(|operand| match operand {
+            Operand::Reg(_) => None,
+            Operand::Imm => Some("imm"),
+            Operand::Offset => Some("offset"),
+            Operand::Target => Some("target"),
+        });
+
+    let mut encoding = format!("opcode=0x{:02x}", bytes[0]);
+    for (part, byte) in parts.zip(&bytes[1..]) {
+        encoding.push_str(&format!(" {}=0x{:02x}", part, byte));
+    }
+    encoding
+}
+
+/// Describes when a conditional jump is taken, in terms of the most recent
+/// comparison of `x` against `y` (e.g. `cmp x, y`).
+fn branch_condition(op: Opcode) -> &'static str {
+    match op {
+        JE => "x == y",
+        JNE => "x != y",
+        JG => "x > y (signed)",
+        JGE => "x >= y (signed)",
+        JL => "x < y (signed)",
+        JLE => "x <= y (signed)",
+        JA => "x > y (unsigned)",
+        JAE => "x >= y (unsigned)",
+        JB => "x < y (unsigned)",
+        JBE => "x <= y (unsigned)",
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::disassemble;
+
+    /// The countdown loop from the `stats` tests, with a halt on the end.
+    const LOOP: [u8; 8] = [0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7];
+
+    /// Lists `ins` as the whole of a program.
+    fn line_alone(ins: Instruction, opts: &ListingOptions) -> String {
+        let program = Program::from(vec![ins]);
+        let line = Listing::new(&program, opts).line(&program[0]);
+        line
+    }
+
+    #[test]
+    fn writes_whole_listing() {
+        let program = Program::from(disassemble(&LOOP).unwrap());
+        let opts = ListingOptions {
+            show_bytes: false,
+            show_targets: true,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        write_listing(&mut out, &program, &opts, &[]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "00:   mvi 255, a\n\
+             02: l0:\n\
+             02:   out a\n\
+             03:   dcr a\n\
+             04:   cmp a, z\n\
+             05:   jne l0   ; -> 0x02\n\
+             07:   hlt\n"
+        );
+    }
+
+    #[test]
+    fn custom_annotators() {
+        struct Halts;
+
+        impl Annotator for Halts {
+            fn annotate(&self, ins: &Instruction, _: &Program) -> Option<String> {
+                match ins {
+                    Instr(_, HLT, _) => Some(String::from("stops here")),
+                    _ => None,
+                }
+            }
+        }
+
+        let program = Program::from(disassemble(&LOOP).unwrap());
+        let opts = ListingOptions {
+            show_bytes: false,
+            show_targets: true,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        write_listing(&mut out, &program, &opts, &[&Halts, &Encodings]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "00:   mvi 255, a   ; opcode=0x7f imm=0xff\n\
+             02: l0:\n\
+             02:   out a   ; opcode=0xbe\n\
+             03:   dcr a   ; opcode=0x67\n\
+             04:   cmp a, z   ; opcode=0xa1\n\
+             05:   jne l0   ; -> 0x02; opcode=0xb3 target=0x02\n\
+             07:   hlt   ; stops here; opcode=0xc7\n"
+        );
+    }
+
+    #[test]
+    fn whole_program_notes() {
+        let program = Program::from(vec![
+            Label(0x00, String::from("l0")),
+            Instr(0x00, ST_Z_A, Operands::Zero),
+            Jump(0x01, JMP, 0x00, String::from("l0")),
+        ]);
+        let opts = ListingOptions {
+            show_bytes: false,
+            loops: true,
+            zero_register: true,
+            ..Default::default()
+        };
+        let listing = Listing::new(&program, &opts);
+
+        assert_eq!(listing.line(&program[0]), "00: l0:");
+        assert_eq!(
+            listing.line(&program[1]),
+            "00:   st z, a   ; loop header (body 0x00\u{2013}0x01); note: z is zero register"
+        );
+        assert_eq!(listing.line(&program[2]), "01:   jmp l0");
+    }
+
+    #[test]
+    fn jump_tables() {
+        // jmp l0; call l1; l0: jmp l1; l1: jmp l0; hlt
+        let program = Program::from(
+            disassemble(&[0xb1, 0x04, 0xbc, 0x06, 0xb1, 0x06, 0xb1, 0x04, 0xc7]).unwrap(),
+        );
+        let opts = ListingOptions {
+            show_bytes: false,
+            jump_tables: true,
+            ..Default::default()
+        };
+        let listing = Listing::new(&program, &opts);
+
+        assert_eq!(
+            listing.line(&program[0]),
+            "00:   jmp l0   ; jump table (4 entries): l0, l1, l1, l0"
+        );
+        assert!(program[1..]
+            .iter()
+            .all(|ins| !listing.line(ins).contains("jump table")));
+    }
+
+    #[test]
+    fn aligned_bytes() {
+        // hlt; stsi 5, 255; outi 1
+        let program = Program::from(disassemble(&[0xc7, 0x9e, 0x05, 0xff, 0xc1, 0x01]).unwrap());
+        let opts = ListingOptions::default();
+
+        let listing = Listing::new(&program, &opts);
+        let lines: Vec<_> = program.iter().map(|ins| listing.line(ins)).collect();
+        assert_eq!(
+            lines,
+            [
+                "00:    c7       |   hlt",
+                "01:    9e 05 ff |   stsi 5, 255",
+                "04:    c1 01    |   outi 1",
+            ]
+        );
+        let separators: Vec<_> = lines.iter().map(|line| line.find('|')).collect();
+        assert!(separators.iter().all(|&col| col == separators[0]));
+    }
+
+    #[test]
+    fn offset_column() {
+        let program = Program::from(disassemble(&LOOP).unwrap());
+        let opts = ListingOptions {
+            show_bytes: false,
+            show_targets: true,
+            offset_from: Some(0x02),
+            ..Default::default()
+        };
+        let listing = Listing::new(&program, &opts);
+        let line = |i: usize| listing.line(&program[i]);
+
+        assert_eq!(line(0), "-2:   mvi 255, a");
+        assert_eq!(line(2), "+0:   out a");
+        assert_eq!(line(5), "+3:   jne l0   ; -> 0x02");
+        assert_eq!(
+            format_padding(0x14, 20, &opts),
+            "+12:   .zero 20",
+            "offsets are in hex"
+        );
+    }
+
+    #[test]
+    fn zero_compares() {
+        let opts = ListingOptions {
+            show_bytes: false,
+            zero_compares: true,
+            ..Default::default()
+        };
+        let line = |ins| line_alone(ins, &opts);
+
+        assert_eq!(
+            line(Instr(0x04, CMP_A_Z, Operands::Zero)),
+            "04:   cmp a, z   ; a == 0?"
+        );
+        assert_eq!(
+            line(Instr(0x04, CMP_Z_C, Operands::Zero)),
+            "04:   cmp z, c   ; c == 0?"
+        );
+        assert_eq!(line(Instr(0x04, CMP_A_B, Operands::Zero)), "04:   cmp a, b");
+        assert_eq!(
+            line(Instr(0x04, CMPI_A_BYTE, Operands::One(0))),
+            "04:   cmpi a, 0"
+        );
+    }
+
+    #[test]
+    fn bitfields() {
+        let opts = ListingOptions {
+            show_bytes: false,
+            operand_radix: Radix::Hex,
+            bitfields: true,
+            ..Default::default()
+        };
+        let line = |ins| line_alone(ins, &opts);
+
+        assert_eq!(
+            line(Instr(0x00, ANI_A, Operands::One(0xaa))),
+            "00:   ani 0xaa, a   ; [1010 1010]"
+        );
+        assert_eq!(
+            line(Instr(0x00, XRI_C, Operands::One(0x01))),
+            "00:   xri 0x01, c   ; [0000 0001]"
+        );
+        // not a mask
+        assert_eq!(
+            line(Instr(0x00, ADDI_A, Operands::One(0xaa))),
+            "00:   addi 0xaa, a"
+        );
+    }
+
+    #[test]
+    fn padding() {
+        let opts = ListingOptions {
+            show_bytes: false,
+            indent: String::from("\t"),
+            ..Default::default()
+        };
+        assert_eq!(format_padding(0x14, 20, &opts), "14: \t.zero 20");
+    }
+
+    #[test]
+    fn data_directive() {
+        let opts = ListingOptions {
+            show_bytes: false,
+            operand_radix: Radix::Hex,
+            data_directive: String::from("db"),
+            ..Default::default()
+        };
+        assert_eq!(format_data(0x02, &[0xff], &opts), "02:   db 0xff");
+    }
+
+    #[test]
+    fn relative_targets() {
+        let program = Program::from(disassemble(&LOOP).unwrap());
+        let opts = ListingOptions {
+            show_bytes: false,
+            show_targets: true,
+            rel_targets: true,
+            ..Default::default()
+        };
+
+        // back from the end of `jne l0` (0x07) to 0x02
+        assert_eq!(
+            Listing::new(&program, &opts).line(&program[5]),
+            "05:   jne l0   ; -> 0x02; -5"
+        );
+        let skip = Jump(0x00, JMP, 0x03, String::from("l0"));
+        assert_eq!(line_alone(skip, &opts), "00:   jmp l0   ; -> 0x03; +1");
+    }
+
+    #[test]
+    fn char_immediates() {
+        let opts = ListingOptions {
+            show_bytes: false,
+            char_imms: true,
+            ..Default::default()
+        };
+        let line = |ins| line_alone(ins, &opts);
+
+        assert_eq!(
+            line(Instr(0x00, MVI_A, Operands::One(0x41))),
+            "00:   mvi 65, a   ; 'A'"
+        );
+        assert_eq!(
+            line(Instr(0x00, OUTI, Operands::One(0x27))),
+            "00:   outi 39   ; '\\''"
+        );
+        // not printable
+        assert_eq!(
+            line(Instr(0x00, MVI_A, Operands::One(0x0a))),
+            "00:   mvi 10, a"
+        );
+        // only the value, not the stack slot
+        assert_eq!(
+            line(Instr(0x00, STSI, Operands::Two(0x41, 0x42))),
+            "00:   stsi 65, 66   ; 'A'"
+        );
+    }
+
+    #[test]
+    fn radixes() {
+        let program = Program::from(disassemble(&LOOP).unwrap());
+        let opts = ListingOptions {
+            addr_radix: AddrRadix::Dec,
+            operand_radix: Radix::Hex,
+            show_targets: true,
+            ..Default::default()
+        };
+
+        let listing =
+            Listing::new(&program, &opts).with(Notes(vec![(0x05, String::from("again"))]));
+        assert_eq!(listing.line(&program[0]), "0:     7f ff    |   mvi 0xff, a");
+        assert_eq!(
+            listing.line(&program[5]),
+            "5:     b3 02    |   jne l0   ; -> 2; again"
+        );
+    }
+}
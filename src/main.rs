@@ -1,19 +1,32 @@
-use anyhow::Result;
-use bimap::BiMap;
-use instr::Instruction::{self, *};
-use instr::Operands::*;
-use opcode::Opcode::{self, *};
-use stats::BinaryStats;
-use std::convert::TryInto;
-use std::fmt;
+use anyhow::{Context, Result};
 use std::fs::File;
-use std::io::{self, Read};
+use std::io::{self, BufRead, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use stew3d::analysis::{find_data_after_jumps, find_opcodes, find_padding};
+use stew3d::cfg::Cfg;
+use stew3d::diff::{diff, Change};
+use stew3d::emit::{html, json, json_string, srec};
+use stew3d::hash::{checksum, sha256, to_hex, ChecksumAlgo};
+use stew3d::hex::{parse_hex, parse_hex_text, parse_pattern, scan};
+use stew3d::instr::Instruction::{self, *};
+use stew3d::instr::Radix;
+use stew3d::lint::lint;
+use stew3d::listing::{format_data, format_padding, AddrRadix, Listing, ListingOptions, Notes};
+use stew3d::opcode::Opcode;
+use stew3d::program::{Program, COUNTDOWN};
+use stew3d::semantics::Operand;
+use stew3d::stats::{write_stats, BinaryStats, HistogramOrder, OpcodeHistogram};
+use stew3d::warning::Warning;
+use stew3d::{
+    decode_one, disassemble_recursive_with_labels, disassemble_with, DecodeOptions, Disassembly,
+    Error, LabelOptions,
+};
 use structopt::StructOpt;
 
-mod instr;
-mod opcode;
-mod stats;
-
 #[derive(StructOpt, Debug)]
 #[structopt(name = "stew3d")]
 #[doc(hidden)]
@@ -22,218 +35,1561 @@ struct Opt {
     #[structopt(name = "FILE")]
     file: Option<String>,
 
+    /// Disassemble the given whitespace-separated hex bytes (e.g. "c1 01 c7")
+    /// instead of reading a file or stdin.
+    #[structopt(long, value_name = "BYTES", conflicts_with = "FILE")]
+    hex: Option<String>,
+
+    /// Keep watching the file, and clear the screen and disassemble it again
+    /// each time it changes.
+    #[structopt(long, requires = "FILE")]
+    watch: bool,
+
+    /// Disassemble a built-in sample program (a countdown loop) with its
+    /// statistics, instead of reading a file or stdin.
+    #[structopt(long, conflicts_with_all = &["FILE", "hex"])]
+    demo: bool,
+
+    /// How the input is encoded: `binary`, or `hextext` for whitespace-separated
+    /// hex bytes with `;`/`#` comments.
+    #[structopt(long, value_name = "FORMAT", default_value = "binary")]
+    format: InputFormat,
+
+    /// Ignore the first N bytes of the input (e.g. a header). The byte after
+    /// them is disassembled as address 0.
+    #[structopt(long, value_name = "N", default_value = "0")]
+    skip: usize,
+
+    /// Only use N bytes of the input (after any --skip), ignoring the rest.
+    #[structopt(long, value_name = "N")]
+    length: Option<usize>,
+
     /// Show statistics about the binary.
     #[structopt(short, long)]
     stats: bool,
+
+    /// How to print statistics: `text`, or `csv` for a single row of CSV
+    /// (printed instead of the listing).
+    #[structopt(long, value_name = "FORMAT", default_value = "text")]
+    stats_format: StatsFormat,
+
+    /// Stop decoding at the first invalid instruction, with a warning, instead
+    /// of failing. With --stats, also reports how much of the input decoded.
+    #[structopt(long)]
+    lenient: bool,
+
+    /// Print a header row naming the columns before the CSV statistics.
+    #[structopt(long)]
+    csv_header: bool,
+
+    /// Include the SHA-256 of the program in the header.
+    #[structopt(long)]
+    hash: bool,
+
+    /// Show how often each opcode is used.
+    #[structopt(long)]
+    histogram: bool,
+
+    /// How to order the opcode histogram: `count`, `opcode`, or `mnemonic`.
+    #[structopt(long, value_name = "ORDER", default_value = "count")]
+    sort: HistogramOrder,
+
+    /// Only show the first N rows of the opcode histogram.
+    #[structopt(long, value_name = "N")]
+    top: Option<usize>,
+
+    /// Comment each jump with the address of its target.
+    #[structopt(long)]
+    show_targets: bool,
+
+    /// Comment each jump with how far it jumps, from the end of the jump to
+    /// its target (negative for backward jumps).
+    #[structopt(long)]
+    rel_targets: bool,
+
+    /// Comment each instruction with the byte encoding each of its parts,
+    /// e.g. `opcode=0xc1 imm=0x01`.
+    #[structopt(long)]
+    show_encoding: bool,
+
+    /// Comment each immediate that is a printable ASCII character with it as a
+    /// character literal (e.g. `mvi 65, a   ; 'A'`).
+    #[structopt(long)]
+    char_imm: bool,
+
+    /// Comment the mask of each `ani`, `ori`, and `xri` with its bits (e.g.
+    /// `ani 170, a   ; [1010 1010]`).
+    #[structopt(long)]
+    bitfield: bool,
+
+    /// Comment each compare against the zero register with the test it
+    /// amounts to (e.g. `cmp a, z   ; a == 0?`).
+    #[structopt(long)]
+    simplify_cmp_z: bool,
+
+    /// Write the stack offsets of `lds`, `sts`, and `stsi` relative to the
+    /// stack pointer (e.g. `lds [sp+4], a` instead of `lds 4, a`).
+    #[structopt(long)]
+    sp_syntax: bool,
+
+    /// The string to indent instructions with, or a number of spaces.
+    #[structopt(long, value_name = "STR", default_value = "2", parse(from_str = parse_indent))]
+    indent: String,
+
+    /// Print only the listing, without the header or statistics.
+    #[structopt(short, long, alias = "no-header")]
+    quiet: bool,
+
+    /// Leave out the column of raw bytes, printing just the address and the
+    /// instruction on each line.
+    #[structopt(long)]
+    no_bytes: bool,
+
+    /// Add explanatory comments to the listing. `-v` shows the address each
+    /// jump targets (like --show-targets) and points out likely tail calls,
+    /// `-vv` also spells out the condition under which each conditional jump
+    /// is taken and the stack slot each `lds`/`sts`/`stsi` accesses, and
+    /// `-vvv` also shows each instruction's encoding (like --show-encoding).
+    #[structopt(short, long, parse(from_occurrences))]
+    verbose: u8,
+
+    /// The prefix used when generating label names. Defaults to `l`, or
+    /// `loc_` when using --addr-labels.
+    #[structopt(long, value_name = "STR")]
+    label_prefix: Option<String>,
+
+    /// A file of user-chosen label names, one `0xADDR NAME` pair per line.
+    /// These replace generated labels at the same addresses.
+    #[structopt(long, value_name = "FILE")]
+    symbols: Option<String>,
+
+    /// A file of entry points, one `0xADDR` per line. Only code reachable from
+    /// them (and address 0) is disassembled, by following jumps and calls, so
+    /// data between routines isn't mistaken for instructions.
+    #[structopt(long, value_name = "FILE")]
+    entrypoints: Option<String>,
+
+    /// A file of notes to attach to the listing, one `0xADDR: comment` per
+    /// line. Each note is added as a comment on the instruction at its address.
+    #[structopt(long, value_name = "FILE")]
+    annotate: Option<String>,
+
+    /// Name labels after the address they point at (e.g. `loc_05`).
+    #[structopt(long)]
+    addr_labels: bool,
+
+    /// The name of the label marking the entry point (address 0).
+    #[structopt(long, value_name = "NAME", default_value = "entry")]
+    entry_label: String,
+
+    /// Don't label the entry point unless something jumps to it.
+    #[structopt(long)]
+    no_entry_label: bool,
+
+    /// Group the listing by subroutine, starting with the top-level code.
+    #[structopt(long)]
+    by_function: bool,
+
+    /// Print only the labels, one `0xADDR NAME` pair per line (the format of
+    /// --symbols, so always in hex), instead of the listing.
+    #[structopt(long, conflicts_with = "only-code")]
+    only_labels: bool,
+
+    /// Leave the labels out of the listing.
+    #[structopt(long)]
+    only_code: bool,
+
+    /// List each run of zero bytes (which decode as `add a, a`) as a single
+    /// `.zero N` directive, as such runs are most likely padding.
+    #[structopt(long)]
+    detect_padding: bool,
+
+    /// The fewest zero bytes in a row that --detect-padding treats as padding.
+    #[structopt(long, value_name = "N", default_value = "8")]
+    padding_threshold: usize,
+
+    /// List whatever follows a `jmp` or `ret` up to the next label as `.byte`
+    /// data, since nothing falls through or jumps to it. Bytes there that don't
+    /// decode are data too, up to the next jump target, where decoding resumes.
+    #[structopt(long)]
+    data_after_jump: bool,
+
+    /// The directive to list data bytes with, to match an assembler (e.g.
+    /// `db` or `dc.b`).
+    #[structopt(long, value_name = "NAME", default_value = ".byte")]
+    data_directive: String,
+
+    /// How to order the listing: `address`, or `opcode` to group every use of
+    /// each opcode together, in order of encoding (leaving out labels).
+    #[structopt(
+        long,
+        value_name = "ORDER",
+        default_value = "address",
+        conflicts_with = "by-function"
+    )]
+    sort_by: ListingOrder,
+
+    /// Only list the code from the given label up to the next `jmp`, `ret`,
+    /// or `hlt` (or up to --until-label, if given).
+    #[structopt(long, value_name = "NAME", conflicts_with = "by-function")]
+    since_label: Option<String>,
+
+    /// Stop listing just before the given label.
+    #[structopt(long, value_name = "NAME", requires = "since-label")]
+    until_label: Option<String>,
+
+    /// Print every opcode in the instruction set, with its encoding, assembly
+    /// form, and size in bytes, instead of disassembling anything.
+    #[structopt(long)]
+    list_opcodes: bool,
+
+    /// Print the opcodes whose assembly form contains the given text (e.g.
+    /// `sp`), in the same form as --list-opcodes, instead of disassembling
+    /// anything.
+    #[structopt(long, value_name = "SUBSTR")]
+    find: Option<String>,
+
+    /// How to write addresses, in the listing, warnings, and reports: `hex` or
+    /// `dec`. --only-labels always writes hex, so it can be read back as a
+    /// symbol file.
+    #[structopt(long, value_name = "RADIX", default_value = "hex")]
+    addr_radix: AddrRadix,
+
+    /// How to write immediate operands: `dec`, `hex`, or `bin`.
+    #[structopt(long, value_name = "RADIX", default_value = "dec")]
+    operand_radix: Radix,
+
+    /// Print warnings about an instruction as a comment on its line in the
+    /// listing, instead of on stderr.
+    #[structopt(long)]
+    inline_warnings: bool,
+
+    /// Suppress the given kind of warning (e.g. `no-halt`). May be given more
+    /// than once.
+    #[structopt(
+        long,
+        value_name = "WARNING",
+        number_of_values = 1,
+        parse(try_from_str = parse_warning_kind)
+    )]
+    allow: Vec<String>,
+
+    /// Comment the first instruction of each loop with the addresses of the
+    /// instructions in the loop.
+    #[structopt(long)]
+    loops: bool,
+
+    /// Comment the first entry of each run of three or more `jmp`s and
+    /// `call`s (likely a dispatch table) with the targets of the entries.
+    #[structopt(long)]
+    jump_tables: bool,
+
+    /// Check that every operand is acceptable for its opcode, failing if not.
+    #[structopt(long)]
+    validate: bool,
+
+    /// Fail if the program uses any instruction with the given mnemonic (e.g.
+    /// `out`), listing where. May be given more than once.
+    #[structopt(long, value_name = "MNEMONIC", number_of_values = 1)]
+    assert_no: Vec<String>,
+
+    /// Check the program for stylistic problems, reporting them on stderr, and
+    /// note moves and stores of the zero register in the listing.
+    #[structopt(long)]
+    lint: bool,
+
+    /// Interactively decode lines of hex bytes typed on stdin.
+    #[structopt(long)]
+    repl: bool,
+
+    /// Search the input for a byte signature, with `??` matching any byte (e.g.
+    /// "7f ?? c7"), and disassemble each match instead of the whole program.
+    #[structopt(long, value_name = "PATTERN")]
+    scan: Option<String>,
+
+    /// Give each address in the address column as an offset from the given
+    /// address (in hex), e.g. `+3`, for quoting part of a listing. Addresses
+    /// in comments are still absolute.
+    #[structopt(long, value_name = "ADDR", parse(try_from_str = parse_addr))]
+    offset_from: Option<usize>,
+
+    /// Check the checksum byte stored at the given address (in hex) against
+    /// the checksum of every other byte of the input, instead of
+    /// disassembling it.
+    #[structopt(long, value_name = "ADDR", parse(try_from_str = parse_addr))]
+    verify_crc: Option<usize>,
+
+    /// The checksum --verify-crc expects: `sum` (of the bytes, modulo 256) or
+    /// `crc8`.
+    #[structopt(long, value_name = "ALGO", default_value = "sum")]
+    crc_algo: ChecksumAlgo,
+
+    /// Compare the program against another one (in the same input format),
+    /// printing the instructions removed (`-`), added (`+`), and shared by
+    /// both, with their address in each.
+    #[structopt(long, value_name = "OTHER")]
+    diff: Option<String>,
+
+    /// What to output: `listing` for the disassembly, `srec` for the
+    /// program's bytes as Motorola S-records, `html` for the disassembly as
+    /// a web page whose jumps link to their targets, or `json` for the
+    /// instructions and what each byte of the input is.
+    #[structopt(long, value_name = "FORMAT", default_value = "listing")]
+    emit: Emit,
+
+    /// How to report errors: `human` or `json`.
+    #[structopt(long, value_name = "FORMAT", default_value = "human")]
+    error_format: ErrorFormat,
+}
+
+impl Opt {
+    /// Gathers the options that control how each line of the listing is laid
+    /// out.
+    fn listing_options(&self) -> ListingOptions {
+        ListingOptions {
+            indent: self.indent.clone(),
+            show_bytes: !self.no_bytes,
+            addr_radix: self.addr_radix,
+            operand_radix: self.operand_radix,
+            data_directive: self.data_directive.clone(),
+            show_targets: self.show_targets || self.verbose >= 1,
+            rel_targets: self.rel_targets,
+            explain: self.verbose >= 2,
+            show_encoding: self.show_encoding || self.verbose >= 3,
+            char_imms: self.char_imm,
+            bitfields: self.bitfield,
+            sp_syntax: self.sp_syntax,
+            zero_compares: self.simplify_cmp_z,
+            loops: self.loops,
+            jump_tables: self.jump_tables,
+            zero_register: self.lint,
+            tail_calls: self.verbose >= 1,
+            offset_from: self.offset_from,
+        }
+    }
+}
+
+/// Interprets the argument to --indent: a number means that many spaces, and
+/// anything else is used as-is.
+fn parse_indent(s: &str) -> String {
+    match s.parse() {
+        Ok(width) => " ".repeat(width),
+        Err(_) => s.to_string(),
+    }
+}
+
+/// Checks that the argument to --allow names a kind of warning.
+fn parse_warning_kind(s: &str) -> Result<String, String> {
+    if Warning::KINDS.contains(&s) {
+        Ok(s.to_string())
+    } else {
+        Err(format!(
+            "unknown warning `{}` (expected one of: {})",
+            s,
+            Warning::KINDS.join(", ")
+        ))
+    }
+}
+
+/// Interprets an address given as an argument, in hex with an optional `0x`
+/// (as in symbol and annotation files).
+fn parse_addr(s: &str) -> Result<usize, std::num::ParseIntError> {
+    usize::from_str_radix(s.trim_start_matches("0x"), 16)
+}
+
+/// The formats in which the input program can be given.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum InputFormat {
+    Binary,
+    HexText,
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "binary" => Ok(InputFormat::Binary),
+            "hextext" => Ok(InputFormat::HexText),
+            _ => Err(format!("unknown input format `{}`", s)),
+        }
+    }
+}
+
+/// The formats in which statistics can be printed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum StatsFormat {
+    Text,
+    Csv,
+}
+
+impl FromStr for StatsFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(StatsFormat::Text),
+            "csv" => Ok(StatsFormat::Csv),
+            _ => Err(format!("unknown stats format `{}`", s)),
+        }
+    }
+}
+
+/// The orders in which the listing can be printed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ListingOrder {
+    Address,
+    Opcode,
+}
+
+impl FromStr for ListingOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "address" => Ok(ListingOrder::Address),
+            "opcode" => Ok(ListingOrder::Opcode),
+            _ => Err(format!("unknown listing order `{}`", s)),
+        }
+    }
+}
+
+/// The forms in which the program can be output.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Emit {
+    Listing,
+    Srec,
+    Html,
+    Json,
+}
+
+impl FromStr for Emit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "listing" => Ok(Emit::Listing),
+            "srec" => Ok(Emit::Srec),
+            "html" => Ok(Emit::Html),
+            "json" => Ok(Emit::Json),
+            _ => Err(format!("unknown output format `{}`", s)),
+        }
+    }
+}
+
+/// The formats in which a fatal error can be reported on stderr.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!("unknown error format `{}`", s)),
+        }
+    }
 }
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+    let opt = Opt::from_args();
+    if opt.watch {
+        watch(&opt);
+    }
+    if let Err(e) = run(&opt) {
+        report_error(&opt, &e);
+        std::process::exit(exit_code(&e));
     }
 }
 
+/// Prints a fatal error on stderr, in the format asked for.
+fn report_error(opt: &Opt, e: &anyhow::Error) {
+    match opt.error_format {
+        ErrorFormat::Human => {
+            eprintln!("Error: {}", e);
+            if let Some(Error::InvalidOpcode(_, _)) = e.downcast_ref::<Error>() {
+                eprintln!(
+                    "hint: use --lenient to stop decoding at the invalid byte \
+                     with a warning, instead of failing"
+                );
+            }
+        }
+        ErrorFormat::Json => eprintln!("{}", error_json(e)),
+    }
+}
+
+/// How often --watch checks whether the file has changed.
+const WATCH_POLL: Duration = Duration::from_millis(200);
+
+/// How long the file must go without changing again before --watch
+/// disassembles it, so that a save made of several writes is shown once.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Disassembles the file each time it changes, forever. Errors are reported
+/// but don't stop the watch, since the next save may well fix them.
+fn watch(opt: &Opt) -> ! {
+    let path = Path::new(opt.file.as_deref().expect("--watch requires a file"));
+    watch_with(path, WATCH_POLL, WATCH_DEBOUNCE, || {
+        // Clear the screen and move the cursor to the top left.
+        print!("\x1b[2J\x1b[H");
+        if let Err(e) = run(opt) {
+            report_error(opt, &e);
+        }
+        let _ = io::stdout().flush();
+        true
+    });
+    unreachable!("the watch never stops");
+}
+
+/// Calls `render` once, and again each time the file at `path` changes, until
+/// it returns false. A change made while `render` runs still counts.
+fn watch_with<F: FnMut() -> bool>(path: &Path, poll: Duration, debounce: Duration, mut render: F) {
+    loop {
+        let stamp = file_stamp(path);
+        if !render() {
+            return;
+        }
+        wait_for_change(path, stamp, poll, debounce);
+    }
+}
+
+/// The modification time and size of a file, one of which changes whenever
+/// it's written, or `None` if it can't be read (e.g. while an editor is
+/// replacing it).
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Blocks until the file at `path` no longer has the stamp `before`, checking
+/// every `poll`, and then until it has gone `debounce` without changing again.
+fn wait_for_change(
+    path: &Path,
+    before: Option<(SystemTime, u64)>,
+    poll: Duration,
+    debounce: Duration,
+) {
+    let mut stamp = before;
+    while stamp == before {
+        thread::sleep(poll);
+        stamp = file_stamp(path);
+    }
+    loop {
+        thread::sleep(debounce);
+        let next = file_stamp(path);
+        if next == stamp && next.is_some() {
+            return;
+        }
+        stamp = next;
+    }
+}
+
+/// Determines the exit code for a fatal error, so that scripts can tell
+/// disassembly failures apart: 2 for an invalid opcode, 3 for input that ends
+/// in the middle of an instruction, and 1 for anything else (e.g. I/O errors).
+fn exit_code(e: &anyhow::Error) -> i32 {
+    match e.downcast_ref::<Error>() {
+        Some(Error::InvalidOpcode(_, _)) => 2,
+        Some(Error::UnexpectedEndOfFile(_)) => 3,
+        None => 1,
+    }
+}
+
+/// Renders a fatal error as a single-line JSON object with its kind, the
+/// address and opcode byte involved (or `null` if not applicable), and the
+/// human-readable message.
+fn error_json(e: &anyhow::Error) -> String {
+    let (kind, addr, opcode) = match e.downcast_ref::<Error>() {
+        Some(Error::InvalidOpcode(opcode, addr)) => {
+            ("invalid_opcode", addr.to_string(), opcode.to_string())
+        }
+        Some(Error::UnexpectedEndOfFile(opcode)) => (
+            "unexpected_end_of_file",
+            String::from("null"),
+            opcode.to_byte().to_string(),
+        ),
+        None => ("other", String::from("null"), String::from("null")),
+    };
+
+    format!(
+        "{{\"kind\": \"{}\", \"address\": {}, \"opcode\": {}, \"message\": {}}}",
+        kind,
+        addr,
+        opcode,
+        json_string(&e.to_string())
+    )
+}
+
 /// Reads the file given by command line arguments and invokes the disassembler on its contents.
-fn run() -> Result<()> {
-    let opt = Opt::from_args();
-    let mut buffer = Vec::new();
+fn run(opt: &Opt) -> Result<()> {
+    if opt.list_opcodes {
+        print!("{}", opcode_table());
+        return Ok(());
+    }
+
+    if let Some(ref substr) = opt.find {
+        print!("{}", search_opcodes(substr));
+        return Ok(());
+    }
+
+    if opt.repl {
+        let stdin = io::stdin();
+        return Ok(repl(stdin.lock(), io::stdout(), opt.addr_radix)?);
+    }
+
+    let buffer = read_input(opt)?;
 
-    let bytes_read = match opt.file {
-        None => io::stdin().read_to_end(&mut buffer)?,
-        Some(ref filename) => File::open(&filename)?.read_to_end(&mut buffer)?,
+    if let Some(ref pattern) = opt.scan {
+        print!(
+            "{}",
+            scan_report(&buffer, &parse_pattern(pattern)?, opt.addr_radix)
+        );
+        return Ok(());
+    }
+
+    if let Some(addr) = opt.verify_crc {
+        println!(
+            "{}",
+            verify_checksum(&buffer, addr, opt.crc_algo, opt.addr_radix)?
+        );
+        return Ok(());
+    }
+
+    let decode_opts = DecodeOptions {
+        labels: LabelOptions {
+            prefix: match opt.label_prefix {
+                Some(ref prefix) => prefix.clone(),
+                None if opt.addr_labels => String::from("loc_"),
+                None => String::from("l"),
+            },
+            by_address: opt.addr_labels,
+            entry: if opt.no_entry_label {
+                None
+            } else {
+                Some(opt.entry_label.clone())
+            },
+            symbols: match opt.symbols {
+                Some(ref filename) => parse_symbols(&std::fs::read_to_string(filename)?)?,
+                None => Vec::new(),
+            },
+        },
+        lenient: opt.lenient,
+        data_after_jumps: opt.data_after_jump,
+        ..Default::default()
     };
+    let Disassembly {
+        instrs,
+        labels,
+        total_bytes,
+        data: undecoded,
+        mut warnings,
+    } = match opt.entrypoints {
+        Some(ref filename) => {
+            let entries = parse_entrypoints(&std::fs::read_to_string(filename)?)?;
+            disassemble_recursive_with_labels(&buffer, &entries, &decode_opts.labels)
+        }
+        None => disassemble_with(&buffer, &decode_opts)?,
+    };
+    let allowed = |warning: &Warning| opt.allow.iter().any(|kind| kind == warning.kind());
+    warnings.retain(|warning| !allowed(warning));
+    if !opt.inline_warnings {
+        print_warnings(warnings.drain(..), opt.addr_radix);
+    }
 
-    let instrs = disassemble(&buffer)?;
+    if opt.validate {
+        validate(&instrs)?;
+    }
 
-    println!(
-        "\nDisassembly of file `{}` ({} bytes)\n",
-        &opt.file.unwrap_or_else(|| "stdin".into()),
-        bytes_read
-    );
+    for mnemonic in &opt.assert_no {
+        assert_no(&instrs, mnemonic, opt.addr_radix)?;
+    }
 
-    if opt.stats {
-        println!("{}", BinaryStats::new(&instrs));
+    if opt.emit == Emit::Srec {
+        print_warnings(warnings, opt.addr_radix);
+        print!("{}", srec(&instrs));
+        return Ok(());
     }
 
-    for ins in instrs {
-        let bytes_str = ins
-            .to_bytes()
+    if opt.emit == Emit::Html {
+        print_warnings(warnings, opt.addr_radix);
+        print!("{}", html(&instrs, opt.addr_radix));
+        return Ok(());
+    }
+
+    if opt.emit == Emit::Json {
+        print_warnings(warnings, opt.addr_radix);
+        print!("{}", json(&instrs, buffer.len()));
+        return Ok(());
+    }
+
+    if let Some(ref other) = opt.diff {
+        print_warnings(warnings, opt.addr_radix);
+        let other = disassemble_with(&read_program(Some(other), opt.format)?, &decode_opts)?;
+        for change in diff(&instrs, &other.instrs) {
+            println!("{}", format_change(&change, opt.addr_radix));
+        }
+        return Ok(());
+    }
+
+    if opt.lint {
+        warnings.extend(
+            lint(&instrs)
+                .into_iter()
+                .filter(|warning| !allowed(warning)),
+        );
+        if !opt.inline_warnings {
+            print_warnings(warnings.drain(..), opt.addr_radix);
+        }
+    }
+
+    if opt.only_labels {
+        print_warnings(warnings, opt.addr_radix);
+        let mut labels: Vec<_> = labels.into_iter().collect();
+        labels.sort();
+        for (addr, name) in labels {
+            println!("0x{:02x} {}", addr, name);
+        }
+        return Ok(());
+    }
+
+    if opt.stats_format == StatsFormat::Csv {
+        print_warnings(warnings, opt.addr_radix);
+        if opt.csv_header {
+            println!("{}", BinaryStats::CSV_HEADER);
+        }
+        println!("{}", BinaryStats::new(&instrs).to_csv());
+        return Ok(());
+    }
+
+    let shown = match opt.since_label {
+        Some(ref since) => slice_by_label(&instrs, since, opt.until_label.as_deref())?,
+        None => 0..instrs.len(),
+    };
+
+    // Warnings about an instruction that's listed are printed on its line, and
+    // the rest on stderr as usual.
+    let (inline, elsewhere): (Vec<_>, Vec<_>) = warnings.into_iter().partition(|warning| {
+        instrs[shown.clone()]
             .iter()
-            .map(|b| format!("{:02x}", b))
-            .collect::<Vec<_>>()
-            .join(" ");
+            .any(|ins| !matches!(ins, Label(_, _)) && ins.addr() == warning.addr())
+    });
+    print_warnings(elsewhere, opt.addr_radix);
+
+    if !opt.quiet {
         println!(
-            "{:6} {:8} | {}",
-            format!("{:02x}:", ins.addr()),
-            bytes_str,
-            ins
+            "\nDisassembly of file `{}` ({} bytes)",
+            match (&opt.file, &opt.hex) {
+                (Some(filename), _) => filename,
+                (None, Some(_)) => "--hex",
+                (None, None) if opt.demo => "--demo",
+                (None, None) => "stdin",
+            },
+            buffer.len()
         );
+        if opt.hash {
+            println!("sha256: {}", to_hex(&sha256(&buffer)));
+        }
+        println!();
+
+        if opt.stats || opt.demo {
+            write_stats(&mut io::stdout().lock(), &instrs, opt.addr_radix)?;
+            if opt.lenient {
+                println!(
+                    "Code coverage: {} of {} bytes ({:.2}%), {} undecoded\n",
+                    total_bytes,
+                    buffer.len(),
+                    BinaryStats::new(&instrs).code_coverage_pct(buffer.len()),
+                    buffer.len() - total_bytes
+                );
+            }
+        }
+
+        if opt.histogram {
+            println!(
+                "{}",
+                OpcodeHistogram::new(&instrs).report(opt.sort, opt.top)
+            );
+        }
+    }
+
+    let annotations = match opt.annotate {
+        Some(ref filename) => parse_annotations(&std::fs::read_to_string(filename)?)?,
+        None => Vec::new(),
+    };
+    let inline = inline
+        .iter()
+        .map(|warning| {
+            let note = format!("warning: {}", warning.message(opt.addr_radix));
+            (warning.addr(), note)
+        })
+        .collect();
+    let instrs = Program::from(instrs);
+    let listing_opts = opt.listing_options();
+    let listing = Listing::new(&instrs, &listing_opts)
+        .with(Notes(inline))
+        .with(Notes(annotations));
+    let line = |i: usize| listing.line(&instrs[i]);
+    let listed = |i: &usize| !(opt.only_code && matches!(instrs[*i], Label(_, _)));
+    let padding = if opt.detect_padding {
+        find_padding(&instrs, opt.padding_threshold)
+    } else {
+        Vec::new()
+    };
+    let data = if opt.data_after_jump {
+        find_data_after_jumps(&instrs)
+    } else {
+        Vec::new()
+    };
+    // The bytes taken as data without being decoded that directly follow an
+    // instruction, if any.
+    let undecoded_after = |ins: &Instruction| match ins {
+        Label(_, _) => &[][..],
+        ins => undecoded
+            .iter()
+            .find(|range| range.start == ins.addr() + ins.size())
+            .map_or(&[][..], |range| &buffer[range.clone()]),
+    };
+    // A run of data or padding is listed as one line, in place of its first
+    // instruction. Padding within data is just more data, as are any undecoded
+    // bytes after it. Undecoded bytes after code get a line of their own.
+    let print_line = |i: usize| {
+        if let Some(run) = data.iter().find(|run| run.contains(&i)) {
+            if run.start == i {
+                let mut bytes: Vec<_> = instrs[run.clone()]
+                    .iter()
+                    .flat_map(Instruction::to_bytes)
+                    .collect();
+                bytes.extend_from_slice(undecoded_after(&instrs[run.end - 1]));
+                println!("{}", format_data(instrs[i].addr(), &bytes, &listing_opts));
+            }
+            return;
+        }
+        match padding.iter().find(|run| run.contains(&i)) {
+            Some(run) if run.start == i => println!(
+                "{}",
+                format_padding(instrs[i].addr(), run.len(), &listing_opts)
+            ),
+            Some(_) => {}
+            None => println!("{}", line(i)),
+        }
+        let undecoded = undecoded_after(&instrs[i]);
+        if !undecoded.is_empty() {
+            let addr = instrs[i].addr() + instrs[i].size();
+            println!("{}", format_data(addr, undecoded, &listing_opts));
+        }
+    };
+
+    if opt.by_function {
+        let cfg = Cfg::new(&instrs);
+        for function in cfg.functions() {
+            let first = &cfg.blocks[function.blocks[0]];
+            let last = &cfg.blocks[function.blocks[function.blocks.len() - 1]];
+            println!(
+                "; === {} ({}\u{2013}{}) ===",
+                function.name,
+                opt.addr_radix.reference(first.start),
+                opt.addr_radix.reference(last.end - 1)
+            );
+            for &block in &function.blocks {
+                for i in cfg.blocks[block].range.clone().filter(listed) {
+                    print_line(i);
+                }
+            }
+        }
+    } else {
+        let mut order: Vec<usize> = shown.filter(listed).collect();
+        if opt.sort_by == ListingOrder::Opcode {
+            // The sort is stable, so each opcode's uses stay in address order.
+            order.retain(|&i| !matches!(instrs[i], Label(_, _)));
+            order.sort_by_key(|&i| instrs[i].to_bytes()[0]);
+        }
+        for i in order {
+            print_line(i);
+        }
     }
 
     Ok(())
 }
 
-/// Represents possible errors that can occur while disassembling. `InvalidOpcode`
-/// indicates an opcode outside the valid range was encountered. `UnexpectedEndOfFile`
-/// indicates we were in the middle of parsing the operands for an instruction,
-/// but encountered the end of input before all the operands were provided.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum Error {
-    InvalidOpcode(u8, usize),
-    UnexpectedEndOfFile(Opcode),
-}
-
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::InvalidOpcode(opcode, addr) => {
-                write!(
-                    f,
-                    "invalid opcode encountered at byte {}: `{:x}`",
-                    addr, opcode
-                )
+/// Names the target of a jump decoded on its own by its address (e.g. `jmp
+/// 0x05`), since there are no labels to refer to.
+fn with_numeric_target(ins: Instruction, radix: AddrRadix) -> Instruction {
+    match ins {
+        Jump(addr, op, target, _) => Jump(addr, op, target, radix.reference(target as usize)),
+        ins => ins,
+    }
+}
+
+/// Lists every place `pattern` matches in `bytes`: the address of the match
+/// and its bytes, followed by the instructions decoded from them. Any part of
+/// a match that can't be decoded is reported in place of instructions.
+fn scan_report(bytes: &[u8], pattern: &[Option<u8>], radix: AddrRadix) -> String {
+    let mut report = String::new();
+    for start in scan(bytes, pattern) {
+        let end = start + pattern.len();
+        let hex: Vec<_> = bytes[start..end]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        report.push_str(&format!("{}: {}\n", radix.reference(start), hex.join(" ")));
+
+        let mut addr = start;
+        while addr < end {
+            match decode_one(&bytes[addr..], addr) {
+                Ok(ins) => {
+                    let ins = with_numeric_target(ins, radix);
+                    report.push_str(&format!("{}\n", ins));
+                    addr += ins.size();
+                }
+                Err(e) => {
+                    report.push_str(&format!("  error: {}\n", e));
+                    break;
+                }
             }
-            Self::UnexpectedEndOfFile(opcode) => write!(
-                f,
-                "unexpected end of file while processing instruction with opcode {:02x}",
-                *opcode as u8
-            ),
         }
     }
+    report
 }
 
-impl std::error::Error for Error {}
+/// Prints warnings on stderr.
+fn print_warnings(warnings: impl IntoIterator<Item = Warning>, radix: AddrRadix) {
+    for warning in warnings {
+        eprintln!("warning: {}", warning.message(radix));
+    }
+}
+
+/// Finds the part of the listing to show with --since-label: from the label
+/// `since` up to just before the label `until`, if given, or else up to and
+/// including the first `jmp`, `ret`, or `hlt`. The result is a range of
+/// indices into `instrs`.
+fn slice_by_label(
+    instrs: &[Instruction],
+    since: &str,
+    until: Option<&str>,
+) -> Result<Range<usize>> {
+    let find_label = |name: &str| {
+        instrs
+            .iter()
+            .position(|ins| matches!(ins, Label(_, label) if label == name))
+            .with_context(|| format!("no label named `{}`", name))
+    };
 
-/// Parses a slice of bytes into an assembly program (list of instructions).
+    let start = find_label(since)?;
+    let end = match until {
+        Some(until) => {
+            let end = find_label(until)?;
+            if end < start {
+                anyhow::bail!("label `{}` comes before `{}`", until, since);
+            }
+            end
+        }
+        None => instrs[start..]
+            .iter()
+            .position(Instruction::is_terminator)
+            .map_or(instrs.len(), |i| start + i + 1),
+    };
+    Ok(start..end)
+}
+
+/// Gets the program to disassemble: the bytes given with --hex, or else the
+/// contents of the input file or stdin, cut down to the window given by
+/// --skip and --length.
+fn read_input(opt: &Opt) -> Result<Vec<u8>> {
+    let buffer = match opt.hex {
+        Some(ref hex) => parse_hex(hex)?,
+        None if opt.demo => COUNTDOWN.to_vec(),
+        None => read_program(opt.file.as_deref(), opt.format)?,
+    };
+    window(buffer, opt.skip, opt.length)
+}
+
+/// Cuts `buffer` down to the `length` bytes (or all, if not given) after the
+/// first `skip`. A length past the end of the input just takes what's there.
+fn window(mut buffer: Vec<u8>, skip: usize, length: Option<usize>) -> Result<Vec<u8>> {
+    if skip > buffer.len() {
+        anyhow::bail!("can't skip {} bytes of a {}-byte input", skip, buffer.len());
+    }
+    buffer.drain(..skip);
+    if let Some(length) = length {
+        buffer.truncate(length);
+    }
+    Ok(buffer)
+}
+
+/// Reads a program from the given file, or stdin if none, decoding it
+/// according to the input format.
 ///
-/// # Examples
-/// ```
-/// // outi 1; hlt
-/// let bytes = [0xc1, 0x01, 0xc7];
-/// assert_eq!(
-///     disassemble(&bytes).unwrap(),
-///     vec![Instr(0x00, OUTI, One(0x01)), Instr(0x02, HLT, Zero)],
-/// );
-/// ```
-fn disassemble(bytes: &[u8]) -> Result<Vec<Instruction>, Error> {
-    let mut bytes = bytes.iter();
-    let mut instrs = Vec::new();
-
-    // Gensym is used to generate unique label names
-    let mut gensym_counter: usize = 0;
-    let mut gensym = move |base: &str| -> String {
-        gensym_counter += 1;
-        format!("{}{}", base, gensym_counter - 1)
+/// Stdin is read as raw bytes through its locked handle, with no text-mode
+/// newline translation on any platform, so binaries containing `0x0d`/`0x0a`
+/// can be piped in unchanged.
+fn read_program(file: Option<&str>, format: InputFormat) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+
+    match file {
+        None => io::stdin().lock().read_to_end(&mut buffer)?,
+        Some(filename) => File::open(filename)?.read_to_end(&mut buffer)?,
     };
 
-    // This map maintains a bidirectional correspondence between addresses and labels
-    let mut label_addr_map: BiMap<usize, String> = BiMap::new();
+    if format == InputFormat::HexText {
+        let text = String::from_utf8(buffer).context("hex text input is not valid UTF-8")?;
+        buffer = parse_hex_text(&text)?;
+    }
 
-    let mut addr = 0; // current address in binary
+    Ok(buffer)
+}
 
-    while let Some(&opcode) = bytes.next() {
-        let opcode: Opcode = match opcode.try_into() {
-            Ok(opcode) => opcode,
-            Err(_) => return Err(Error::InvalidOpcode(opcode, addr)),
+/// Reads lines of hex bytes from `input` until EOF, writing the decoded
+/// instructions to `output`. Each line is decoded on its own, starting from
+/// address 0, and jump targets are shown as raw addresses. Lines that fail to
+/// parse or decode produce an error message, and the loop carries on.
+fn repl<R: BufRead, W: Write>(input: R, mut output: W, radix: AddrRadix) -> io::Result<()> {
+    for (i, line) in input.lines().enumerate() {
+        let bytes = match parse_hex(&line?).map_err(|e| e.on_line(i + 1)) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                writeln!(output, "error: {}", e)?;
+                continue;
+            }
         };
-        let size = opcode.instruction_size();
-
-        // Expect another byte in the input stream and error with unexpected
-        // end of input if no more bytes.
-        let mut expect_operand = || bytes.next().ok_or(Error::UnexpectedEndOfFile(opcode));
-
-        let ins = match size {
-            // Opcode + no operands
-            1 => Instr(addr, opcode, Zero),
-            // Opcode + single operand
-            2 => {
-                let operand = *expect_operand()?;
-
-                match opcode {
-                    // If the instruction is a jump (needs labels)
-                    JMP | JE | JNE | JL | JLE | JG | JGE | JA | JAE | JB | JBE | CALL => {
-                        // Check map for label already generated for this address
-                        match label_addr_map.get_by_left(&(operand as usize)) {
-                            Some(label) => Jump(addr, opcode, operand, label.clone()),
-                            None => {
-                                // No label for this address, generate a new one and
-                                // insert it into the map.
-                                let new_label = gensym("l");
-                                label_addr_map.insert(operand as usize, new_label.clone());
-                                Jump(addr, opcode, operand, new_label.clone())
-                            }
-                        }
-                    }
-                    _ => Instr(addr, opcode, One(operand)),
+
+        let mut addr = 0;
+        while addr < bytes.len() {
+            match decode_one(&bytes[addr..], addr) {
+                Ok(ins) => {
+                    let ins = with_numeric_target(ins, radix);
+                    writeln!(output, "{}", ins.to_string().trim_start())?;
+                    addr += ins.size();
+                }
+                Err(e) => {
+                    writeln!(output, "error: {}", e)?;
+                    break;
                 }
             }
-            // Opcode + two operands
-            3 => {
-                let operand1 = *expect_operand()?;
-                let operand2 = *expect_operand()?;
-                Instr(addr, opcode, Two(operand1, operand2))
-            }
-            // All instructions are currently between 1-3 bytes in size.
-            _ => unreachable!(),
+        }
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Parses a symbol file: one `0xADDR NAME` pair per line, ignoring blank
+/// lines and `#` comments.
+fn parse_symbols(text: &str) -> Result<Vec<(usize, String)>> {
+    let mut symbols = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        let (addr, name) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(addr), Some(name), None) => (addr, name),
+            _ => anyhow::bail!("line {}: expected `0xADDR NAME`", i + 1),
         };
+        let addr = usize::from_str_radix(addr.trim_start_matches("0x"), 16)
+            .with_context(|| format!("line {}: invalid address `{}`", i + 1, addr))?;
+        symbols.push((addr, name.to_string()));
+    }
+    Ok(symbols)
+}
 
-        instrs.push(ins);
-        addr += size;
+/// Parses an entry point file: one `0xADDR` per line, ignoring blank lines
+/// and `#` comments.
+fn parse_entrypoints(text: &str) -> Result<Vec<usize>> {
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let addr = line.split('#').next().unwrap().trim();
+        if addr.is_empty() {
+            continue;
+        }
+
+        let addr = usize::from_str_radix(addr.trim_start_matches("0x"), 16)
+            .with_context(|| format!("line {}: invalid address `{}`", i + 1, addr))?;
+        entries.push(addr);
     }
+    Ok(entries)
+}
 
-    let mut addr: usize = 0;
-    let mut with_labels = Vec::with_capacity(instrs.len());
-    for ins in &instrs {
-        // If a label points at this address, add one
-        if let Some(label) = label_addr_map.get_by_left(&addr) {
-            with_labels.push(Label(addr as usize, label.clone()));
+/// Parses an annotation file: one `0xADDR: comment` pair per line, ignoring
+/// blank lines and lines starting with `#`.
+fn parse_annotations(text: &str) -> Result<Vec<(usize, String)>> {
+    let mut annotations = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
         }
 
-        let opcode = match ins {
-            Jump(_, opcode, _, _) => opcode,
-            Instr(_, opcode, _) => opcode,
-            _ => unreachable!(),
+        let (addr, note) = match line.find(':') {
+            Some(colon) => (line[..colon].trim(), line[colon + 1..].trim()),
+            None => anyhow::bail!("line {}: expected `0xADDR: comment`", i + 1),
         };
+        let addr = usize::from_str_radix(addr.trim_start_matches("0x"), 16)
+            .with_context(|| format!("line {}: invalid address `{}`", i + 1, addr))?;
+        annotations.push((addr, note.to_string()));
+    }
+    Ok(annotations)
+}
+
+/// Checks every operand in the program against the constraints of its opcode.
+fn validate(instrs: &[Instruction]) -> Result<()> {
+    for ins in instrs {
+        let (op, operands) = match ins {
+            Label(_, _) => continue,
+            Jump(_, op, _, _) | Instr(_, op, _) => (*op, ins.to_bytes().split_off(1)),
+        };
+
+        for value in operands {
+            op.validate_operand(value)
+                .with_context(|| format!("invalid operand at byte {}", ins.addr()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the program has no instructions with the given mnemonic, failing
+/// with the address of each one if it does.
+fn assert_no(instrs: &[Instruction], mnemonic: &str, radix: AddrRadix) -> Result<()> {
+    let ops = Opcode::with_mnemonic(mnemonic);
+    if ops.is_empty() {
+        anyhow::bail!("unknown mnemonic `{}`", mnemonic);
+    }
+
+    let found = find_opcodes(instrs, &ops);
+    if !found.is_empty() {
+        let addrs: Vec<_> = found
+            .iter()
+            .map(|&i| radix.reference(instrs[i].addr()))
+            .collect();
+        anyhow::bail!("found `{}` at {}", mnemonic, addrs.join(", "));
+    }
+
+    Ok(())
+}
+
+/// Checks the checksum stored at `addr` in `bytes` against the checksum of all
+/// the other bytes, describing the match or failing with the mismatch.
+fn verify_checksum(
+    bytes: &[u8],
+    addr: usize,
+    algo: ChecksumAlgo,
+    radix: AddrRadix,
+) -> Result<String> {
+    let stored = match bytes.get(addr) {
+        Some(&stored) => stored,
+        None => anyhow::bail!(
+            "checksum address {} is past the end of the input",
+            radix.reference(addr)
+        ),
+    };
 
-        addr += opcode.instruction_size();
-        with_labels.push(ins.clone());
+    let rest: Vec<u8> = [&bytes[..addr], &bytes[addr + 1..]].concat();
+    let computed = checksum(&rest, algo);
+    if stored != computed {
+        anyhow::bail!(
+            "checksum mismatch: 0x{:02x} is stored at {}, but the input's is 0x{:02x}",
+            stored,
+            radix.reference(addr),
+            computed
+        );
     }
+    Ok(format!(
+        "checksum 0x{:02x} at {} matches",
+        stored,
+        radix.reference(addr)
+    ))
+}
 
-    Ok(with_labels)
+/// Formats one line of a diff: a `-`/`+` marker for removed/added lines, the
+/// address in the old and new program (blank if absent from either), and the
+/// instruction.
+fn format_change(change: &Change, radix: AddrRadix) -> String {
+    let addr = |ins: &Instruction| format!("{}:", radix.column(ins.addr()));
+    let (marker, old, new, ins) = match change {
+        Change::Same(old, new) => (' ', addr(old), addr(new), old),
+        Change::Removed(old) => ('-', addr(old), String::new(), old),
+        Change::Added(new) => ('+', String::new(), addr(new), new),
+    };
+    format!("{} {:4} {:4} | {}", marker, old, new, ins)
+}
+
+/// Builds a reference table of the whole instruction set, one opcode per row:
+/// its name, encoding, size in bytes, and assembly form.
+fn opcode_table() -> String {
+    Opcode::all().map(opcode_row).collect()
+}
+
+/// Builds the rows of the opcode table whose assembly form contains `substr`,
+/// ignoring case.
+fn search_opcodes(substr: &str) -> String {
+    let substr = substr.to_lowercase();
+    Opcode::all()
+        .filter(|op| assembly_form(*op).contains(&substr))
+        .map(opcode_row)
+        .collect()
+}
+
+/// Writes an opcode the way it's used in assembly, with placeholders for its
+/// operands (e.g. `cmpi byte, a`).
+fn assembly_form(op: Opcode) -> String {
+    let operands = op
+        .semantics()
+        .operands
+        .iter()
+        .map(|operand| match operand {
+            Operand::Reg(reg) => reg.to_string(),
+            Operand::Imm => String::from("byte"),
+            Operand::Offset => String::from("offset"),
+            Operand::Target => String::from("label"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{} {}", op, operands).trim_end().to_string()
+}
+
+/// Formats one row of the opcode table.
+fn opcode_row(op: Opcode) -> String {
+    format!(
+        "{:12} 0x{:02x} {}   {}\n",
+        format!("{:?}", op),
+        op.to_byte(),
+        op.instruction_size(),
+        assembly_form(op)
+    )
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use stew3d::instr::Operands::*;
+    use stew3d::{disassemble, disassemble_with_labels};
+
+    #[test]
+    fn opcode_table_lists_every_opcode() {
+        let table = opcode_table();
+        let rows: Vec<Vec<&str>> = table
+            .lines()
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+
+        assert_eq!(rows.len(), 0xc9);
+        assert!(rows.contains(&vec!["HLT", "0xc7", "1", "hlt"]));
+        assert!(rows.contains(&vec!["NOP", "0xc8", "1", "nop"]));
+        assert!(rows.contains(&vec!["CMPI_BYTE_A", "0xac", "2", "cmpi", "byte,", "a"]));
+    }
+
+    #[test]
+    fn search_opcodes_by_form() {
+        let found = search_opcodes("neg");
+        let rows: Vec<Vec<&str>> = found
+            .lines()
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["NEG_A", "0x58", "1", "neg", "a"],
+                vec!["NEG_B", "0x59", "1", "neg", "b"],
+                vec!["NEG_C", "0x5a", "1", "neg", "c"],
+            ]
+        );
+
+        assert!(search_opcodes("SP").lines().all(|row| row.contains("sp")));
+        assert_eq!(search_opcodes("frobnicate"), "");
+    }
+
+    #[test]
+    fn scan_reports_matches() {
+        // mvi 1, a; outi 5; hlt
+        let bytes = [0x7f, 0x01, 0xc1, 0x05, 0xc7];
+        let pattern = parse_pattern("c1 ?? c7").unwrap();
+        assert_eq!(
+            scan_report(&bytes, &pattern, AddrRadix::Hex),
+            "0x02: c1 05 c7\n  outi 5\n  hlt\n"
+        );
+
+        // a match that ends partway into an instruction is decoded in full
+        let pattern = parse_pattern("?? 01").unwrap();
+        assert_eq!(
+            scan_report(&bytes, &pattern, AddrRadix::Hex),
+            "0x00: 7f 01\n  mvi 1, a\n"
+        );
+
+        // addresses and jump targets follow the address base
+        let bytes = [0xc8, 0xc8, 0xb3, 0x0c];
+        let pattern = parse_pattern("b3 ??").unwrap();
+        assert_eq!(
+            scan_report(&bytes, &pattern, AddrRadix::Dec),
+            "2: b3 0c\n  jne 12\n"
+        );
+    }
+
+    /// The countdown loop from the `stats` tests, with a halt on the end.
+    const LOOP: [u8; 8] = [0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7];
 
     #[test]
-    fn simple_disassembly() {
-        let b = [0x7f, 0x0a, 0xbc, 0x05, 0xc7, 0x0c, 0x04, 0xbd];
+    fn show_targets() {
+        let program = Program::from(
+            disassemble_with_labels(&LOOP, &LabelOptions::default())
+                .unwrap()
+                .instrs,
+        );
+        let jne = &program[5];
+
+        let opts = Opt::from_iter(&["stew3d"]).listing_options();
+        assert_eq!(
+            Listing::new(&program, &opts).line(jne),
+            "05:    b3 02    |   jne l0"
+        );
+
+        let opts = Opt::from_iter(&["stew3d", "--show-targets"]).listing_options();
+        let listing = Listing::new(&program, &opts);
+        assert_eq!(listing.line(jne), "05:    b3 02    |   jne l0   ; -> 0x02");
+        assert_eq!(listing.line(&program[6]), "07:    c7       |   hlt");
+    }
+
+    #[test]
+    fn verbosity_levels() {
+        let program = Program::from(
+            disassemble_with_labels(&LOOP, &LabelOptions::default())
+                .unwrap()
+                .instrs,
+        );
+        let jne = &program[5];
+
+        let opts = Opt::from_iter(&["stew3d", "-v"]).listing_options();
+        assert_eq!(
+            Listing::new(&program, &opts).line(jne),
+            "05:    b3 02    |   jne l0   ; -> 0x02"
+        );
+
+        let opts = Opt::from_iter(&["stew3d", "-vv"]).listing_options();
         assert_eq!(
-            disassemble(&b).unwrap(),
+            Listing::new(&program, &opts).line(jne),
+            "05:    b3 02    |   jne l0   ; -> 0x02; taken if x != y"
+        );
+    }
+
+    #[test]
+    fn inline_hex() {
+        let opt = Opt::from_iter(&["stew3d", "--hex", "c1 01 c7"]);
+        let bytes = read_input(&opt).unwrap();
+
+        assert_eq!(
+            disassemble(&bytes).unwrap(),
             vec![
-                Instr(0x00, MVI_A, One(0x0a)),
-                Jump(0x02, CALL, 0x05, String::from("l0")),
-                Instr(0x04, HLT, Zero),
-                Label(0x05, String::from("l0")),
-                Instr(0x05, ADDI_A, One(0x04)),
-                Instr(0x07, RET, Zero)
+                Instr(0x00, Opcode::OUTI, One(0x01)),
+                Instr(0x02, Opcode::HLT, Zero)
             ]
         );
     }
 
     #[test]
-    fn errs_on_invalid_opcode() {
-        // df is above OPCODE_MAX
-        let b = [0x80, 0x05, 0xc5, 0xdf, 0xc7];
-        assert_eq!(disassemble(&b), Err(Error::InvalidOpcode(0xdf, 3)));
+    fn show_encoding() {
+        let opts = Opt::from_iter(&["stew3d", "--show-encoding"]).listing_options();
+        let program = Program::from(vec![Instr(0x00, Opcode::OUTI, One(0x01))]);
+        assert_eq!(
+            Listing::new(&program, &opts).line(&program[0]),
+            "00:    c1 01    |   outi 1   ; opcode=0xc1 imm=0x01"
+        );
+
+        let opts = Opt::from_iter(&["stew3d", "-vvv"]).listing_options();
+        let program = Program::from(disassemble(&LOOP).unwrap());
+        assert_eq!(
+            Listing::new(&program, &opts).line(&program[5]),
+            "05:    b3 02    |   jne l0   ; -> 0x02; taken if x != y; opcode=0xb3 target=0x02"
+        );
     }
 
     #[test]
-    fn errs_on_unexpected_eof() {
-        // 97 (lds byte, a) expects a byte operand
-        let b = [0xc8, 0xc8, 0x6f, 0x97];
-        assert_eq!(disassemble(&b), Err(Error::UnexpectedEndOfFile(LDS_A)));
+    fn custom_indent() {
+        let program = Program::from(disassemble(&LOOP).unwrap());
+
+        let opts = Opt::from_iter(&["stew3d", "--indent", "4"]).listing_options();
+        let listing = Listing::new(&program, &opts);
+        assert_eq!(listing.line(&program[2]), "02:    be       |     out a");
+        assert_eq!(listing.line(&program[1]), "02:             | l0:");
+
+        let opts = Opt::from_iter(&["stew3d", "--indent", "\t"]).listing_options();
+        assert_eq!(
+            Listing::new(&program, &opts).line(&program[2]),
+            "02:    be       | \tout a"
+        );
+    }
+
+    #[test]
+    fn no_bytes() {
+        let program = Program::from(disassemble(&LOOP).unwrap());
+        let full = Opt::from_iter(&["stew3d"]).listing_options();
+        let full = Listing::new(&program, &full);
+        let compact = Opt::from_iter(&["stew3d", "--no-bytes"]).listing_options();
+        let compact = Listing::new(&program, &compact);
+
+        assert_eq!(full.line(&program[0]), "00:    7f ff    |   mvi 255, a");
+        assert_eq!(compact.line(&program[0]), "00:   mvi 255, a");
+        assert_eq!(full.line(&program[1]), "02:             | l0:");
+        assert_eq!(compact.line(&program[1]), "02: l0:");
+    }
+
+    #[test]
+    fn annotation_files() {
+        let text = "# notes\n0x02: main loop start\n\n0x07:halt: done\n";
+        assert_eq!(
+            parse_annotations(text).unwrap(),
+            vec![
+                (0x02, String::from("main loop start")),
+                (0x07, String::from("halt: done"))
+            ]
+        );
+        assert!(parse_annotations("0x02 no colon").is_err());
+        assert!(parse_annotations("0xzz: bad address").is_err());
+    }
+
+    #[test]
+    fn label_slices() {
+        let opts = LabelOptions {
+            symbols: vec![(0x05, String::from("fn0")), (0x08, String::from("pad"))],
+            ..Default::default()
+        };
+
+        // mvi 10, a; call fn0; hlt; fn0: addi 4, a; ret; pad: nop
+        let b = [0x7f, 0x0a, 0xbc, 0x05, 0xc7, 0x0c, 0x04, 0xbd, 0xc8];
+        let instrs = disassemble_with_labels(&b, &opts).unwrap().instrs;
+
+        let slice = slice_by_label(&instrs, "fn0", None).unwrap();
+        assert_eq!(
+            instrs[slice]
+                .iter()
+                .map(|ins| ins.canonical())
+                .collect::<Vec<_>>(),
+            vec!["fn0:", "addi 4, a", "ret"]
+        );
+        assert_eq!(slice_by_label(&instrs, "fn0", Some("pad")).unwrap(), 3..6);
+        assert!(slice_by_label(&instrs, "pad", Some("fn0")).is_err());
+        assert!(slice_by_label(&instrs, "main", None).is_err());
+    }
+
+    #[test]
+    fn symbol_files() {
+        let text = "# routines\n0x05 add_4\n\n0x00 start  # reset\n";
+        assert_eq!(
+            parse_symbols(text).unwrap(),
+            vec![(0x05, String::from("add_4")), (0x00, String::from("start"))]
+        );
+        assert!(parse_symbols("0x05").is_err());
+        assert!(parse_symbols("zz name").is_err());
+    }
+
+    #[test]
+    fn entrypoint_files() {
+        let text = "0x00  # reset
+
+0x1f
+";
+        assert_eq!(parse_entrypoints(text).unwrap(), vec![0x00, 0x1f]);
+        assert!(parse_entrypoints("0x05 main").is_err());
+    }
+
+    #[test]
+    fn input_window() {
+        let bytes = vec![0xff, 0xc1, 0x01, 0xc7, 0xff];
+        assert_eq!(
+            window(bytes.clone(), 1, Some(3)).unwrap(),
+            [0xc1, 0x01, 0xc7]
+        );
+        assert_eq!(window(bytes.clone(), 0, None).unwrap(), bytes);
+        assert_eq!(window(bytes.clone(), 3, Some(10)).unwrap(), [0xc7, 0xff]);
+        assert_eq!(window(bytes.clone(), 5, None).unwrap(), []);
+        assert!(window(bytes, 6, None).is_err());
+    }
+
+    #[test]
+    fn checksum_bytes() {
+        // outi 1; hlt; then the sum of those bytes
+        let bytes = [0xc1, 0x01, 0xc7, 0x89];
+        assert_eq!(
+            verify_checksum(&bytes, 0x03, ChecksumAlgo::Sum, AddrRadix::Hex).unwrap(),
+            "checksum 0x89 at 0x03 matches"
+        );
+        assert_eq!(
+            verify_checksum(&bytes, 0x03, ChecksumAlgo::Crc8, AddrRadix::Hex)
+                .unwrap_err()
+                .to_string(),
+            "checksum mismatch: 0x89 is stored at 0x03, but the input's is 0xa8"
+        );
+        assert!(verify_checksum(&bytes, 0x04, ChecksumAlgo::Sum, AddrRadix::Hex).is_err());
+
+        // a checksum at the start covers what follows it
+        assert!(verify_checksum(
+            &[0x89, 0xc1, 0x01, 0xc7],
+            0x00,
+            ChecksumAlgo::Sum,
+            AddrRadix::Hex
+        )
+        .is_ok());
+
+        // the address is written in the address base, the checksum in hex
+        let bytes = [0x00; 0x13];
+        assert_eq!(
+            verify_checksum(&bytes, 0x12, ChecksumAlgo::Sum, AddrRadix::Dec).unwrap(),
+            "checksum 0x00 at 18 matches"
+        );
+    }
+
+    #[test]
+    fn repl_decodes_lines() {
+        let input = "c1 01 c7\nb3 02\nzz\n\ndf\n7f 0a\n";
+        let mut output = Vec::new();
+        repl(input.as_bytes(), &mut output, AddrRadix::Hex).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "outi 1\nhlt\njne 0x02\nerror: invalid hex byte `zz` on line 3\n\
+             error: invalid opcode encountered at byte 0: `df` (opcodes range from 00 to c8)\n\
+             mvi 10, a\n"
+        );
+    }
+
+    #[test]
+    fn waits_for_change() {
+        let path = std::env::temp_dir().join(format!("stew3d-watch-{}.b", std::process::id()));
+        std::fs::write(&path, [0xc7]).unwrap();
+        let opt = Opt::from_iter(&["stew3d", "--watch", path.to_str().unwrap()]);
+
+        // Disassemble twice, changing the file during the first time around.
+        let mut listings = Vec::new();
+        let mut writer = None;
+        let poll = Duration::from_millis(10);
+        watch_with(&path, poll, Duration::from_millis(20), || {
+            listings.push(disassemble(&read_input(&opt).unwrap()).unwrap());
+            if writer.is_none() {
+                let path = path.clone();
+                writer = Some(thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(50));
+                    std::fs::write(&path, [0xc1, 0x01, 0xc7]).unwrap();
+                }));
+            }
+            listings.len() < 2
+        });
+        writer.unwrap().join().unwrap();
+
+        assert_eq!(
+            listings,
+            vec![
+                vec![Instr(0x00, Opcode::HLT, Zero)],
+                vec![
+                    Instr(0x00, Opcode::OUTI, One(0x01)),
+                    Instr(0x02, Opcode::HLT, Zero)
+                ],
+            ]
+        );
+        std::fs::remove_file(&path).unwrap();
     }
 }
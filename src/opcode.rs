@@ -1,15 +1,16 @@
 use std::convert::TryFrom;
 use std::fmt;
+use std::ops::RangeInclusive;
 
 /// Limits on the range of valid opcodes.
-const OPCODE_MIN: u8 = 0x00;
-const OPCODE_MAX: u8 = 0xc8;
+pub(crate) const OPCODE_MIN: u8 = 0x00;
+pub(crate) const OPCODE_MAX: u8 = 0xc8;
 
 /// This type represents the opcode of a valid instruction in the 3000's
 /// instruction set. Each opcode uniquely identifies a single instruction.
 ///
 /// Opcodes are single bytes (`u8`s), which is why this enum is `repr(u8)`.
-#[allow(non_camel_case_types)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Opcode {
@@ -263,6 +264,49 @@ impl fmt::Display for ConversionFailure {
 
 impl std::error::Error for ConversionFailure {}
 
+/// Represents an operand value that an opcode cannot accept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperandError {
+    /// The opcode takes no operands at all.
+    Unexpected(Opcode),
+    /// The operand lies outside the range of values the opcode accepts.
+    OutOfRange(Opcode, u8, RangeInclusive<u8>),
+}
+
+impl fmt::Display for OperandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unexpected(opcode) => {
+                write!(
+                    f,
+                    "opcode {:02x} does not take an operand",
+                    opcode.to_byte()
+                )
+            }
+            Self::OutOfRange(opcode, value, range) => write!(
+                f,
+                "operand {} is out of range for opcode {:02x} (expected {}-{})",
+                value,
+                opcode.to_byte(),
+                range.start(),
+                range.end()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OperandError {}
+
+impl fmt::Display for Opcode {
+    /// Writes the opcode's mnemonic, without any operands (e.g. `mov` for
+    /// `MOV_A_B`), as it's written in assembly.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = format!("{:?}", self);
+        let base = name.split('_').next().unwrap();
+        write!(f, "{}", base.to_lowercase())
+    }
+}
+
 impl TryFrom<u8> for Opcode {
     type Error = ConversionFailure;
 
@@ -270,23 +314,44 @@ impl TryFrom<u8> for Opcode {
         match byte {
             OPCODE_MIN..=OPCODE_MAX => {
                 // SAFETY: The byte is within the valid range of opcodes.
-                Ok(unsafe { std::mem::transmute(byte) })
+                Ok(unsafe { std::mem::transmute::<u8, Opcode>(byte) })
             }
             _ => Err(ConversionFailure(byte)),
         }
     }
 }
 
+impl From<Opcode> for u8 {
+    fn from(op: Opcode) -> u8 {
+        op.to_byte()
+    }
+}
+
 impl Opcode {
+    /// The size of the largest instruction (`stsi`), in bytes.
+    pub const MAX_SIZE: usize = 3;
+
+    /// Gives the byte that encodes this opcode, the inverse of `try_from`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use stew3d::opcode::Opcode::*;
+    /// assert_eq!(OUTI.to_byte(), 0xc1);
+    /// ```
+    pub fn to_byte(self) -> u8 {
+        self as u8
+    }
+
     /// Determines the size of an instruction, given its opcode.
     ///
     /// # Examples
     /// ```
+    /// # use stew3d::opcode::Opcode::*;
     /// let op = HLT;
     /// assert_eq!(op.instruction_size(), 1);
     /// ```
     pub fn instruction_size(self) -> usize {
-        match self as u8 {
+        match self.to_byte() {
             // add, addc, sub, subb, and, or, xor, not, neg, inr, inr2, inr3,
             // dcr, dcr2, dcr3, mov, ld, st, cmp, ret, out, dd, hlt, nop
             0x00..=0x0b
@@ -324,6 +389,112 @@ impl Opcode {
             opcode => panic!("called instruction_size on invalid opcode: {}", opcode),
         }
     }
+
+    /// Determines the number of operand bytes that follow this opcode.
+    ///
+    /// # Examples
+    /// ```
+    /// # use stew3d::opcode::Opcode::*;
+    /// assert_eq!(STSI.operand_count(), 2);
+    /// ```
+    pub fn operand_count(self) -> usize {
+        self.instruction_size() - 1
+    }
+
+    /// Every jump opcode (including `call`): those whose operand is the
+    /// address of a target. This is the one place the set is defined.
+    pub const JUMPS: [Opcode; 12] = {
+        use Opcode::*;
+        [JMP, JE, JNE, JG, JGE, JL, JLE, JA, JAE, JB, JBE, CALL]
+    };
+
+    /// Determines whether this is a jump (including `call`), whose operand is
+    /// the address of its target.
+    pub fn is_jump(self) -> bool {
+        Self::JUMPS.contains(&self)
+    }
+
+    /// Every opcode that performs I/O, i.e. has an effect outside the
+    /// registers, memory, and stack: writing to the output register (`out`,
+    /// `outi`) and driving the display (`dic`, `did`, `dd`). This is the one
+    /// place the set is defined.
+    pub const IO: [Opcode; 9] = {
+        use Opcode::*;
+        [OUT_A, OUT_B, OUT_C, OUTI, DIC, DID, DD_A, DD_B, DD_C]
+    };
+
+    /// Determines whether this opcode performs I/O (see [`IO`](Opcode::IO)).
+    pub fn is_io(self) -> bool {
+        Self::IO.contains(&self)
+    }
+
+    /// Determines whether this opcode takes an immediate byte operand, as
+    /// opposed to a jump target or no operand at all.
+    pub fn has_immediate(self) -> bool {
+        !self.is_jump() && self.operand_count() > 0
+    }
+
+    /// Lists every opcode in the instruction set, in order of encoding.
+    pub fn all() -> impl Iterator<Item = Opcode> {
+        (OPCODE_MIN..=OPCODE_MAX).filter_map(|byte| Opcode::try_from(byte).ok())
+    }
+
+    /// Finds the opcodes written with the given mnemonic, ignoring case (e.g.
+    /// `out` gives `OUT_A`, `OUT_B`, and `OUT_C`), in order of encoding.
+    ///
+    /// # Examples
+    /// ```
+    /// # use stew3d::opcode::Opcode::{self, *};
+    /// assert_eq!(Opcode::with_mnemonic("NEG"), vec![NEG_A, NEG_B, NEG_C]);
+    /// assert!(Opcode::with_mnemonic("frob").is_empty());
+    /// ```
+    pub fn with_mnemonic(mnemonic: &str) -> Vec<Opcode> {
+        let mnemonic = mnemonic.to_lowercase();
+        Self::all()
+            .filter(|op| op.to_string() == mnemonic)
+            .collect()
+    }
+}
+
+impl Opcode {
+    /// Determines the range of values this opcode accepts for its operands, or
+    /// `None` if it takes no operands. Every operand of the current instruction
+    /// set may be any byte, but constraints on particular opcodes belong here so
+    /// that validation stays in one place.
+    fn operand_range(self) -> Option<RangeInclusive<u8>> {
+        match self.instruction_size() {
+            1 => None,
+            _ => Some(0x00..=0xff),
+        }
+    }
+
+    /// Checks that `value` is an acceptable operand for this opcode.
+    ///
+    /// # Examples
+    /// ```
+    /// # use stew3d::opcode::Opcode::*;
+    /// assert!(ADDI_A.validate_operand(255).is_ok());
+    /// assert!(HLT.validate_operand(0).is_err());
+    /// ```
+    pub fn validate_operand(self, value: u8) -> Result<(), OperandError> {
+        self.check_operand(value, self.operand_range())
+    }
+
+    /// Checks that `value` lies in `range`, the operand range of this opcode as
+    /// given by [`operand_range`](Self::operand_range).
+    fn check_operand(
+        self,
+        value: u8,
+        range: Option<RangeInclusive<u8>>,
+    ) -> Result<(), OperandError> {
+        match range {
+            None => Err(OperandError::Unexpected(self)),
+            Some(range) if !range.contains(&value) => {
+                Err(OperandError::OutOfRange(self, value, range))
+            }
+            Some(_) => Ok(()),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -331,12 +502,73 @@ mod test {
     use super::*;
     use Opcode::*;
 
+    #[test]
+    fn operand_shapes() {
+        assert_eq!(JNE.operand_count(), 1);
+        assert!(JNE.is_jump());
+        assert!(!JNE.has_immediate());
+
+        assert_eq!(ADDI_B.operand_count(), 1);
+        assert!(!ADDI_B.is_jump());
+        assert!(ADDI_B.has_immediate());
+
+        assert_eq!(OUT_A.operand_count(), 0);
+        assert!(!OUT_A.is_jump());
+        assert!(!OUT_A.has_immediate());
+    }
+
+    #[test]
+    fn jump_set() {
+        let jumps = (0..=u8::MAX)
+            .filter_map(|byte| Opcode::try_from(byte).ok())
+            .filter(|op| op.is_jump());
+        assert!(jumps.eq(Opcode::JUMPS.iter().copied()));
+
+        for op in &[JMP, JE, JNE, JG, JGE, JL, JLE, JA, JAE, JB, JBE, CALL] {
+            assert!(op.is_jump(), "{:?}", op);
+        }
+        assert!(!HLT.is_jump());
+        assert!(!RET.is_jump());
+        assert!(!CMPI_A_BYTE.is_jump());
+    }
+
+    #[test]
+    fn io_set() {
+        assert!(OUTI.is_io());
+        assert!(OUT_A.is_io());
+        assert!(!ADD_A_A.is_io());
+
+        // the set is exactly the output and display instructions
+        let io: Vec<_> = Opcode::all().filter(|op| op.is_io()).collect();
+        assert_eq!(io, Opcode::IO);
+        for op in io {
+            assert!(["out", "outi", "dic", "did", "dd"].contains(&&*op.to_string()));
+        }
+    }
+
+    #[test]
+    fn mnemonics() {
+        assert_eq!(format!("{}", MOV_A_B), "mov");
+        assert_eq!(format!("{}", CMPI_BYTE_A), "cmpi");
+        assert_eq!(format!("{}", INR2_SP), "inr2");
+        assert_eq!(format!("{}", JNE), "jne");
+
+        // every opcode is printed the way it starts in assembly
+        for op in (0..=u8::MAX).filter_map(|byte| Opcode::try_from(byte).ok()) {
+            let asm = crate::instr::Instruction::example(op).canonical();
+            assert_eq!(asm.split_whitespace().next(), Some(&*op.to_string()));
+        }
+    }
+
     #[test]
     fn instruction_sizes() {
         assert_eq!(CALL.instruction_size(), 2);
         assert_eq!(MOV_A_C.instruction_size(), 1);
         assert_eq!(STSI.instruction_size(), 3);
         assert_eq!(OUTI.instruction_size(), 2);
+
+        let largest = Opcode::all().map(Opcode::instruction_size).max();
+        assert_eq!(largest, Some(Opcode::MAX_SIZE));
     }
 
     #[test]
@@ -345,5 +577,34 @@ mod test {
         assert_eq!(Opcode::try_from(0xc8), Ok(NOP));
         assert_eq!(Opcode::try_from(0x55), Ok(NOT_A));
         assert!(Opcode::try_from(0xc9).is_err());
+
+        assert_eq!(HLT.to_byte(), 0xc7);
+        assert_eq!(u8::from(ADD_A_A), 0x00);
+        for op in Opcode::all() {
+            assert_eq!(Opcode::try_from(op.to_byte()), Ok(op));
+        }
+    }
+
+    #[test]
+    fn operand_validation() {
+        for value in 0x00..=0xff {
+            assert_eq!(MVI_A.validate_operand(value), Ok(()));
+            assert_eq!(JMP.validate_operand(value), Ok(()));
+        }
+        assert_eq!(
+            MOV_A_B.validate_operand(1),
+            Err(OperandError::Unexpected(MOV_A_B))
+        );
+        assert_eq!(NOP.validate_operand(0), Err(OperandError::Unexpected(NOP)));
+
+        // a hypothetical limit on stack offsets
+        let range = Some(0x00..=0x0f);
+        assert_eq!(LDS_A.check_operand(0x0f, range.clone()), Ok(()));
+        let e = LDS_A.check_operand(0x10, range).unwrap_err();
+        assert_eq!(e, OperandError::OutOfRange(LDS_A, 0x10, 0x00..=0x0f));
+        assert_eq!(
+            e.to_string(),
+            "operand 16 is out of range for opcode 97 (expected 0-15)"
+        );
     }
 }
@@ -0,0 +1,385 @@
+use crate::builder::{bind_jump_targets, BuildError};
+use crate::instr::Instruction::{self, *};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::Deref;
+
+/// A small sample program, for trying out the disassembler without a binary
+/// of one's own: it counts down from 255, printing each value, then halts.
+///
+/// ```text
+///     mvi 255, a
+/// l0:
+///     out a
+///     dcr a
+///     cmp a, z
+///     jne l0
+///     hlt
+/// ```
+pub const COUNTDOWN: [u8; 8] = [0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7];
+
+/// Determines the size of a program, in bytes: the sum of the sizes of its
+/// instructions (labels take up no space).
+///
+/// # Examples
+/// ```
+/// # use stew3d::program::{byte_len, COUNTDOWN};
+/// # use stew3d::disassemble;
+/// assert_eq!(byte_len(&disassemble(&COUNTDOWN).unwrap()), COUNTDOWN.len());
+/// ```
+pub fn byte_len(instrs: &[Instruction]) -> usize {
+    instrs.iter().map(Instruction::size).sum()
+}
+
+/// A disassembled program: its instructions and labels, in address order.
+///
+/// This is a thin wrapper around the list of instructions that the rest of the
+/// crate works with, and it dereferences to a slice of them, so a `&Program`
+/// can be passed wherever a `&[Instruction]` is expected. The exceptions are
+/// [`len`](Program::len) and [`is_empty`](Program::is_empty), which count
+/// only instructions, not labels.
+///
+/// # Examples
+/// ```
+/// # use stew3d::disassemble;
+/// # use stew3d::program::Program;
+/// // mvi 1, c; jmp l0; l0: hlt
+/// let program = Program::from(disassemble(&[0x81, 0x01, 0xb1, 0x04, 0xc7]).unwrap());
+/// assert_eq!(program.len_bytes(), 5);
+/// assert_eq!(program.labels().collect::<Vec<_>>(), vec![(0x04, "l0")]);
+/// assert_eq!(program.at_address(0x03).unwrap().canonical(), "jmp l0");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program(Vec<Instruction>);
+
+impl Program {
+    /// Wraps a list of instructions, which should be in address order.
+    pub fn new(instrs: Vec<Instruction>) -> Self {
+        Program(instrs)
+    }
+
+    /// Lists the instructions of the program, not including labels.
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.0.iter().filter(|ins| !matches!(ins, Label(_, _)))
+    }
+
+    /// Lists the labels of the program, as their address and name.
+    pub fn labels(&self) -> impl Iterator<Item = (usize, &str)> {
+        self.0.iter().filter_map(|ins| match ins {
+            Label(addr, name) => Some((*addr, name.as_str())),
+            _ => None,
+        })
+    }
+
+    /// Finds the instruction whose bytes include the given address, whether
+    /// it starts there or the address is one of its operands.
+    pub fn at_address(&self, addr: usize) -> Option<&Instruction> {
+        self.instructions()
+            .find(|ins| (ins.addr()..ins.addr() + ins.size()).contains(&addr))
+    }
+
+    /// Finds the addresses of the instructions that control can reach from
+    /// `entry`, by falling through, jumping, or calling. A call reaches both
+    /// its target and the instruction after it, which the callee returns to.
+    /// Jumps to addresses that don't start an instruction go nowhere.
+    ///
+    /// # Examples
+    /// ```
+    /// # use stew3d::disassemble;
+    /// # use stew3d::program::Program;
+    /// // jmp l0; outi 1; l0: hlt
+    /// let program = Program::from(disassemble(&[0xb1, 0x04, 0xc1, 0x01, 0xc7]).unwrap());
+    /// assert_eq!(program.reachable_addresses(0x00).into_iter().collect::<Vec<_>>(), vec![0x00, 0x04]);
+    /// ```
+    pub fn reachable_addresses(&self, entry: usize) -> BTreeSet<usize> {
+        let by_addr: BTreeMap<usize, &Instruction> =
+            self.instructions().map(|ins| (ins.addr(), ins)).collect();
+
+        let mut reached = BTreeSet::new();
+        let mut worklist = vec![entry];
+        while let Some(addr) = worklist.pop() {
+            let ins = match by_addr.get(&addr) {
+                Some(ins) if reached.insert(addr) => ins,
+                _ => continue,
+            };
+            if let Jump(_, _, target, _) = ins {
+                worklist.push(*target as usize);
+            }
+            if !ins.is_terminator() {
+                worklist.push(addr + ins.size());
+            }
+        }
+        reached
+    }
+
+    /// Determines the size of the program, in bytes.
+    pub fn len_bytes(&self) -> usize {
+        byte_len(&self.0)
+    }
+
+    /// Determines the number of instructions in the program, not counting
+    /// labels. (The length of the slice it dereferences to counts both.)
+    pub fn len(&self) -> usize {
+        self.instructions().count()
+    }
+
+    /// Determines whether the program has no instructions, though it may
+    /// still have labels.
+    pub fn is_empty(&self) -> bool {
+        self.instructions().next().is_none()
+    }
+
+    /// Unwraps the list of instructions.
+    pub fn into_vec(self) -> Vec<Instruction> {
+        self.0
+    }
+
+    /// Encodes the program as the bytes of a binary.
+    pub fn to_binary(&self) -> Vec<u8> {
+        self.0.iter().flat_map(Instruction::to_bytes).collect()
+    }
+
+    /// Assembles a program from source: one instruction or label per line, in
+    /// the form an [`Instruction`] is parsed from, with blank and comment-only
+    /// lines allowed. A label may also share a line with the instruction it's
+    /// placed at (`loop: dcr a`). Jumps refer to labels by name, or to
+    /// addresses by number.
+    ///
+    /// Together with the disassembler, this allows a binary to be edited as
+    /// text and reassembled.
+    ///
+    /// # Examples
+    /// ```
+    /// # use stew3d::program::Program;
+    /// let program = Program::from_source("outi 1 ; hi\n\nhlt").unwrap();
+    /// assert_eq!(program.to_binary(), vec![0xc1, 0x01, 0xc7]);
+    ///
+    /// let err = Program::from_source("hlt\njmp nowhere").unwrap_err();
+    /// assert_eq!(err.to_string(), "line 2: label `nowhere` is never defined");
+    /// ```
+    pub fn from_source(src: &str) -> Result<Program, AsmError> {
+        let parse = |line: usize, text: &str| {
+            text.parse::<Instruction>().map_err(|e| AsmError {
+                line,
+                error: BuildError::InvalidInstruction(e.0),
+            })
+        };
+
+        // Each entry is kept with its line number, for reporting errors.
+        let mut entries = Vec::new();
+        for (i, text) in src.lines().enumerate() {
+            let line = i + 1;
+            let code = text
+                .find(&[';', '#'][..])
+                .map_or(text, |i| &text[..i])
+                .trim();
+            if code.is_empty() {
+                continue;
+            }
+            match code.find(':') {
+                Some(i) if !code[i + 1..].trim().is_empty() => {
+                    entries.push((line, parse(line, &code[..=i])?));
+                    entries.push((line, parse(line, &code[i + 1..])?));
+                }
+                _ => entries.push((line, parse(line, code)?)),
+            }
+        }
+
+        let mut addr = 0;
+        for (_, ins) in entries.iter_mut() {
+            *ins = ins.with_addr(addr);
+            addr += ins.size();
+        }
+
+        // A jump to a number already targets it, keeping the number as its
+        // label; a jump to a label is resolved to the label's address.
+        match bind_jump_targets(entries, true) {
+            Ok(instrs) => Ok(Program(instrs)),
+            Err((line, error)) => Err(AsmError { line, error }),
+        }
+    }
+}
+
+/// The error produced when assembling a program from source fails: what went
+/// wrong, and on which line (counting from 1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub error: BuildError,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+impl TryFrom<&str> for Program {
+    type Error = AsmError;
+
+    /// Assembles a program from source, as [`from_source`](Program::from_source) does.
+    fn try_from(src: &str) -> Result<Self, Self::Error> {
+        Program::from_source(src)
+    }
+}
+
+impl From<Vec<Instruction>> for Program {
+    fn from(instrs: Vec<Instruction>) -> Self {
+        Program(instrs)
+    }
+}
+
+impl FromIterator<Instruction> for Program {
+    fn from_iter<I: IntoIterator<Item = Instruction>>(iter: I) -> Self {
+        Program(iter.into_iter().collect())
+    }
+}
+
+impl Deref for Program {
+    type Target = [Instruction];
+
+    fn deref(&self) -> &[Instruction] {
+        &self.0
+    }
+}
+
+impl IntoIterator for Program {
+    type Item = Instruction;
+    type IntoIter = std::vec::IntoIter<Instruction>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Program {
+    type Item = &'a Instruction;
+    type IntoIter = std::slice::Iter<'a, Instruction>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::disassemble;
+    use crate::stats::BinaryStats;
+
+    /// The countdown loop from the `stats` tests, with a halt on the end.
+    const LOOP: [u8; 8] = [0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7];
+
+    #[test]
+    fn at_address() {
+        let program = Program::from(disassemble(&LOOP).unwrap());
+
+        // the start of an instruction, which shares its address with a label
+        assert_eq!(program.at_address(0x02).unwrap().canonical(), "out a");
+        // the operand of `jne l0`
+        assert_eq!(program.at_address(0x06).unwrap().canonical(), "jne l0");
+        assert_eq!(program.at_address(0x01).unwrap().canonical(), "mvi 255, a");
+        // past the end
+        assert_eq!(program.at_address(0x08), None);
+    }
+
+    #[test]
+    fn assembles_source() {
+        let src = "mvi 1, c ; count\n\
+                   jmp done\n\
+                   done: hlt\n";
+        let program = Program::from_source(src).unwrap();
+        assert_eq!(program.to_binary(), vec![0x81, 0x01, 0xb1, 0x04, 0xc7]);
+        assert_eq!(program.labels().collect::<Vec<_>>(), vec![(0x04, "done")]);
+
+        // round trip through the disassembler
+        let text: Vec<_> = program.iter().map(Instruction::canonical).collect();
+        assert_eq!(Program::try_from(&*text.join("\n")), Ok(program));
+        assert_eq!(
+            Program::from_source("jmp 0x03\nnop\nhlt")
+                .unwrap()
+                .to_binary(),
+            vec![0xb1, 0x03, 0xc8, 0xc7]
+        );
+    }
+
+    #[test]
+    fn assembly_errors() {
+        let err = |src| Program::from_source(src).unwrap_err();
+
+        assert_eq!(
+            err("hlt\n\n  frob a"),
+            AsmError {
+                line: 3,
+                error: BuildError::InvalidInstruction(String::from("frob a"))
+            }
+        );
+        assert_eq!(err("l0:\nl0: hlt").line, 2);
+        assert_eq!(
+            err("l0:\nl0: hlt").error,
+            BuildError::DuplicateLabel(String::from("l0"))
+        );
+        assert_eq!(
+            err("nop\njne l9").to_string(),
+            "line 2: label `l9` is never defined"
+        );
+    }
+
+    #[test]
+    fn reachable_addresses() {
+        let program = Program::from(disassemble(&LOOP).unwrap());
+        let every: BTreeSet<_> = program.instructions().map(Instruction::addr).collect();
+        assert_eq!(program.reachable_addresses(0x00), every);
+        // the loop never goes back to the `mvi`
+        assert_eq!(
+            program
+                .reachable_addresses(0x02)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![0x02, 0x03, 0x04, 0x05, 0x07]
+        );
+        assert!(program.reachable_addresses(0x01).is_empty());
+
+        // call f; hlt; f: ret
+        let program = Program::from_source("call f\nhlt\nf: ret").unwrap();
+        assert_eq!(
+            program
+                .reachable_addresses(0x00)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![0x00, 0x02, 0x03]
+        );
+    }
+
+    #[test]
+    fn lengths() {
+        // the loop without its halt
+        let instrs = disassemble(&LOOP[..7]).unwrap();
+        assert_eq!(byte_len(&instrs), 7);
+
+        let program = Program::from(instrs);
+        assert_eq!(program.len(), 5);
+        assert!(!program.is_empty());
+        assert!(Program::from(vec![Label(0x00, "l0".into())]).is_empty());
+        assert!(Program::default().is_empty());
+    }
+
+    #[test]
+    fn views() {
+        let program = Program::from(disassemble(&LOOP).unwrap());
+        assert_eq!(program.len(), 6);
+        assert_eq!(program[..].len(), 7);
+        assert_eq!(program.instructions().count(), 6);
+        assert_eq!(program.len_bytes(), LOOP.len());
+        assert_eq!(program.labels().collect::<Vec<_>>(), vec![(0x02, "l0")]);
+
+        // usable anywhere a slice of instructions is
+        assert_eq!(BinaryStats::new(&program), BinaryStats::new(&program[..]));
+        assert_eq!((&program).into_iter().count(), 7);
+        assert_eq!(program.clone().into_vec(), program.to_vec());
+    }
+}
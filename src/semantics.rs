@@ -0,0 +1,508 @@
+use crate::opcode::Opcode::{self, *};
+use std::fmt;
+
+/// The registers of the 3000. `z` is hardwired to zero, so it can be read but
+/// never meaningfully written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Reg {
+    A,
+    B,
+    C,
+    Sp,
+    Z,
+}
+
+impl Reg {
+    /// Every register, in a fixed order.
+    pub const ALL: [Reg; 5] = [Reg::A, Reg::B, Reg::C, Reg::Sp, Reg::Z];
+}
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Reg::A => "a",
+            Reg::B => "b",
+            Reg::C => "c",
+            Reg::Sp => "sp",
+            Reg::Z => "z",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A set of registers, stored as a bitmask.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct RegSet(u8);
+
+impl RegSet {
+    /// Constructs a set containing exactly the given registers.
+    pub fn of(regs: &[Reg]) -> Self {
+        regs.iter()
+            .fold(RegSet::default(), |set, &reg| set.with(reg))
+    }
+
+    /// Returns this set with `reg` added.
+    pub fn with(self, reg: Reg) -> Self {
+        RegSet(self.0 | 1 << reg as u8)
+    }
+
+    /// Returns the registers in either this set or `other`.
+    pub fn union(self, other: RegSet) -> Self {
+        RegSet(self.0 | other.0)
+    }
+
+    /// Returns the registers in this set but not in `other`.
+    pub fn difference(self, other: RegSet) -> Self {
+        RegSet(self.0 & !other.0)
+    }
+
+    /// Determines whether `reg` is in this set.
+    pub fn contains(self, reg: Reg) -> bool {
+        self.0 & 1 << reg as u8 != 0
+    }
+
+    /// Determines the number of registers in this set.
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Determines whether this set has no registers in it.
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Iterates over the registers in this set, in the order of `Reg::ALL`.
+    pub fn iter(self) -> impl Iterator<Item = Reg> {
+        Reg::ALL
+            .iter()
+            .copied()
+            .filter(move |&reg| self.contains(reg))
+    }
+}
+
+/// A single operand slot of an instruction, as it is written in assembly.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operand {
+    /// A fixed register, encoded in the opcode itself.
+    Reg(Reg),
+    /// An immediate byte following the opcode.
+    Imm,
+    /// A stack slot, as an immediate byte following the opcode that is added
+    /// to `sp` to give the address accessed.
+    Offset,
+    /// The address of a jump target, following the opcode.
+    Target,
+}
+
+/// Describes the data flow of an instruction: the operands it is written
+/// with, and which registers it reads and writes.
+///
+/// The 3000's assembly consistently writes the source before the destination,
+/// so `mov a, b` copies `a` into `b`, `add a, b` computes `b + a` into `b`, and
+/// `sts a, 4` stores `a` to the stack slot at `sp + 4`. Likewise, `stsi 5, 255`
+/// stores the value 5 to the slot at `sp + 255`. Flags and memory are
+/// not tracked here; stack-relative accesses read `sp`, and `call`/`ret` both
+/// read and write it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Semantics {
+    /// The operands in the order they appear in assembly.
+    pub operands: &'static [Operand],
+    /// Registers whose values the instruction depends on.
+    pub reads: RegSet,
+    /// Registers the instruction overwrites.
+    pub writes: RegSet,
+}
+
+impl Semantics {
+    /// The register operands of `operands`, in order.
+    fn regs(operands: &[Operand]) -> Vec<Reg> {
+        operands
+            .iter()
+            .filter_map(|op| match op {
+                Operand::Reg(reg) => Some(*reg),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Marks the instruction as additionally reading `reg`.
+    fn reading(self, reg: Reg) -> Self {
+        Semantics {
+            reads: self.reads.with(reg),
+            ..self
+        }
+    }
+
+    /// Marks the instruction as additionally writing `reg`.
+    fn writing(self, reg: Reg) -> Self {
+        Semantics {
+            writes: self.writes.with(reg),
+            ..self
+        }
+    }
+}
+
+/// An arithmetic/logic instruction that updates its last register operand
+/// in place, reading every register operand.
+fn alu(operands: &'static [Operand]) -> Semantics {
+    let regs = Semantics::regs(operands);
+    Semantics {
+        operands,
+        reads: RegSet::of(&regs),
+        writes: RegSet::of(&regs[regs.len() - 1..]),
+    }
+}
+
+/// An instruction that overwrites its last register operand, reading only
+/// the register operands before it.
+fn mov(operands: &'static [Operand]) -> Semantics {
+    let regs = Semantics::regs(operands);
+    Semantics {
+        operands,
+        reads: RegSet::of(&regs[..regs.len() - 1]),
+        writes: RegSet::of(&regs[regs.len() - 1..]),
+    }
+}
+
+/// An instruction that reads all of its register operands and writes none.
+fn uses(operands: &'static [Operand]) -> Semantics {
+    Semantics {
+        operands,
+        reads: RegSet::of(&Semantics::regs(operands)),
+        writes: RegSet::default(),
+    }
+}
+
+impl Opcode {
+    /// Describes the operands of this opcode and the registers it reads and
+    /// writes. See [`Semantics`] for the operand ordering conventions.
+    ///
+    /// # Examples
+    /// ```
+    /// # use stew3d::opcode::Opcode::*;
+    /// # use stew3d::semantics::{Reg, RegSet};
+    /// let sem = MOV_A_B.semantics();
+    /// assert_eq!(sem.reads, RegSet::of(&[Reg::A]));
+    /// assert_eq!(sem.writes, RegSet::of(&[Reg::B]));
+    /// ```
+    pub fn semantics(self) -> Semantics {
+        use Operand::{Imm, Offset, Reg as R, Target};
+        use Reg::*;
+
+        match self {
+            ADD_A_A => alu(&[R(A), R(A)]),
+            ADD_A_B => alu(&[R(A), R(B)]),
+            ADD_A_C => alu(&[R(A), R(C)]),
+            ADD_A_SP => alu(&[R(A), R(Sp)]),
+            ADD_B_A => alu(&[R(B), R(A)]),
+            ADD_B_B => alu(&[R(B), R(B)]),
+            ADD_B_C => alu(&[R(B), R(C)]),
+            ADD_B_SP => alu(&[R(B), R(Sp)]),
+            ADD_C_A => alu(&[R(C), R(A)]),
+            ADD_C_B => alu(&[R(C), R(B)]),
+            ADD_C_C => alu(&[R(C), R(C)]),
+            ADD_C_SP => alu(&[R(C), R(Sp)]),
+
+            ADDI_A => alu(&[Imm, R(A)]),
+            ADDI_B => alu(&[Imm, R(B)]),
+            ADDI_C => alu(&[Imm, R(C)]),
+            ADDI_SP => alu(&[Imm, R(Sp)]),
+
+            ADDC_A_A => alu(&[R(A), R(A)]),
+            ADDC_A_B => alu(&[R(A), R(B)]),
+            ADDC_A_C => alu(&[R(A), R(C)]),
+            ADDC_A_SP => alu(&[R(A), R(Sp)]),
+            ADDC_B_A => alu(&[R(B), R(A)]),
+            ADDC_B_B => alu(&[R(B), R(B)]),
+            ADDC_B_C => alu(&[R(B), R(C)]),
+            ADDC_B_SP => alu(&[R(B), R(Sp)]),
+            ADDC_C_A => alu(&[R(C), R(A)]),
+            ADDC_C_B => alu(&[R(C), R(B)]),
+            ADDC_C_C => alu(&[R(C), R(C)]),
+            ADDC_C_SP => alu(&[R(C), R(Sp)]),
+
+            ADDCI_A => alu(&[Imm, R(A)]),
+            ADDCI_B => alu(&[Imm, R(B)]),
+            ADDCI_C => alu(&[Imm, R(C)]),
+            ADDCI_SP => alu(&[Imm, R(Sp)]),
+
+            SUB_B_A => alu(&[R(B), R(A)]),
+            SUB_C_A => alu(&[R(C), R(A)]),
+            SUB_A_B => alu(&[R(A), R(B)]),
+            SUB_C_B => alu(&[R(C), R(B)]),
+            SUB_A_C => alu(&[R(A), R(C)]),
+            SUB_B_C => alu(&[R(B), R(C)]),
+            SUB_A_SP => alu(&[R(A), R(Sp)]),
+            SUB_B_SP => alu(&[R(B), R(Sp)]),
+            SUB_C_SP => alu(&[R(C), R(Sp)]),
+
+            SUBI_A => alu(&[Imm, R(A)]),
+            SUBI_B => alu(&[Imm, R(B)]),
+            SUBI_C => alu(&[Imm, R(C)]),
+            SUBI_SP => alu(&[Imm, R(Sp)]),
+
+            SUBB_B_A => alu(&[R(B), R(A)]),
+            SUBB_C_A => alu(&[R(C), R(A)]),
+            SUBB_A_B => alu(&[R(A), R(B)]),
+            SUBB_C_B => alu(&[R(C), R(B)]),
+            SUBB_A_C => alu(&[R(A), R(C)]),
+            SUBB_B_C => alu(&[R(B), R(C)]),
+            SUBB_A_SP => alu(&[R(A), R(Sp)]),
+            SUBB_B_SP => alu(&[R(B), R(Sp)]),
+            SUBB_C_SP => alu(&[R(C), R(Sp)]),
+
+            SUBBI_A => alu(&[Imm, R(A)]),
+            SUBBI_B => alu(&[Imm, R(B)]),
+            SUBBI_C => alu(&[Imm, R(C)]),
+            SUBBI_SP => alu(&[Imm, R(Sp)]),
+
+            AND_B_A => alu(&[R(B), R(A)]),
+            AND_C_A => alu(&[R(C), R(A)]),
+            AND_A_B => alu(&[R(A), R(B)]),
+            AND_C_B => alu(&[R(C), R(B)]),
+            AND_A_C => alu(&[R(A), R(C)]),
+            AND_B_C => alu(&[R(B), R(C)]),
+
+            ANI_A => alu(&[Imm, R(A)]),
+            ANI_B => alu(&[Imm, R(B)]),
+            ANI_C => alu(&[Imm, R(C)]),
+
+            OR_B_A => alu(&[R(B), R(A)]),
+            OR_C_A => alu(&[R(C), R(A)]),
+            OR_A_B => alu(&[R(A), R(B)]),
+            OR_C_B => alu(&[R(C), R(B)]),
+            OR_A_C => alu(&[R(A), R(C)]),
+            OR_B_C => alu(&[R(B), R(C)]),
+
+            ORI_A => alu(&[Imm, R(A)]),
+            ORI_B => alu(&[Imm, R(B)]),
+            ORI_C => alu(&[Imm, R(C)]),
+
+            XOR_B_A => alu(&[R(B), R(A)]),
+            XOR_C_A => alu(&[R(C), R(A)]),
+            XOR_A_B => alu(&[R(A), R(B)]),
+            XOR_C_B => alu(&[R(C), R(B)]),
+            XOR_A_C => alu(&[R(A), R(C)]),
+            XOR_B_C => alu(&[R(B), R(C)]),
+
+            XRI_A => alu(&[Imm, R(A)]),
+            XRI_B => alu(&[Imm, R(B)]),
+            XRI_C => alu(&[Imm, R(C)]),
+
+            NOT_A => alu(&[R(A)]),
+            NOT_B => alu(&[R(B)]),
+            NOT_C => alu(&[R(C)]),
+
+            NEG_A => alu(&[R(A)]),
+            NEG_B => alu(&[R(B)]),
+            NEG_C => alu(&[R(C)]),
+
+            INR_A => alu(&[R(A)]),
+            INR_B => alu(&[R(B)]),
+            INR_C => alu(&[R(C)]),
+            INR_SP => alu(&[R(Sp)]),
+
+            INR2_A => alu(&[R(A)]),
+            INR2_B => alu(&[R(B)]),
+            INR2_C => alu(&[R(C)]),
+            INR2_SP => alu(&[R(Sp)]),
+
+            INR3_A => alu(&[R(A)]),
+            INR3_B => alu(&[R(B)]),
+            INR3_C => alu(&[R(C)]),
+            INR3_SP => alu(&[R(Sp)]),
+
+            DCR_A => alu(&[R(A)]),
+            DCR_B => alu(&[R(B)]),
+            DCR_C => alu(&[R(C)]),
+            DCR_SP => alu(&[R(Sp)]),
+
+            DCR2_A => alu(&[R(A)]),
+            DCR2_B => alu(&[R(B)]),
+            DCR2_C => alu(&[R(C)]),
+            DCR2_SP => alu(&[R(Sp)]),
+
+            DCR3_A => alu(&[R(A)]),
+            DCR3_B => alu(&[R(B)]),
+            DCR3_C => alu(&[R(C)]),
+            DCR3_SP => alu(&[R(Sp)]),
+
+            MOV_A_B => mov(&[R(A), R(B)]),
+            MOV_A_C => mov(&[R(A), R(C)]),
+            MOV_B_A => mov(&[R(B), R(A)]),
+            MOV_B_C => mov(&[R(B), R(C)]),
+            MOV_C_A => mov(&[R(C), R(A)]),
+            MOV_C_B => mov(&[R(C), R(B)]),
+            MOV_Z_A => mov(&[R(Z), R(A)]),
+            MOV_Z_B => mov(&[R(Z), R(B)]),
+            MOV_Z_C => mov(&[R(Z), R(C)]),
+            MOV_SP_A => mov(&[R(Sp), R(A)]),
+            MOV_SP_B => mov(&[R(Sp), R(B)]),
+            MOV_SP_C => mov(&[R(Sp), R(C)]),
+
+            MVI_A => mov(&[Imm, R(A)]),
+            MVI_B => mov(&[Imm, R(B)]),
+            MVI_C => mov(&[Imm, R(C)]),
+
+            LD_A_A => mov(&[R(A), R(A)]),
+            LD_B_A => mov(&[R(B), R(A)]),
+            LD_C_A => mov(&[R(C), R(A)]),
+            LD_A_B => mov(&[R(A), R(B)]),
+            LD_B_B => mov(&[R(B), R(B)]),
+            LD_C_B => mov(&[R(C), R(B)]),
+            LD_A_C => mov(&[R(A), R(C)]),
+            LD_B_C => mov(&[R(B), R(C)]),
+            LD_C_C => mov(&[R(C), R(C)]),
+
+            ST_A_A => uses(&[R(A), R(A)]),
+            ST_A_B => uses(&[R(A), R(B)]),
+            ST_A_C => uses(&[R(A), R(C)]),
+            ST_B_A => uses(&[R(B), R(A)]),
+            ST_B_B => uses(&[R(B), R(B)]),
+            ST_B_C => uses(&[R(B), R(C)]),
+            ST_C_A => uses(&[R(C), R(A)]),
+            ST_C_B => uses(&[R(C), R(B)]),
+            ST_C_C => uses(&[R(C), R(C)]),
+            ST_Z_A => uses(&[R(Z), R(A)]),
+            ST_Z_B => uses(&[R(Z), R(B)]),
+            ST_Z_C => uses(&[R(Z), R(C)]),
+
+            LDS_A => mov(&[Offset, R(A)]).reading(Sp),
+            LDS_B => mov(&[Offset, R(B)]).reading(Sp),
+            LDS_C => mov(&[Offset, R(C)]).reading(Sp),
+
+            STS_A => uses(&[R(A), Offset]).reading(Sp),
+            STS_B => uses(&[R(B), Offset]).reading(Sp),
+            STS_C => uses(&[R(C), Offset]).reading(Sp),
+            STS_Z => uses(&[R(Z), Offset]).reading(Sp),
+
+            STSI => uses(&[Imm, Offset]).reading(Sp),
+
+            CMP_A_B => uses(&[R(A), R(B)]),
+            CMP_A_C => uses(&[R(A), R(C)]),
+            CMP_A_Z => uses(&[R(A), R(Z)]),
+            CMP_B_A => uses(&[R(B), R(A)]),
+            CMP_B_C => uses(&[R(B), R(C)]),
+            CMP_B_Z => uses(&[R(B), R(Z)]),
+            CMP_C_A => uses(&[R(C), R(A)]),
+            CMP_C_B => uses(&[R(C), R(B)]),
+            CMP_C_Z => uses(&[R(C), R(Z)]),
+            CMP_Z_A => uses(&[R(Z), R(A)]),
+            CMP_Z_B => uses(&[R(Z), R(B)]),
+            CMP_Z_C => uses(&[R(Z), R(C)]),
+
+            CMPI_A_BYTE => uses(&[R(A), Imm]),
+            CMPI_BYTE_A => uses(&[Imm, R(A)]),
+            CMPI_B_BYTE => uses(&[R(B), Imm]),
+            CMPI_BYTE_B => uses(&[Imm, R(B)]),
+            CMPI_C_BYTE => uses(&[R(C), Imm]),
+            CMPI_BYTE_C => uses(&[Imm, R(C)]),
+
+            JMP => uses(&[Target]),
+
+            JE => uses(&[Target]),
+
+            JNE => uses(&[Target]),
+
+            JG => uses(&[Target]),
+
+            JGE => uses(&[Target]),
+
+            JL => uses(&[Target]),
+
+            JLE => uses(&[Target]),
+
+            JA => uses(&[Target]),
+
+            JAE => uses(&[Target]),
+
+            JB => uses(&[Target]),
+
+            JBE => uses(&[Target]),
+
+            CALL => uses(&[Target]).reading(Sp).writing(Sp),
+
+            RET => uses(&[]).reading(Sp).writing(Sp),
+
+            OUT_A => uses(&[R(A)]),
+            OUT_B => uses(&[R(B)]),
+            OUT_C => uses(&[R(C)]),
+
+            OUTI => uses(&[Imm]),
+
+            DIC => uses(&[Imm]),
+
+            DID => uses(&[Imm]),
+
+            DD_A => uses(&[R(A)]),
+            DD_B => uses(&[R(B)]),
+            DD_C => uses(&[R(C)]),
+
+            HLT => uses(&[]),
+
+            NOP => uses(&[]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::convert::TryFrom;
+    use Reg::*;
+
+    #[test]
+    fn move_semantics() {
+        let sem = MOV_SP_C.semantics();
+        assert_eq!(sem.operands, &[Operand::Reg(Sp), Operand::Reg(C)]);
+        assert_eq!(sem.reads, RegSet::of(&[Sp]));
+        assert_eq!(sem.writes, RegSet::of(&[C]));
+
+        let sem = MVI_B.semantics();
+        assert_eq!(sem.operands, &[Operand::Imm, Operand::Reg(B)]);
+        assert!(sem.reads.is_empty());
+        assert_eq!(sem.writes, RegSet::of(&[B]));
+    }
+
+    #[test]
+    fn add_semantics() {
+        let sem = ADD_A_B.semantics();
+        assert_eq!(sem.operands, &[Operand::Reg(A), Operand::Reg(B)]);
+        assert_eq!(sem.reads, RegSet::of(&[A, B]));
+        assert_eq!(sem.writes, RegSet::of(&[B]));
+
+        let sem = ADDI_SP.semantics();
+        assert_eq!(sem.reads, RegSet::of(&[Sp]));
+        assert_eq!(sem.writes, RegSet::of(&[Sp]));
+    }
+
+    #[test]
+    fn store_semantics() {
+        let sem = STS_A.semantics();
+        assert_eq!(sem.operands, &[Operand::Reg(A), Operand::Offset]);
+        assert_eq!(sem.reads, RegSet::of(&[A, Sp]));
+        assert!(sem.writes.is_empty());
+
+        let sem = STSI.semantics();
+        assert_eq!(sem.operands, &[Operand::Imm, Operand::Offset]);
+        assert_eq!(sem.reads, RegSet::of(&[Sp]));
+
+        let sem = ST_Z_C.semantics();
+        assert_eq!(sem.reads, RegSet::of(&[Z, C]));
+        assert!(sem.writes.is_empty());
+    }
+
+    #[test]
+    fn operands_match_instruction_size() {
+        for byte in 0x00..=0xc8u8 {
+            let op = Opcode::try_from(byte).unwrap();
+            let imm_bytes = op
+                .semantics()
+                .operands
+                .iter()
+                .filter(|operand| !matches!(operand, Operand::Reg(_)))
+                .count();
+            assert_eq!(imm_bytes + 1, op.instruction_size(), "{:?}", op);
+        }
+    }
+}
@@ -1,12 +1,20 @@
+use crate::cfg::Cfg;
 use crate::instr::Instruction::{self, *};
+use crate::listing::AddrRadix;
+use crate::opcode::Opcode;
+use std::cmp::Reverse;
+use std::convert::TryFrom;
 use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
 
 /// `BinaryStats` contains information about a given binary, such as:
 ///   - Number of instructions
 ///   - Size of program (bytes)
 ///   - Breakdown of bytes between opcodes/operands
 ///   - Breakdown of one-/two-/three-byte instructions
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+///   - Number of conditional branches, unconditional jumps, and calls
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
 pub struct BinaryStats {
     total_instrs: usize,
     total_bytes: usize,
@@ -15,23 +23,162 @@ pub struct BinaryStats {
     single_byte_instrs: usize,
     two_byte_instrs: usize,
     three_byte_instrs: usize,
+    conditional_branches: usize,
+    unconditional_jumps: usize,
+    calls: usize,
 }
 
 impl BinaryStats {
+    /// The header row of the CSV produced by [`to_csv`](BinaryStats::to_csv),
+    /// naming each column.
+    pub const CSV_HEADER: &'static str = "total_instrs,total_bytes,opcode_bytes,operand_bytes,\
+         single_byte_instrs,two_byte_instrs,three_byte_instrs,\
+         conditional_branches,unconditional_jumps,calls";
+
     /// Analyzes the given program to collect the statistics found in a `BinaryStats` struct.
     pub fn new(instrs: &[Instruction]) -> Self {
-        let sum_up = |f: fn(&Instruction) -> usize| instrs.iter().map(f).sum();
-        let count_instrs = |pred: fn(&&Instruction) -> bool| instrs.iter().filter(pred).count();
+        let mut acc = StatsAccumulator::new();
+        for ins in instrs {
+            acc.push(ins);
+        }
+        acc.finish()
+    }
+
+    /// Determines the average size of an instruction, in bytes.
+    pub fn avg_bytes_per_instr(&self) -> f64 {
+        self.total_bytes as f64 / self.total_instrs as f64
+    }
+
+    /// Determines how much larger the program is than if every instruction
+    /// were a single byte (the minimum), as a percentage. This is the average
+    /// overhead of encoding operands.
+    pub fn operand_overhead_pct(&self) -> f64 {
+        (self.operand_bytes as f64 / self.total_instrs as f64) * 100.0
+    }
+
+    /// Breaks down the instructions by size, as the percentage that are one,
+    /// two, and three bytes long. The percentages are out of the instructions
+    /// counted in the breakdown (every instruction, but no labels), so they
+    /// always sum to 100%, or are all 0% for an empty program.
+    pub fn size_breakdown_pct(&self) -> [f64; 3] {
+        let counts = [
+            self.single_byte_instrs,
+            self.two_byte_instrs,
+            self.three_byte_instrs,
+        ];
+        let total: usize = counts.iter().sum();
+        let mut pcts = [0.0; 3];
+        if total > 0 {
+            for (pct, count) in pcts.iter_mut().zip(&counts) {
+                *pct = (*count as f64 / total as f64) * 100.0;
+            }
+        }
+        pcts
+    }
+
+    /// Determines what percentage of an image of `image_bytes` bytes was
+    /// decoded into this program's instructions. The rest is whatever lenient
+    /// decoding stopped at, such as data or a truncated instruction. An empty
+    /// image is fully covered.
+    pub fn code_coverage_pct(&self, image_bytes: usize) -> f64 {
+        if image_bytes == 0 {
+            return 100.0;
+        }
+        (self.total_bytes as f64 / image_bytes as f64) * 100.0
+    }
+
+    /// Determines the number of conditional branches per 100 instructions, a
+    /// rough measure of how control-flow heavy the program is.
+    pub fn branch_density(&self) -> f64 {
+        (self.conditional_branches as f64 / self.total_instrs as f64) * 100.0
+    }
+
+    /// Renders every field as a single row of CSV (without a line break), in
+    /// the order of [`CSV_HEADER`](BinaryStats::CSV_HEADER), for collecting
+    /// the statistics of many programs into one table.
+    pub fn to_csv(&self) -> String {
+        [
+            self.total_instrs,
+            self.total_bytes,
+            self.opcode_bytes,
+            self.operand_bytes,
+            self.single_byte_instrs,
+            self.two_byte_instrs,
+            self.three_byte_instrs,
+            self.conditional_branches,
+            self.unconditional_jumps,
+            self.calls,
+        ]
+        .iter()
+        .map(|field| field.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+}
+
+/// Collects [`BinaryStats`] one instruction at a time, so that they can be
+/// computed while decoding, without holding the whole program in memory.
+///
+/// # Examples
+/// ```
+/// # use stew3d::for_each_instruction;
+/// # use stew3d::stats::{BinaryStats, StatsAccumulator};
+/// # use stew3d::disassemble;
+/// // mvi 10, a; call 5; hlt
+/// let bytes = [0x7f, 0x0a, 0xbc, 0x05, 0xc7];
+/// let mut acc = StatsAccumulator::new();
+/// for_each_instruction(&bytes, |ins| acc.push(ins)).unwrap();
+/// assert_eq!(acc.finish(), BinaryStats::new(&disassemble(&bytes).unwrap()));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StatsAccumulator {
+    stats: BinaryStats,
+}
+
+impl StatsAccumulator {
+    /// Starts with no instructions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts the next instruction (or label) of the program.
+    pub fn push(&mut self, ins: &Instruction) {
+        let stats = &mut self.stats;
+        stats.total_bytes += ins.size();
+        stats.opcode_bytes += ins.num_opcodes();
+        stats.operand_bytes += ins.num_operands();
+        if matches!(ins, Label(_, _)) {
+            return;
+        }
 
-        BinaryStats {
-            total_instrs: count_instrs(|ins| !matches!(ins, Label(_, _))),
-            total_bytes: sum_up(|ins| ins.size()),
-            opcode_bytes: sum_up(|ins| ins.num_opcodes()),
-            operand_bytes: sum_up(|ins| ins.num_operands()),
-            single_byte_instrs: count_instrs(|ins| ins.size() == 1),
-            two_byte_instrs: count_instrs(|ins| ins.size() == 2),
-            three_byte_instrs: count_instrs(|ins| ins.size() == 3),
+        // Every instruction (but no label) falls into exactly one size
+        // category, so that the breakdown partitions `total_instrs`.
+        stats.total_instrs += 1;
+        match ins.size() {
+            1 => stats.single_byte_instrs += 1,
+            2 => stats.two_byte_instrs += 1,
+            3 => stats.three_byte_instrs += 1,
+            size => unreachable!("`{}` is {} bytes long", ins.canonical(), size),
         }
+        if ins.is_conditional_branch() {
+            stats.conditional_branches += 1;
+        }
+        if matches!(ins, Jump(_, Opcode::JMP, _, _)) {
+            stats.unconditional_jumps += 1;
+        }
+        if ins.is_call() {
+            stats.calls += 1;
+        }
+    }
+
+    /// Gives the statistics of every instruction pushed so far.
+    pub fn finish(self) -> BinaryStats {
+        let stats = self.stats;
+        debug_assert_eq!(
+            stats.single_byte_instrs + stats.two_byte_instrs + stats.three_byte_instrs,
+            stats.total_instrs
+        );
+        stats
     }
 }
 
@@ -53,33 +200,288 @@ impl fmt::Display for BinaryStats {
             percentage(self.operand_bytes, self.total_bytes),
         )?;
 
+        writeln!(
+            f,
+            "Bytes/instr:  {:.2} ({:.2}% over the 1-byte minimum)",
+            self.avg_bytes_per_instr(),
+            self.operand_overhead_pct(),
+        )?;
+
+        let [single_pct, two_pct, three_pct] = self.size_breakdown_pct();
         writeln!(f, "Instruction breakdown:")?;
         writeln!(
             f,
             "  1-byte: {} ({:.2}%)",
-            self.single_byte_instrs,
-            percentage(self.single_byte_instrs, self.total_instrs),
+            self.single_byte_instrs, single_pct,
         )?;
+        writeln!(f, "  2-byte: {} ({:.2}%)", self.two_byte_instrs, two_pct)?;
         writeln!(
             f,
-            "  2-byte: {} ({:.2}%)",
-            self.two_byte_instrs,
-            percentage(self.two_byte_instrs, self.total_instrs),
+            "  3-byte: {} ({:.2}%)",
+            self.three_byte_instrs, three_pct,
         )?;
+
+        writeln!(f, "Control flow:")?;
         writeln!(
             f,
-            "  3-byte: {} ({:.2}%)",
-            self.three_byte_instrs,
-            percentage(self.three_byte_instrs, self.total_instrs),
+            "  Conditional branches: {} ({:.2} per 100 instructions)",
+            self.conditional_branches,
+            self.branch_density(),
+        )?;
+        writeln!(f, "  Unconditional jumps:  {}", self.unconditional_jumps)?;
+        writeln!(f, "  Calls:                {}", self.calls)
+    }
+}
+
+/// The orders in which an [`OpcodeHistogram`] report can list its rows.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HistogramOrder {
+    /// Most frequent first.
+    Count,
+    /// By opcode value.
+    Opcode,
+    /// Alphabetically by mnemonic, then by opcode value.
+    Mnemonic,
+}
+
+impl FromStr for HistogramOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "count" => Ok(HistogramOrder::Count),
+            "opcode" => Ok(HistogramOrder::Opcode),
+            "mnemonic" => Ok(HistogramOrder::Mnemonic),
+            _ => Err(format!("unknown sort order `{}`", s)),
+        }
+    }
+}
+
+/// `OpcodeHistogram` counts how many times each opcode is used in a program.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OpcodeHistogram {
+    /// The count of every opcode that appears at least once, by opcode value.
+    counts: Vec<(Opcode, usize)>,
+    total_instrs: usize,
+}
+
+impl OpcodeHistogram {
+    /// Counts the opcodes used by the given program.
+    pub fn new(instrs: &[Instruction]) -> Self {
+        let mut counts = [0; 256];
+        for ins in instrs {
+            if let Jump(_, op, _, _) | Instr(_, op, _) = ins {
+                counts[*op as usize] += 1;
+            }
+        }
+
+        let counts: Vec<_> = (0..=u8::MAX)
+            .filter(|&byte| counts[byte as usize] > 0)
+            .map(|byte| (Opcode::try_from(byte).unwrap(), counts[byte as usize]))
+            .collect();
+        let total_instrs = counts.iter().map(|(_, count)| count).sum();
+
+        OpcodeHistogram {
+            counts,
+            total_instrs,
+        }
+    }
+
+    /// Lists each opcode used with its count, in the given order. Ties are
+    /// broken by opcode value, so the order is always the same.
+    pub fn rows(&self, order: HistogramOrder) -> Vec<(Opcode, usize)> {
+        let mut rows = self.counts.clone();
+        match order {
+            HistogramOrder::Count => rows.sort_by_key(|&(_, count)| Reverse(count)),
+            HistogramOrder::Opcode => {}
+            HistogramOrder::Mnemonic => rows.sort_by_key(|(op, _)| op.to_string()),
+        }
+        rows
+    }
+
+    /// Renders the histogram as a table with one row per opcode, giving its
+    /// name, encoding, count, and percentage of all instructions. At most
+    /// `top` rows are included, if given.
+    pub fn report(&self, order: HistogramOrder, top: Option<usize>) -> String {
+        let rows = self.rows(order);
+        let top = top.unwrap_or(rows.len());
+
+        let mut report = String::from("Opcode frequency:\n");
+        for (op, count) in rows.into_iter().take(top) {
+            report.push_str(&format!(
+                "  {:12} 0x{:02x} {:5} ({:.2}%)\n",
+                format!("{:?}", op),
+                op.to_byte(),
+                count,
+                (count as f64 / self.total_instrs as f64) * 100.0,
+            ));
+        }
+        report
+    }
+}
+
+/// `OperandHistogram` counts how many times each byte value is used as an
+/// operand (an immediate or a jump target) in a program, which makes magic
+/// constants stand out.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OperandHistogram {
+    /// The count of every byte value used as an operand at least once, by
+    /// value.
+    counts: Vec<(u8, usize)>,
+    total_operands: usize,
+}
+
+impl OperandHistogram {
+    /// The number of rows in the report given by `Display`.
+    pub const REPORT_ROWS: usize = 5;
+
+    /// Counts the operand bytes of the given program. Opcode bytes aren't
+    /// counted.
+    pub fn new(instrs: &[Instruction]) -> Self {
+        let mut counts = [0; 256];
+        for ins in instrs {
+            // every byte after the opcode is an operand
+            for &byte in ins.to_bytes().iter().skip(ins.num_opcodes()) {
+                counts[byte as usize] += 1;
+            }
+        }
+
+        let counts: Vec<_> = (0..=u8::MAX)
+            .filter(|&byte| counts[byte as usize] > 0)
+            .map(|byte| (byte, counts[byte as usize]))
+            .collect();
+        let total_operands = counts.iter().map(|(_, count)| count).sum();
+
+        OperandHistogram {
+            counts,
+            total_operands,
+        }
+    }
+
+    /// Lists each operand value used with its count, most used first. Ties are
+    /// broken by value.
+    pub fn rows(&self) -> Vec<(u8, usize)> {
+        let mut rows = self.counts.clone();
+        rows.sort_by_key(|&(_, count)| Reverse(count));
+        rows
+    }
+}
+
+impl fmt::Display for OperandHistogram {
+    /// Lists the most used operand values, with their count and percentage of
+    /// all operands.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Top operand values:")?;
+        for (byte, count) in self.rows().into_iter().take(Self::REPORT_ROWS) {
+            writeln!(
+                f,
+                "  0x{:02x} {:5} ({:.2}%)",
+                byte,
+                count,
+                (count as f64 / self.total_operands as f64) * 100.0,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// `IoEffects` lists where a program performs I/O (see
+/// [`Opcode::is_io`]), for auditing what it can do besides compute.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IoEffects {
+    /// The address of every I/O instruction, in order.
+    pub addrs: Vec<usize>,
+}
+
+impl IoEffects {
+    /// Finds the I/O instructions in the given program.
+    pub fn new(instrs: &[Instruction]) -> Self {
+        let addrs = instrs
+            .iter()
+            .filter(|ins| matches!(ins, Instr(_, op, _) if op.is_io()))
+            .map(|ins| ins.addr())
+            .collect();
+        IoEffects { addrs }
+    }
+
+    /// Renders the number of I/O operations, then their addresses (in the
+    /// given base), if any.
+    pub fn report(&self, radix: AddrRadix) -> String {
+        let mut report = format!("I/O operations: {}", self.addrs.len());
+        if !self.addrs.is_empty() {
+            let addrs: Vec<_> = self.addrs.iter().map(|&a| radix.reference(a)).collect();
+            report.push_str(&format!(" (at {})", addrs.join(", ")));
+        }
+        report.push('\n');
+        report
+    }
+}
+
+/// `LongestBlock` describes the longest straight-line stretch of a program:
+/// the basic block with the most instructions, which is entered only at its
+/// start and left only at its end.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct LongestBlock {
+    /// The address of the block's first instruction.
+    pub start: usize,
+    /// The number of instructions in the block (not counting a label).
+    pub instrs: usize,
+    /// The size of the block, in bytes.
+    pub bytes: usize,
+}
+
+impl LongestBlock {
+    /// Finds the longest basic block of the given program, or the first of
+    /// them if there's a tie. An empty program has none.
+    pub fn new(instrs: &[Instruction]) -> Option<Self> {
+        Cfg::new(instrs)
+            .blocks
+            .into_iter()
+            .map(|block| LongestBlock {
+                start: block.start,
+                instrs: instrs[block.range]
+                    .iter()
+                    .filter(|ins| !matches!(ins, Label(_, _)))
+                    .count(),
+                bytes: block.end - block.start,
+            })
+            .max_by_key(|block| (block.instrs, Reverse(block.start)))
+    }
+
+    /// Renders the block's length, then its address in the given base.
+    pub fn report(&self, radix: AddrRadix) -> String {
+        format!(
+            "Longest straight-line run: {} instructions, {} bytes (at {})\n",
+            self.instrs,
+            self.bytes,
+            radix.reference(self.start)
         )
     }
 }
 
+/// Writes the report that `--stats` prints: the [`BinaryStats`] of the
+/// program, its I/O operations, its longest basic block, then its most used
+/// operand values, each followed by a blank line. Addresses are written in
+/// the given base.
+pub fn write_stats<W: Write>(
+    w: &mut W,
+    instrs: &[Instruction],
+    radix: AddrRadix,
+) -> io::Result<()> {
+    writeln!(w, "{}", BinaryStats::new(instrs))?;
+    writeln!(w, "{}", IoEffects::new(instrs).report(radix))?;
+    if let Some(longest) = LongestBlock::new(instrs) {
+        writeln!(w, "{}", longest.report(radix))?;
+    }
+    writeln!(w, "{}", OperandHistogram::new(instrs))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::instr::Operands::*;
     use crate::opcode::Opcode::*;
+    use crate::{disassemble_with, DecodeOptions};
 
     #[test]
     fn small_program() {
@@ -109,8 +511,113 @@ mod test {
                 single_byte_instrs: 3,
                 two_byte_instrs: 2,
                 three_byte_instrs: 0,
+                conditional_branches: 1,
+                unconditional_jumps: 0,
+                calls: 0,
             }
         );
+        assert_eq!(stats.branch_density(), 20.0);
+        assert_eq!(stats.avg_bytes_per_instr(), 1.4);
+        assert_eq!(stats.operand_overhead_pct(), 40.0);
+
+        assert_eq!(
+            BinaryStats::CSV_HEADER,
+            "total_instrs,total_bytes,opcode_bytes,operand_bytes,single_byte_instrs,\
+             two_byte_instrs,three_byte_instrs,conditional_branches,unconditional_jumps,calls"
+        );
+        assert_eq!(stats.to_csv(), "5,7,5,2,3,2,0,1,0,0");
+    }
+
+    #[test]
+    fn accumulates() {
+        // mvi 255, a; l0: out a; dcr a; cmp a, z; jne l0; call l0; stsi 1, 2; hlt
+        let instrs = crate::disassemble(&[
+            0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xbc, 0x02, 0x9e, 0x01, 0x02, 0xc7,
+        ])
+        .unwrap();
+
+        let mut acc = StatsAccumulator::new();
+        for ins in &instrs {
+            acc.push(ins);
+        }
+        assert_eq!(
+            acc.finish(),
+            BinaryStats {
+                total_instrs: 8,
+                total_bytes: 13,
+                opcode_bytes: 8,
+                operand_bytes: 5,
+                single_byte_instrs: 4,
+                two_byte_instrs: 3,
+                three_byte_instrs: 1,
+                conditional_branches: 1,
+                unconditional_jumps: 0,
+                calls: 1,
+            }
+        );
+        assert_eq!(StatsAccumulator::new().finish(), BinaryStats::default());
+    }
+
+    #[test]
+    fn code_coverage() {
+        // mvi 1, a; outi 5; then two bytes that aren't an opcode
+        let image = [0x7f, 0x01, 0xc1, 0x05, 0xff, 0xfe];
+        let lenient = DecodeOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let d = disassemble_with(&image, &lenient).unwrap();
+        let stats = BinaryStats::new(&d.instrs);
+
+        assert_eq!(d.total_bytes, 4);
+        assert_eq!(stats.total_bytes, 4);
+        assert!((stats.code_coverage_pct(image.len()) - 66.67).abs() < 0.01);
+        assert_eq!(stats.code_coverage_pct(4), 100.0);
+        assert_eq!(BinaryStats::new(&[]).code_coverage_pct(0), 100.0);
+    }
+
+    #[test]
+    fn size_breakdown_with_labels() {
+        // l0: mvi 1, a; l1: stsi 1, 2; l2: hlt
+        let instrs = [
+            Label(0x00, "l0".into()),
+            Instr(0x00, MVI_A, One(0x01)),
+            Label(0x02, "l1".into()),
+            Instr(0x02, STSI, Two(0x01, 0x02)),
+            Label(0x05, "l2".into()),
+            Instr(0x05, HLT, Zero),
+        ];
+        let stats = BinaryStats::new(&instrs);
+        assert_eq!(stats.total_instrs, 3);
+
+        let pcts = stats.size_breakdown_pct();
+        for pct in &pcts {
+            assert!((pct - 100.0 / 3.0).abs() < 1e-9, "{:?}", pcts);
+        }
+        assert!((pcts.iter().sum::<f64>() - 100.0).abs() < 1e-9);
+
+        assert_eq!(BinaryStats::new(&[]).size_breakdown_pct(), [0.0; 3]);
+    }
+
+    #[test]
+    fn size_breakdown_partitions_instructions() {
+        let programs: [&[u8]; 4] = [
+            &[0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7],
+            &[0x7f, 0x0a, 0xbc, 0x05, 0xc7, 0x0c, 0x04, 0xbd],
+            &[0xb1, 0x05, 0x9e, 0x03, 0x07, 0xc7],
+            &[],
+        ];
+        for bytes in &programs {
+            let instrs = crate::disassemble(bytes).unwrap();
+            let stats = BinaryStats::new(&instrs);
+            assert_eq!(
+                stats.single_byte_instrs + stats.two_byte_instrs + stats.three_byte_instrs,
+                stats.total_instrs,
+                "{:?}",
+                bytes
+            );
+            assert_eq!(stats.total_bytes, bytes.len());
+        }
     }
 
     #[test]
@@ -126,7 +633,100 @@ mod test {
                 single_byte_instrs: 0,
                 two_byte_instrs: 0,
                 three_byte_instrs: 0,
+                conditional_branches: 0,
+                unconditional_jumps: 0,
+                calls: 0,
             }
         );
     }
+
+    #[test]
+    fn io_effects() {
+        // 00: outi 5; 02: mvi 1, a; 04: out a; 05: hlt
+        let instrs = crate::disassemble(&[0xc1, 0x05, 0x7f, 0x01, 0xbe, 0xc7]).unwrap();
+        let io = IoEffects::new(&instrs);
+        assert_eq!(io.addrs, vec![0x00, 0x04]);
+        assert_eq!(
+            io.report(AddrRadix::Hex),
+            "I/O operations: 2 (at 0x00, 0x04)\n"
+        );
+        assert_eq!(io.report(AddrRadix::Dec), "I/O operations: 2 (at 0, 4)\n");
+
+        let none = IoEffects::new(&[Instr(0x00, HLT, Zero)]);
+        assert_eq!(none.report(AddrRadix::Hex), "I/O operations: 0\n");
+    }
+
+    #[test]
+    fn longest_block() {
+        // 00: mvi 255, a; l0: out a; dcr a; cmp a, z; jne l0; hlt
+        let instrs = crate::disassemble(&[0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7]).unwrap();
+        let longest = LongestBlock::new(&instrs).unwrap();
+        assert_eq!(
+            longest,
+            LongestBlock {
+                start: 0x02,
+                instrs: 4,
+                bytes: 5
+            }
+        );
+        assert_eq!(
+            longest.report(AddrRadix::Hex),
+            "Longest straight-line run: 4 instructions, 5 bytes (at 0x02)\n"
+        );
+        assert_eq!(
+            longest.report(AddrRadix::Dec),
+            "Longest straight-line run: 4 instructions, 5 bytes (at 2)\n"
+        );
+        assert_eq!(LongestBlock::new(&[]), None);
+    }
+
+    #[test]
+    fn opcode_histogram() {
+        // out a; dcr a; out a; dcr a; out b; hlt
+        let instrs = [
+            Instr(0x00, OUT_A, Zero),
+            Instr(0x01, DCR_A, Zero),
+            Instr(0x02, OUT_A, Zero),
+            Instr(0x03, DCR_A, Zero),
+            Instr(0x04, OUT_B, Zero),
+            Instr(0x05, HLT, Zero),
+        ];
+        let histogram = OpcodeHistogram::new(&instrs);
+
+        // DCR_A (0x67) comes before OUT_A (0xbe) on a tie
+        assert_eq!(
+            histogram.rows(HistogramOrder::Count),
+            vec![(DCR_A, 2), (OUT_A, 2), (OUT_B, 1), (HLT, 1)]
+        );
+        assert_eq!(
+            histogram.rows(HistogramOrder::Mnemonic),
+            vec![(DCR_A, 2), (HLT, 1), (OUT_A, 2), (OUT_B, 1)]
+        );
+        assert_eq!(
+            histogram.report(HistogramOrder::Opcode, Some(1)),
+            "Opcode frequency:\n  DCR_A        0x67     2 (33.33%)\n"
+        );
+    }
+
+    #[test]
+    fn operand_histogram() {
+        // mvi 255, a; mvi 255, b; cmpi a, 1; stsi 255, 1; l0: jne l0; hlt
+        let instrs = [
+            Instr(0x00, MVI_A, One(0xff)),
+            Instr(0x02, MVI_B, One(0xff)),
+            Instr(0x04, CMPI_A_BYTE, One(0x01)),
+            Instr(0x06, STSI, Two(0xff, 0x01)),
+            Label(0x09, "l0".into()),
+            Jump(0x09, JNE, 0x09, "l0".into()),
+            Instr(0x0b, HLT, Zero),
+        ];
+        let histogram = OperandHistogram::new(&instrs);
+
+        assert_eq!(histogram.rows(), vec![(0xff, 3), (0x01, 2), (0x09, 1)]);
+        assert_eq!(
+            histogram.to_string(),
+            "Top operand values:\n  0xff     3 (50.00%)\n  0x01     2 (33.33%)\n  \
+             0x09     1 (16.67%)\n"
+        );
+    }
 }
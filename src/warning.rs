@@ -0,0 +1,206 @@
+use crate::listing::AddrRadix;
+use crate::Error;
+use std::fmt;
+
+/// A non-fatal problem noticed in a program. Unlike an [`Error`](crate::Error),
+/// a warning doesn't stop disassembly; it's up to the caller to report it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// The program compares registers against immediates in both orders
+    /// (`cmpi a, byte` and `cmpi byte, a`), which is easy to misread. Holds the
+    /// address of the first compare written each way, register first.
+    MixedCompareOrder(usize, usize),
+    /// Two names were requested for the same address. Holds the address, the
+    /// name that was kept, and the name that was dropped.
+    DuplicateLabel(usize, String, String),
+    /// One name was requested for two different addresses. Holds the name,
+    /// the address it was kept for, and the address it was dropped for.
+    DuplicateSymbol(String, usize, usize),
+    /// A jump targets an address that isn't the start of an instruction, so
+    /// its label can't be placed. Holds the address of the jump and its target.
+    MisalignedTarget(usize, u8),
+    /// A user symbol names an address that isn't the start of an instruction,
+    /// so it doesn't appear in the listing. Holds the address and the name.
+    UnplacedLabel(usize, String),
+    /// A jump targets an address with a user symbol, so the symbol names it
+    /// in place of a generated label. Holds the address and the symbol.
+    SymbolReplacesLabel(usize, String),
+    /// The program has no `hlt` and doesn't end in a `jmp` to itself, so it
+    /// may run off the end. Holds the address of the last instruction.
+    NoHalt(usize),
+    /// An instruction has no effect, given the one just before it. Holds its
+    /// address and text.
+    Redundant(usize, String),
+    /// Two instructions in a row undo each other, like `not a; not a`, so the
+    /// pair has no effect (though neither alone could be removed). Holds the
+    /// address of each and their text.
+    Cancelling(usize, usize, String),
+    /// An `mvi` and the immediate arithmetic on the same register after it
+    /// could be a single `mvi`. Holds the address of the `mvi`, the text of
+    /// the pair, and the instruction they fold into.
+    Foldable(usize, String, String),
+    /// Decoding stopped early at an invalid instruction, in lenient mode.
+    /// Holds its address and the error it would otherwise have caused.
+    Undecodable(usize, Error),
+}
+
+impl Warning {
+    /// The name of every kind of warning, as given by [`kind`](Warning::kind),
+    /// in the order the variants are declared.
+    pub const KINDS: [&'static str; 11] = [
+        "mixed-compare-order",
+        "duplicate-label",
+        "duplicate-symbol",
+        "misaligned-target",
+        "unplaced-label",
+        "symbol-replaces-label",
+        "no-halt",
+        "redundant",
+        "cancelling",
+        "foldable",
+        "undecodable",
+    ];
+
+    /// The name of this kind of warning: its variant in kebab case (e.g.
+    /// `no-halt` for `NoHalt`), for referring to it on the command line.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::MixedCompareOrder(_, _) => "mixed-compare-order",
+            Self::DuplicateLabel(_, _, _) => "duplicate-label",
+            Self::DuplicateSymbol(_, _, _) => "duplicate-symbol",
+            Self::MisalignedTarget(_, _) => "misaligned-target",
+            Self::UnplacedLabel(_, _) => "unplaced-label",
+            Self::SymbolReplacesLabel(_, _) => "symbol-replaces-label",
+            Self::NoHalt(_) => "no-halt",
+            Self::Redundant(_, _) => "redundant",
+            Self::Cancelling(_, _, _) => "cancelling",
+            Self::Foldable(_, _, _) => "foldable",
+            Self::Undecodable(_, _) => "undecodable",
+        }
+    }
+
+    /// The address of the instruction (or label) a warning is about: for a mix
+    /// of compare orders, the first compare written the second way, and for a
+    /// name given to two addresses, the one it was dropped for.
+    pub fn addr(&self) -> usize {
+        match self {
+            Self::MixedCompareOrder(reg_first, imm_first) => *reg_first.max(imm_first),
+            Self::DuplicateLabel(addr, _, _) => *addr,
+            Self::DuplicateSymbol(_, _, dropped) => *dropped,
+            Self::MisalignedTarget(addr, _) => *addr,
+            Self::UnplacedLabel(addr, _) => *addr,
+            Self::SymbolReplacesLabel(addr, _) => *addr,
+            Self::NoHalt(last) => *last,
+            Self::Redundant(addr, _) => *addr,
+            Self::Cancelling(first, _, _) => *first,
+            Self::Foldable(addr, _, _) => *addr,
+            Self::Undecodable(addr, _) => *addr,
+        }
+    }
+}
+
+impl Warning {
+    /// Describes the problem, writing addresses in the given base.
+    pub fn message(&self, radix: AddrRadix) -> String {
+        let at = |addr: usize| radix.reference(addr);
+        match self {
+            Self::MixedCompareOrder(reg_first, imm_first) => format!(
+                "compares are written both register-first (at {}) and \
+                 immediate-first (at {}); consider using one order",
+                at(*reg_first),
+                at(*imm_first)
+            ),
+            Self::DuplicateLabel(addr, kept, dropped) => format!(
+                "address {} is labeled both `{}` and `{}`; keeping `{}`",
+                at(*addr),
+                kept,
+                dropped,
+                kept
+            ),
+            Self::DuplicateSymbol(name, kept, dropped) => format!(
+                "label `{}` is given to both {} and {}; keeping {}",
+                name,
+                at(*kept),
+                at(*dropped),
+                at(*kept)
+            ),
+            Self::MisalignedTarget(addr, target) => format!(
+                "jump at {} targets {}, which is not the start of an instruction",
+                at(*addr),
+                at(*target as usize)
+            ),
+            Self::UnplacedLabel(addr, name) => format!(
+                "label `{}` at {} is not at the start of an instruction",
+                name,
+                at(*addr)
+            ),
+            Self::SymbolReplacesLabel(addr, name) => format!(
+                "jump target {} is labeled with symbol `{}` instead of a generated label",
+                at(*addr),
+                name
+            ),
+            Self::NoHalt(last) => format!(
+                "program never halts and may run off the end after {}; \
+                 consider ending it with `hlt`",
+                at(*last)
+            ),
+            Self::Redundant(addr, ins) => format!(
+                "`{}` at {} has no effect after the instruction before it",
+                ins,
+                at(*addr)
+            ),
+            Self::Cancelling(first, second, ins) => format!(
+                "`{}` at {} and {} cancel each other out; consider removing both",
+                ins,
+                at(*first),
+                at(*second)
+            ),
+            Self::Foldable(addr, pair, folded) => format!(
+                "`{}` at {} could be folded into `{}`",
+                pair,
+                at(*addr),
+                folded
+            ),
+            Self::Undecodable(addr, e) => format!("stopped decoding at {}: {}", at(*addr), e),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    /// Describes the problem as [`message`](Warning::message) does, with
+    /// addresses in hex.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message(AddrRadix::Hex))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kinds() {
+        // one of each variant, in order
+        let warnings = [
+            Warning::MixedCompareOrder(0x00, 0x02),
+            Warning::DuplicateLabel(0x00, String::from("a"), String::from("b")),
+            Warning::DuplicateSymbol(String::from("a"), 0x00, 0x02),
+            Warning::MisalignedTarget(0x00, 0x01),
+            Warning::UnplacedLabel(0x01, String::from("a")),
+            Warning::SymbolReplacesLabel(0x00, String::from("a")),
+            Warning::NoHalt(0x00),
+            Warning::Redundant(0x00, String::from("nop")),
+            Warning::Cancelling(0x00, 0x01, String::from("not a")),
+            Warning::Foldable(0x00, String::from("a"), String::from("b")),
+            Warning::Undecodable(0x00, Error::InvalidOpcode(0xff, 0x00)),
+        ];
+        let kinds: Vec<_> = warnings.iter().map(Warning::kind).collect();
+        assert_eq!(kinds, Warning::KINDS);
+
+        assert_eq!(
+            warnings[3].message(AddrRadix::Dec),
+            "jump at 0 targets 1, which is not the start of an instruction"
+        );
+        assert_eq!(warnings[3].message(AddrRadix::Hex), warnings[3].to_string());
+    }
+}
@@ -0,0 +1,691 @@
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Output, Stdio};
+
+/// Runs the `stew3d` binary with the given arguments, feeding `stdin` to it.
+/// A run may exit (e.g. rejecting its arguments) before reading all of its
+/// input, which is fine.
+fn stew3d(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_stew3d"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn stew3d");
+
+    match child.stdin.take().unwrap().write_all(stdin) {
+        Err(e) if e.kind() != ErrorKind::BrokenPipe => panic!("failed to write stdin: {}", e),
+        _ => {}
+    }
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn json_error_for_invalid_opcode() {
+    // df is above OPCODE_MAX
+    let out = stew3d(&["--error-format", "json"], &[0x80, 0x05, 0xc5, 0xdf, 0xc7]);
+
+    assert_eq!(out.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap().trim_end(),
+        r#"{"kind": "invalid_opcode", "address": 3, "opcode": 223, "message": "invalid opcode encountered at byte 3: `df` (opcodes range from 00 to c8)"}"#
+    );
+}
+
+#[test]
+fn hint_for_invalid_opcode() {
+    let out = stew3d(&[], &[0x80, 0x05, 0xc5, 0xdf, 0xc7]);
+
+    assert_eq!(out.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(out.stderr).unwrap(),
+        "Error: invalid opcode encountered at byte 3: `df` (opcodes range from 00 to c8)\n\
+         hint: use --lenient to stop decoding at the invalid byte with a warning, instead of failing\n"
+    );
+}
+
+#[test]
+fn exit_code_for_unexpected_eof() {
+    // 97 (lds byte, a) expects a byte operand
+    let out = stew3d(&[], &[0xc8, 0xc8, 0x6f, 0x97]);
+
+    assert_eq!(out.status.code(), Some(3));
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .starts_with("Error: "));
+}
+
+#[test]
+fn entry_label_starts_listing() {
+    // mvi 10, a; hlt
+    let bytes = [0x7f, 0x0a, 0xc7];
+
+    let out = stew3d(&[], &bytes);
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let first = stdout.lines().find(|line| line.contains('|')).unwrap();
+    assert_eq!(first, "00:             | entry:");
+
+    let out = stew3d(&["--no-entry-label"], &bytes);
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(!stdout.contains("entry:"));
+}
+
+#[test]
+fn quiet_prints_only_listing() {
+    // mvi 10, a; call 5; hlt; addi 4, a; ret
+    let bytes = [0x7f, 0x0a, 0xbc, 0x05, 0xc7, 0x0c, 0x04, 0xbd];
+
+    let plain = String::from_utf8(stew3d(&["--stats"], &bytes).stdout).unwrap();
+    let quiet = String::from_utf8(stew3d(&["-q", "--stats"], &bytes).stdout).unwrap();
+
+    let listing: Vec<_> = plain.lines().filter(|line| line.contains('|')).collect();
+    assert_eq!(quiet.lines().collect::<Vec<_>>(), listing);
+    assert!(plain.contains("Disassembly of file"));
+}
+
+#[test]
+fn diff_against_other_file() {
+    // the other program has a `nop` inserted before the `hlt`
+    let other = std::env::temp_dir().join(format!("stew3d-diff-{}.bin", std::process::id()));
+    std::fs::write(&other, [0xbe, 0xc8, 0xc7]).unwrap();
+
+    let out = stew3d(&["--diff", other.to_str().unwrap()], &[0xbe, 0xc7]);
+    std::fs::remove_file(&other).unwrap();
+
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "  00:  00:  | entry:\n  00:  00:  |   out a\n+      01:  |   nop\n  01:  02:  |   hlt\n"
+    );
+}
+
+#[test]
+fn stdin_is_read_as_binary() {
+    // addi 10, b; addi 13, b -- the bytes of a CRLF and LFCR
+    let out = stew3d(&["-q", "--no-entry-label"], &[0x0d, 0x0a, 0x0d, 0x0d, 0xc7]);
+
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "00:    0d 0a    |   addi 10, b\n02:    0d 0d    |   addi 13, b\n04:    c7       |   hlt\n"
+    );
+}
+
+#[test]
+fn verbose_marks_tail_calls() {
+    // call 5; jmp 5; hlt; ret
+    let out = stew3d(&["-q", "-v"], &[0xbc, 0x05, 0xb1, 0x05, 0xc7, 0xbd]);
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(
+        lines[2],
+        "02:    b1 05    |   jmp l0   ; -> 0x05; tail call to l0"
+    );
+    assert!(!lines[1].contains("tail call"));
+}
+
+#[test]
+fn histogram_top_rows() {
+    // out a; dcr a; out a; dcr a; out a; out b; hlt
+    let out = stew3d(
+        &["--histogram", "--top", "2"],
+        &[0xbe, 0x67, 0xbe, 0x67, 0xbe, 0xbf, 0xc7],
+    );
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let report: Vec<_> = stdout
+        .lines()
+        .skip_while(|line| *line != "Opcode frequency:")
+        .take_while(|line| !line.is_empty())
+        .collect();
+    assert_eq!(
+        report,
+        vec![
+            "Opcode frequency:",
+            "  OUT_A        0xbe     3 (42.86%)",
+            "  DCR_A        0x67     2 (28.57%)",
+        ]
+    );
+}
+
+#[test]
+fn hash_in_header() {
+    let out = stew3d(&["--hash"], &[0xc1, 0x01, 0xc7]);
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().take(4).collect();
+    assert_eq!(
+        lines,
+        vec![
+            "",
+            "Disassembly of file `stdin` (3 bytes)",
+            "sha256: b4bdf3c4cc25d5aa80d1852d1cced8e3c7c2364e992231eec0022600d99ef56f",
+            "",
+        ]
+    );
+}
+
+#[test]
+fn annotations_appear_on_their_line() {
+    let notes = std::env::temp_dir().join(format!("stew3d-notes-{}.txt", std::process::id()));
+    std::fs::write(&notes, "0x02: main loop start\n").unwrap();
+
+    // mvi 255, a; l0: out a; dcr a; cmp a, z; jne l0; hlt
+    let bytes = [0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7];
+    let out = stew3d(&["-q", "--annotate", notes.to_str().unwrap()], &bytes);
+    std::fs::remove_file(&notes).unwrap();
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let annotated: Vec<_> = stdout
+        .lines()
+        .filter(|line| line.contains("main loop start"))
+        .collect();
+    assert_eq!(
+        annotated,
+        vec!["02:    be       |   out a   ; main loop start"]
+    );
+}
+
+#[test]
+fn entrypoints_skip_data() {
+    let entries = std::env::temp_dir().join(format!("stew3d-entries-{}.txt", std::process::id()));
+    std::fs::write(&entries, "0x05  # the subroutine\n").unwrap();
+
+    // hlt; then data: 0x7f 0xbd 0xbd 0xbd; out a; ret
+    let bytes = [0xc7, 0x7f, 0xbd, 0xbd, 0xbd, 0xbe, 0xbd];
+    let out = stew3d(
+        &[
+            "-q",
+            "--no-bytes",
+            "--entrypoints",
+            entries.to_str().unwrap(),
+        ],
+        &bytes,
+    );
+    std::fs::remove_file(&entries).unwrap();
+
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("out a"));
+    assert!(!stdout.contains("mvi"));
+}
+
+#[test]
+fn stack_slots_spelled_out() {
+    // stsi 5, 255; lds 2, b; hlt
+    let out = stew3d(
+        &["-q", "--no-bytes", "-vv"],
+        &[0x9e, 0x05, 0xff, 0x98, 0x02, 0xc7],
+    );
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("  stsi 5, 255   ; stores 5 at sp+255\n"));
+    assert!(stdout.contains("  lds 2, b   ; loads b from sp+2\n"));
+}
+
+#[test]
+fn sorted_by_opcode() {
+    // mvi 255, a; l0: out a; dcr a; cmp a, z; jne l0; out a; hlt
+    let bytes = [0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xbe, 0xc7];
+    let out = stew3d(&["-q", "--sort-by", "opcode"], &bytes);
+
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "03:    67       |   dcr a",
+            "00:    7f ff    |   mvi 255, a",
+            "04:    a1       |   cmp a, z",
+            "05:    b3 02    |   jne l0",
+            "02:    be       |   out a",
+            "07:    be       |   out a",
+            "08:    c7       |   hlt",
+        ]
+    );
+}
+
+/// mvi 10, a; call l0; hlt; l0: addi 4, a; ret
+const SIMPLE: [u8; 8] = [0x7f, 0x0a, 0xbc, 0x05, 0xc7, 0x0c, 0x04, 0xbd];
+
+#[test]
+fn only_labels() {
+    let out = stew3d(&["--only-labels"], &SIMPLE);
+
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "0x00 entry\n0x05 l0\n"
+    );
+}
+
+#[test]
+fn only_labels_as_symbols() {
+    // jmp l0; 14 nops; l0: hlt
+    let mut bytes = vec![0xb1, 0x10];
+    bytes.extend([0xc8; 14]);
+    bytes.push(0xc7);
+
+    // the labels are in the format of --symbols, whatever the address base
+    let out = stew3d(&["--only-labels", "--addr-radix", "dec"], &bytes);
+    let labels = String::from_utf8(out.stdout).unwrap();
+    assert_eq!(labels, "0x00 entry\n0x10 l0\n");
+
+    let symbols = std::env::temp_dir().join(format!("stew3d-labels-{}.txt", std::process::id()));
+    std::fs::write(&symbols, labels.replace(" l0", " done")).unwrap();
+    let out = stew3d(
+        &[
+            "-q",
+            "--no-bytes",
+            "--addr-radix",
+            "dec",
+            "--symbols",
+            symbols.to_str().unwrap(),
+        ],
+        &bytes,
+    );
+    std::fs::remove_file(&symbols).unwrap();
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("0:   jmp done\n"));
+    assert!(stdout.contains("16: done:\n16:   hlt\n"));
+}
+
+#[test]
+fn only_code() {
+    let out = stew3d(&["-q", "--no-bytes", "--only-code"], &SIMPLE);
+
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "00:   mvi 10, a\n02:   call l0\n04:   hlt\n05:   addi 4, a\n07:   ret\n"
+    );
+}
+
+#[test]
+fn zero_padding() {
+    // outi 1; hlt; then 20 bytes of zero fill
+    let mut bytes = vec![0xc1, 0x01, 0xc7];
+    bytes.extend_from_slice(&[0x00; 20]);
+
+    let out = stew3d(&["-q", "--no-bytes", "--detect-padding"], &bytes);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "00: entry:\n00:   outi 1\n02:   hlt\n03:   .zero 20\n"
+    );
+
+    // too short to count as padding
+    let out = stew3d(
+        &["-q", "--detect-padding", "--padding-threshold", "21"],
+        &bytes,
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert_eq!(stdout.matches("add a, a").count(), 20);
+}
+
+#[test]
+fn verify_checksum() {
+    // outi 1; hlt; then the sum of those bytes
+    let bytes = [0xc1, 0x01, 0xc7, 0x89];
+
+    let out = stew3d(&["--verify-crc", "0x03"], &bytes);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "checksum 0x89 at 0x03 matches\n"
+    );
+
+    let out = stew3d(&["--verify-crc", "3", "--crc-algo", "crc8"], &bytes);
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("checksum mismatch"));
+}
+
+#[test]
+fn char_immediates() {
+    // mvi 65, a; out a; hlt
+    let out = stew3d(
+        &["-q", "--no-bytes", "--char-imm"],
+        &[0x7f, 0x41, 0xbe, 0xc7],
+    );
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("00:   mvi 65, a   ; 'A'\n"));
+    assert!(stdout.contains("02:   out a\n"));
+}
+
+#[test]
+fn sp_relative_syntax() {
+    // lds 4, a; hlt
+    let out = stew3d(&["-q", "--no-bytes", "--sp-syntax"], &[0x97, 0x04, 0xc7]);
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("00:   lds [sp+4], a\n"));
+    assert!(stdout.contains("02:   hlt\n"));
+}
+
+#[test]
+fn data_after_jump() {
+    // jmp l0; two bytes of data (decoding as mvi 65, a); l0: hlt
+    let bytes = [0xb1, 0x04, 0x7f, 0x41, 0xc7];
+
+    let out = stew3d(&["-q", "--no-bytes", "--data-after-jump"], &bytes);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "00: entry:\n00:   jmp l0\n02:   .byte 127, 65\n04: l0:\n04:   hlt\n"
+    );
+
+    let out = stew3d(&["-q", "--no-bytes"], &bytes);
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("02:   mvi 65, a\n"));
+
+    // jmp l0; a byte that isn't an opcode; l0: hlt
+    let out = stew3d(&["-q", "--data-after-jump", "--hex", "b1 03 ff c7"], &[]);
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "00:             | entry:\n\
+         00:    b1 03    |   jmp l0\n\
+         02:    ff       |   .byte 255\n\
+         03:             | l0:\n\
+         03:    c7       |   hlt\n"
+    );
+
+    let out = stew3d(
+        &[
+            "-q",
+            "--no-bytes",
+            "--data-after-jump",
+            "--data-directive",
+            "db",
+        ],
+        &bytes,
+    );
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("02:   db 127, 65\n"));
+}
+
+#[test]
+fn offset_column() {
+    // mvi 1, a; out a; mvi 2, b; hlt
+    let bytes = [0x7f, 0x01, 0xbe, 0x80, 0x02, 0xc7];
+    let out = stew3d(&["-q", "--no-bytes", "--offset-from", "0x02"], &bytes);
+    assert!(out.status.success());
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("-2:   mvi 1, a\n"));
+    assert!(stdout.contains("+0:   out a\n"));
+    assert!(stdout.contains("+3:   hlt\n"));
+}
+
+#[test]
+fn bitfields() {
+    // mvi 255, a; ani 0xaa, a; out a; hlt
+    let out = stew3d(
+        &["-q", "--no-bytes", "--bitfield", "--operand-radix", "hex"],
+        &[0x7f, 0xff, 0x40, 0xaa, 0xbe, 0xc7],
+    );
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("02:   ani 0xaa, a   ; [1010 1010]\n"));
+    assert!(stdout.contains("00:   mvi 0xff, a\n"));
+}
+
+#[test]
+fn zero_compares() {
+    // dcr a; cmp a, z; hlt
+    let out = stew3d(
+        &["-q", "--no-bytes", "--simplify-cmp-z"],
+        &[0x67, 0xa1, 0xc7],
+    );
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("01:   cmp a, z   ; a == 0?\n"));
+    assert!(stdout.contains("00:   dcr a\n"));
+}
+
+#[test]
+fn jump_tables() {
+    // four jmps in a row, to four hlts
+    let bytes = [
+        0xb1, 0x08, 0xb1, 0x09, 0xb1, 0x0a, 0xb1, 0x0b, 0xc7, 0xc7, 0xc7, 0xc7,
+    ];
+    let out = stew3d(&["-q", "--no-bytes", "--jump-tables"], &bytes);
+    assert!(out.status.success());
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("00:   jmp l0   ; jump table (4 entries): l0, l1, l2, l3\n"));
+    assert!(stdout.contains("02:   jmp l1\n"));
+}
+
+#[test]
+fn decode_window() {
+    // a 1-byte header, then outi 1; hlt, then trailing data
+    let bytes = [0xff, 0xc1, 0x01, 0xc7, 0xff, 0xfe];
+
+    assert!(!stew3d(&[], &bytes).status.success());
+    let out = stew3d(&["--no-bytes", "--skip", "1", "--length", "3"], &bytes);
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("(3 bytes)"));
+    assert!(stdout.ends_with("00: entry:\n00:   outi 1\n02:   hlt\n"));
+}
+
+#[test]
+fn relative_targets() {
+    // mvi 255, a; l0: out a; dcr a; cmp a, z; jne l0; hlt
+    let bytes = [0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7];
+    let out = stew3d(&["-q", "--no-bytes", "--rel-targets"], &bytes);
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("05:   jne l0   ; -5\n"));
+}
+
+#[test]
+fn emit_srec() {
+    let out = stew3d(&["--emit", "srec"], &[0xc1, 0x01, 0xc7]);
+
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        "S1060000C101C770\nS9030000FC\n"
+    );
+}
+
+#[test]
+fn lenient_coverage() {
+    // mvi 1, a; outi 5; then two bytes that aren't opcodes
+    let program = [0x7f, 0x01, 0xc1, 0x05, 0xff, 0xfe];
+    assert!(!stew3d(&[], &program).status.success());
+
+    let out = stew3d(&["--lenient", "--stats"], &program);
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("Code coverage: 4 of 6 bytes (66.67%), 2 undecoded\n"));
+    assert!(String::from_utf8(out.stderr)
+        .unwrap()
+        .contains("stopped decoding at 0x04"));
+}
+
+#[test]
+fn emit_html() {
+    // mvi 1, c; jmp l0; l0: hlt
+    let out = stew3d(&["--emit", "html"], &[0x81, 0x01, 0xb1, 0x04, 0xc7]);
+
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains("<a href=\"#l0\""));
+    assert!(stdout.contains("id=\"l0\""));
+}
+
+#[test]
+fn emit_json() {
+    // outi 1; then an invalid byte
+    let out = stew3d(&["--emit", "json", "--lenient"], &[0xc1, 0x01, 0xff]);
+
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.contains(r#"{"addr": 0, "bytes": [193, 1], "text": "outi 1"}"#));
+    assert!(stdout.contains(r#"{"offset": 0, "kind": "opcode", "addr": 0}"#));
+    assert!(stdout.contains(r#"{"offset": 1, "kind": "operand", "addr": 0}"#));
+    assert!(stdout.contains(r#"{"offset": 2, "kind": "data", "addr": null}"#));
+}
+
+#[test]
+fn lint_notes_zero_register() {
+    // st z, a; cmp a, z; hlt
+    let out = stew3d(&["-q", "--no-bytes", "--lint"], &[0x94, 0xa1, 0xc7]);
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            "00: entry:",
+            "00:   st z, a   ; note: z is zero register",
+            "01:   cmp a, z",
+            "02:   hlt",
+        ]
+    );
+}
+
+#[test]
+fn stats_as_csv() {
+    // mvi 255, a; out a; dcr a; cmp a, z; jne 2; hlt
+    let program = [0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7];
+    let out = stew3d(&["--stats-format", "csv", "--csv-header"], &program);
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("total_instrs,total_bytes,"));
+    assert_eq!(lines[1], "6,8,6,2,4,2,0,1,0,0");
+}
+
+#[test]
+fn loops_are_annotated() {
+    // mvi 255, a; l0: out a; dcr a; cmp a, z; jne l0; hlt
+    let program = [0x7f, 0xff, 0xbe, 0x67, 0xa1, 0xb3, 0x02, 0xc7];
+    let out = stew3d(&["-q", "--no-bytes", "--loops"], &program);
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines[2], "02: l0:");
+    assert_eq!(
+        lines[3],
+        "02:   out a   ; loop header (body 0x02\u{2013}0x05)"
+    );
+    assert!(!lines[4].contains("loop"));
+}
+
+#[test]
+fn operands_in_binary() {
+    // ani 0xaa, a; hlt
+    let out = stew3d(&["-q", "--operand-radix", "bin"], &[0x40, 0xaa, 0xc7]);
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert_eq!(
+        stdout.lines().nth(1),
+        Some("00:    40 aa    |   ani 0b10101010, a")
+    );
+}
+
+#[test]
+fn decimal_addresses() {
+    // jmp l0; stsi 3, 7; l0: hlt
+    let out = stew3d(
+        &["-q", "--show-targets", "--addr-radix", "dec"],
+        &[0xb1, 0x05, 0x9e, 0x03, 0x07, 0xc7],
+    );
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines[1], "0:     b1 05    |   jmp l0   ; -> 5");
+    assert_eq!(lines[3], "5:              | l0:");
+    assert_eq!(lines[4], "5:     c7       |   hlt");
+}
+
+#[test]
+fn inline_warnings() {
+    // jmp 1; hlt
+    let program = [0xb1, 0x01, 0xc7];
+
+    let out = stew3d(&["-q", "--no-bytes"], &program);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.starts_with("warning: jump at 0x00 targets 0x01"));
+
+    let out = stew3d(&["-q", "--no-bytes", "--inline-warnings"], &program);
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(out.stderr.is_empty());
+    assert!(stdout
+        .lines()
+        .any(|line| line.starts_with("00:   jmp l0   ; warning: jump at 0x00 targets 0x01")));
+}
+
+#[test]
+fn allowed_warnings() {
+    // jmp 1; out a, which never halts
+    let program = [0xb1, 0x01, 0xbe];
+
+    let out = stew3d(&["-q", "--lint"], &program);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.contains("warning: program never halts"));
+    assert!(stderr.contains("warning: jump at 0x00 targets 0x01"));
+
+    let out = stew3d(&["-q", "--lint", "--allow", "no-halt"], &program);
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(!stderr.contains("never halts"));
+    assert!(stderr.contains("warning: jump at 0x00 targets 0x01"));
+
+    let out = stew3d(&["--allow", "no-hlt"], &program);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.contains("unknown warning `no-hlt`"));
+}
+
+#[test]
+fn demo() {
+    let out = stew3d(&["--demo"], &[]);
+    assert!(out.status.success());
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(stdout.starts_with("\nDisassembly of file `--demo` (8 bytes)\n"));
+    assert!(stdout.contains("Instructions: 6\n"));
+    assert!(stdout.ends_with(
+        "00:             | entry:\n\
+         00:    7f ff    |   mvi 255, a\n\
+         02:             | l0:\n\
+         02:    be       |   out a\n\
+         03:    67       |   dcr a\n\
+         04:    a1       |   cmp a, z\n\
+         05:    b3 02    |   jne l0\n\
+         07:    c7       |   hlt\n"
+    ));
+}
+
+#[test]
+fn assert_no_mnemonic() {
+    // mvi 1, a; out a; hlt
+    let out = stew3d(&["-q", "--assert-no", "out"], &[0x7f, 0x01, 0xbe, 0xc7]);
+    assert_eq!(out.status.code(), Some(1));
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert_eq!(stderr.trim_end(), "Error: found `out` at 0x02");
+
+    // mvi 1, a; hlt
+    let out = stew3d(&["-q", "--assert-no", "out"], &[0x7f, 0x01, 0xc7]);
+    assert!(out.status.success());
+}
+
+#[test]
+fn scan_for_signature() {
+    // a header that isn't code, then outi 5; hlt
+    let out = stew3d(&["--scan", "c1 ?? c7"], &[0xff, 0xfe, 0xc1, 0x05, 0xc7]);
+
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(out.status.success());
+    assert_eq!(stdout, "0x02: c1 05 c7\n  outi 5\n  hlt\n");
+}
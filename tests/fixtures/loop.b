@@ -0,0 +1 @@
+g
\ No newline at end of file
@@ -0,0 +1,53 @@
+use std::fs;
+use std::path::Path;
+use stew3d::disassemble;
+use stew3d::listing::{write_listing, AddrRadix, ListingOptions};
+use stew3d::program::Program;
+use stew3d::stats::write_stats;
+
+/// Renders everything the golden files pin down for a program: its listing
+/// with the default layout, followed by its statistics.
+fn render(bytes: &[u8]) -> String {
+    let program = Program::from(disassemble(bytes).unwrap());
+
+    let mut out = Vec::new();
+    write_listing(&mut out, &program, &ListingOptions::default(), &[]).unwrap();
+    out.push(b'\n');
+    write_stats(&mut out, &program, AddrRadix::Hex).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+/// Checks the rendering of every `tests/fixtures/NAME.b` against the
+/// committed `NAME.golden` beside it. After an intended change to the output,
+/// run with `UPDATE_GOLDEN=1` to rewrite the golden files, and review the
+/// diff.
+#[test]
+fn fixtures_match_golden_files() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+
+    let mut programs: Vec<_> = fs::read_dir(&fixtures)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension() == Some("b".as_ref()))
+        .collect();
+    programs.sort();
+    assert!(programs.len() >= 3, "missing fixtures in {:?}", fixtures);
+
+    let mut mismatched = Vec::new();
+    for program in programs {
+        let actual = render(&fs::read(&program).unwrap());
+        let golden = program.with_extension("golden");
+        if update {
+            fs::write(&golden, &actual).unwrap();
+        } else if fs::read_to_string(&golden).ok().as_deref() != Some(actual.as_str()) {
+            eprintln!("{} renders as:\n{}", program.display(), actual);
+            mismatched.push(golden);
+        }
+    }
+    assert!(
+        mismatched.is_empty(),
+        "output differs from {:?}; rerun with UPDATE_GOLDEN=1 if intended",
+        mismatched
+    );
+}
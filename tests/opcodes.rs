@@ -0,0 +1,31 @@
+use std::convert::TryFrom;
+use stew3d::instr::Instruction;
+use stew3d::opcode::Opcode;
+
+/// Checks that every byte is either a valid opcode, which the rest of the
+/// crate fully supports, or rejected as invalid.
+#[test]
+fn whole_opcode_space() {
+    for byte in 0..=u8::MAX {
+        let op = match Opcode::try_from(byte) {
+            Ok(op) => op,
+            Err(_) => {
+                assert!(byte > 0xc8, "0x{:02x} should be a valid opcode", byte);
+                continue;
+            }
+        };
+        assert!(byte <= 0xc8, "0x{:02x} should be an invalid opcode", byte);
+        assert_eq!(op.to_byte(), byte);
+
+        let size = op.instruction_size();
+        assert!((1..=3).contains(&size), "{:?} has size {}", op, size);
+
+        let ins = Instruction::example(op);
+        assert_eq!(ins.size(), size, "{:?} has the wrong operand shape", op);
+        assert!(!ins.to_string().trim().is_empty());
+
+        // Decoding the encoded example gives it back
+        let decoded = stew3d::decode_one(&ins.to_bytes(), 0).unwrap();
+        assert_eq!(decoded.to_bytes(), ins.to_bytes());
+    }
+}